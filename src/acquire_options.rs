@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+/// Limits how a transaction waits for a session from the pool.
+///
+/// By default a transaction waits as long as needed and there isn't
+/// any limit on how many transactions can be waiting. When the ledger
+/// throttles the driver this can pile up requests, so you can set a
+/// `timeout`, which makes the transaction fail with
+/// `QldbError::SessionAcquireTimeout`, and a maximum of `max_waiters`,
+/// which makes new transactions fail right away with
+/// `QldbError::PoolExhausted`.
+///
+/// They are set for the whole client with
+/// `QldbClientBuilder::acquire_options` and can be overridden for a
+/// single transaction with `QldbClient::transaction_with_options`.
+///
+/// ```rust
+/// use qldb::AcquireOptions;
+/// use std::time::Duration;
+///
+/// let options = AcquireOptions::new()
+///     .timeout(Duration::from_secs(2))
+///     .max_waiters(100);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AcquireOptions {
+    timeout: Option<Duration>,
+    max_waiters: Option<usize>,
+}
+
+impl AcquireOptions {
+    /// Options without any limit.
+    pub fn new() -> AcquireOptions {
+        AcquireOptions::default()
+    }
+
+    /// Maximum time waiting for a session.
+    pub fn timeout(mut self, timeout: Duration) -> AcquireOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum amount of transactions waiting for a session at the
+    /// same time.
+    pub fn max_waiters(mut self, max_waiters: usize) -> AcquireOptions {
+        self.max_waiters = Some(max_waiters);
+        self
+    }
+
+    pub fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn get_max_waiters(&self) -> Option<usize> {
+        self.max_waiters
+    }
+
+    /// Options of `self`, taking the ones not set from `defaults`.
+    pub(crate) fn or(&self, defaults: &AcquireOptions) -> AcquireOptions {
+        AcquireOptions {
+            timeout: self.timeout.or(defaults.timeout),
+            max_waiters: self.max_waiters.or(defaults.max_waiters),
+        }
+    }
+}
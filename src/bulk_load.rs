@@ -0,0 +1,298 @@
+use crate::{QldbError, QldbResult};
+use ion_binary_rs::{IonEncoder, IonValue};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Limits used for splitting the documents of
+/// `Transaction::insert_many` in statements and the ones of
+/// `QldbClient::bulk_load` in transactions.
+///
+/// The defaults follow the QLDB quotas: up to 40 documents and less
+/// than 4 MB per transaction. Change them if your ledger has other
+/// quotas or your documents are big.
+///
+/// ```rust
+/// use qldb::BulkLimits;
+///
+/// let limits = BulkLimits::new()
+///     .documents_per_statement(20)
+///     .bytes_per_transaction(1024 * 1024);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BulkLimits {
+    documents_per_statement: usize,
+    documents_per_transaction: usize,
+    bytes_per_transaction: usize,
+}
+
+impl BulkLimits {
+    pub fn new() -> BulkLimits {
+        BulkLimits::default()
+    }
+
+    /// Maximum amount of documents in one `INSERT` statement. Values
+    /// under 1 are treated as 1.
+    pub fn documents_per_statement(mut self, documents: usize) -> BulkLimits {
+        self.documents_per_statement = documents.max(1);
+        self
+    }
+
+    /// Maximum amount of documents inserted in one transaction, by
+    /// `bulk_load` or `insert_many`. Values under 1 are treated as 1.
+    pub fn documents_per_transaction(mut self, documents: usize) -> BulkLimits {
+        self.documents_per_transaction = documents.max(1);
+        self
+    }
+
+    /// Maximum size, Ion binary encoded, of the documents inserted in
+    /// one transaction, by `bulk_load` or `insert_many`. A document
+    /// bigger than this goes alone.
+    pub fn bytes_per_transaction(mut self, bytes: usize) -> BulkLimits {
+        self.bytes_per_transaction = bytes;
+        self
+    }
+
+    pub fn get_documents_per_statement(&self) -> usize {
+        self.documents_per_statement
+    }
+
+    pub fn get_documents_per_transaction(&self) -> usize {
+        self.documents_per_transaction
+    }
+
+    pub fn get_bytes_per_transaction(&self) -> usize {
+        self.bytes_per_transaction
+    }
+}
+
+impl Default for BulkLimits {
+    fn default() -> Self {
+        BulkLimits {
+            documents_per_statement: 40,
+            documents_per_transaction: 40,
+            // QLDB counts the metadata of the revisions in its 4 MB
+            bytes_per_transaction: 3 * 1024 * 1024,
+        }
+    }
+}
+
+/// Progress of `QldbClient::bulk_load`, given to the callback set
+/// with `BulkLoadOptions::on_progress` after every commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkLoadProgress {
+    loaded_documents: usize,
+    transactions: usize,
+    document_ids: Vec<String>,
+}
+
+impl BulkLoadProgress {
+    /// Documents of the input committed so far, counting the ones
+    /// skipped with `BulkLoadOptions::resume_from`. Giving it to
+    /// `resume_from` continues the load after them.
+    pub fn loaded_documents(&self) -> usize {
+        self.loaded_documents
+    }
+
+    /// Transactions committed by this `bulk_load` call.
+    pub fn transactions(&self) -> usize {
+        self.transactions
+    }
+
+    /// Ids of the documents inserted by the last transaction, in the
+    /// order of the input.
+    pub fn document_ids(&self) -> &[String] {
+        &self.document_ids
+    }
+
+    pub(crate) fn new(loaded_documents: usize) -> BulkLoadProgress {
+        BulkLoadProgress {
+            loaded_documents,
+            transactions: 0,
+            document_ids: vec![],
+        }
+    }
+
+    pub(crate) fn committed(&mut self, document_ids: Vec<String>) {
+        self.loaded_documents += document_ids.len();
+        self.transactions += 1;
+        self.document_ids = document_ids;
+    }
+}
+
+/// Function called by `QldbClient::bulk_load` after every commit.
+pub type BulkLoadCallback = Arc<dyn Fn(&BulkLoadProgress) + Send + Sync>;
+
+/// Options of `QldbClient::bulk_load`.
+///
+/// ```rust
+/// use qldb::BulkLoadOptions;
+/// use std::sync::Arc;
+///
+/// let options = BulkLoadOptions::new()
+///     .resume_from(1200)
+///     .on_progress(Arc::new(|progress| println!("{} loaded", progress.loaded_documents())));
+/// ```
+#[derive(Clone, Default)]
+pub struct BulkLoadOptions {
+    resume_from: usize,
+    on_progress: Option<BulkLoadCallback>,
+}
+
+impl BulkLoadOptions {
+    pub fn new() -> BulkLoadOptions {
+        BulkLoadOptions::default()
+    }
+
+    /// Skips the first `loaded_documents` documents of the input,
+    /// which were loaded by a previous call.
+    pub fn resume_from(mut self, loaded_documents: usize) -> BulkLoadOptions {
+        self.resume_from = loaded_documents;
+        self
+    }
+
+    /// Called after every committed transaction.
+    pub fn on_progress(mut self, callback: BulkLoadCallback) -> BulkLoadOptions {
+        self.on_progress = Some(callback);
+        self
+    }
+
+    pub fn get_resume_from(&self) -> usize {
+        self.resume_from
+    }
+
+    pub(crate) fn report(&self, progress: &BulkLoadProgress) {
+        if let Some(callback) = &self.on_progress {
+            callback(progress);
+        }
+    }
+}
+
+impl Debug for BulkLoadOptions {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("BulkLoadOptions")
+            .field("resume_from", &self.resume_from)
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
+}
+
+/// A document with its Ion binary encoding, which is what the QLDB
+/// quotas count and what is sent as param of the statement.
+#[derive(Debug, Clone)]
+pub(crate) struct EncodedDocument {
+    pub(crate) value: IonValue,
+    pub(crate) bytes: Vec<u8>,
+}
+
+impl EncodedDocument {
+    pub(crate) fn new(value: IonValue) -> EncodedDocument {
+        let mut encoder = IonEncoder::new();
+        encoder.add(value.clone());
+
+        EncodedDocument {
+            value,
+            bytes: encoder.encode(),
+        }
+    }
+}
+
+/// Groups documents in batches of up to `max_documents` and
+/// `max_bytes`, keeping their order.
+pub(crate) struct Batches<I: Iterator<Item = EncodedDocument>> {
+    documents: I,
+    max_documents: usize,
+    max_bytes: usize,
+    // The document that didn't fit in the previous batch
+    pending: Option<EncodedDocument>,
+}
+
+impl<I: Iterator<Item = EncodedDocument>> Batches<I> {
+    pub(crate) fn new(documents: I, max_documents: usize, max_bytes: usize) -> Batches<I> {
+        Batches {
+            documents,
+            max_documents,
+            max_bytes,
+            pending: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = EncodedDocument>> Iterator for Batches<I> {
+    type Item = Vec<EncodedDocument>;
+
+    fn next(&mut self) -> Option<Vec<EncodedDocument>> {
+        let mut batch = vec![];
+        let mut bytes = 0;
+
+        while batch.len() < self.max_documents {
+            let document = match self.pending.take().or_else(|| self.documents.next()) {
+                Some(document) => document,
+                None => break,
+            };
+
+            let size = document.bytes.len();
+
+            if !batch.is_empty() && bytes + size > self.max_bytes {
+                self.pending = Some(document);
+                break;
+            }
+
+            bytes += size;
+            batch.push(document);
+        }
+
+        if batch.is_empty() {
+            None
+        } else {
+            Some(batch)
+        }
+    }
+}
+
+/// Fails when the documents don't fit in one transaction. A single
+/// document always fits, as it cannot be split.
+pub(crate) fn check_transaction_limits(documents: &[EncodedDocument], limits: &BulkLimits) -> QldbResult<()> {
+    let bytes = documents.iter().map(|document| document.bytes.len()).sum();
+
+    let too_many = documents.len() > limits.documents_per_transaction;
+    let too_big = documents.len() > 1 && bytes > limits.bytes_per_transaction;
+
+    if too_many || too_big {
+        return Err(QldbError::BulkLimitsExceeded {
+            documents: documents.len(),
+            bytes,
+        });
+    }
+
+    Ok(())
+}
+
+/// Fails unless `table` is a valid QLDB table name: letters, digits and
+/// underscores, not starting with a digit and up to 128 characters. The
+/// name is written in the statement, so anything else could change it.
+pub(crate) fn check_table_name(table: &str) -> QldbResult<()> {
+    let mut chars = table.chars();
+
+    let valid_start = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_');
+    let valid_rest = chars.all(|char| char.is_ascii_alphanumeric() || char == '_');
+
+    if valid_start && valid_rest && table.len() <= 128 {
+        Ok(())
+    } else {
+        Err(QldbError::InvalidTableName(table.to_string()))
+    }
+}
+
+/// `INSERT` statement of a bag with `documents` params.
+pub(crate) fn insert_statement(table: &str, documents: usize) -> QldbResult<String> {
+    check_table_name(table)?;
+
+    Ok(format!(
+        "INSERT INTO {} << {} >>",
+        table,
+        vec!["?"; documents].join(", ")
+    ))
+}
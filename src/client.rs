@@ -1,13 +1,19 @@
-#[cfg(feature = "internal_pool_with_spawner")]
-use crate::session_pool::{SpawnerFnMonoMultithread, SpawnerSessionPool};
+use crate::bulk_load::{check_table_name, Batches, BulkLimits, BulkLoadOptions, BulkLoadProgress, EncodedDocument};
+#[cfg(all(feature = "rusoto", feature = "internal_pool_with_spawner"))]
+use crate::session_pool::SpawnerFnMonoMultithread;
+use crate::throttling::ConcurrencyLimiter;
 use crate::{
-    session_pool::{SessionPool, ThreadedSessionPool},
-    QldbError, QldbResult, QueryBuilder, Transaction,
+    session_pool::SessionPool,
+    transport::{QldbTransport, TransportError},
+    AcquireOptions, PoolCloseReport, PoolStats, QldbClientBuilder, QldbError, QldbResult, QueryBuilder, RetryPolicy,
+    ThrottlingPolicy, TimeoutPhase, Transaction,
 };
-use rusoto_core::{credential::ChainProvider, request::HttpClient, Region};
-use rusoto_qldb_session::QldbSessionClient;
+use async_io::Timer;
+use futures::future::{self, Either};
+use ion_binary_rs::IonValue;
 use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// It allows to start transactions. In QLDB all queries are transactions.
 /// So you always need to create a transaction for every query.
@@ -15,9 +21,13 @@ use std::sync::Arc;
 /// The recommended method is `transaction_within`.
 #[derive(Clone)]
 pub struct QldbClient {
-    client: Arc<QldbSessionClient>,
+    client: Arc<dyn QldbTransport>,
     _ledger_name: String,
     session_pool: Arc<dyn SessionPool>,
+    retry_policy: RetryPolicy,
+    acquire_options: AcquireOptions,
+    limiter: Arc<ConcurrencyLimiter>,
+    bulk_limits: BulkLimits,
 }
 
 impl QldbClient {
@@ -39,24 +49,9 @@ impl QldbClient {
     /// If it is not present it will fallback on the value associated with the current
     /// profile in ~/.aws/config or the file specified by the AWS_CONFIG_FILE environment
     /// variable. If that is malformed of absent it will fall back on Region::UsEast1
-    #[cfg(feature = "internal_pool_with_thread")]
+    #[cfg(all(feature = "rusoto", feature = "internal_pool_with_thread"))]
     pub async fn default(ledger_name: &str, max_sessions: u16) -> QldbResult<QldbClient> {
-        let region = Region::default();
-
-        let credentials = ChainProvider::default();
-
-        // TODO: Map error correctly
-        let http_client = HttpClient::new()?;
-
-        let client = Arc::new(QldbSessionClient::new_with(http_client, credentials, region));
-
-        let session_pool = Arc::new(ThreadedSessionPool::new(client.clone(), ledger_name, max_sessions));
-
-        Ok(QldbClient {
-            client,
-            _ledger_name: ledger_name.to_string(),
-            session_pool,
-        })
+        QldbClient::builder(ledger_name).max_sessions(max_sessions).build()
     }
 
     /// Creates a new QldbClient.
@@ -79,33 +74,124 @@ impl QldbClient {
     /// If it is not present it will fallback on the value associated with the current
     /// profile in ~/.aws/config or the file specified by the AWS_CONFIG_FILE environment
     /// variable. If that is malformed of absent it will fall back on Region::UsEast1
-    #[cfg(feature = "internal_pool_with_spawner")]
+    #[cfg(all(feature = "rusoto", feature = "internal_pool_with_spawner"))]
     pub async fn default_with_spawner(
         ledger_name: &str,
         max_sessions: u16,
         spawner: SpawnerFnMonoMultithread,
     ) -> QldbResult<QldbClient> {
-        let region = Region::default();
+        QldbClient::builder(ledger_name)
+            .max_sessions(max_sessions)
+            .build_with_spawner(spawner)
+    }
+
+    /// Creates a new QldbClient with the session pool running in the
+    /// current tokio runtime. It panics when called outside of a tokio
+    /// runtime, use `with_tokio_handle` in that case.
+    ///
+    /// The credentials and the region are taken from the environment,
+    /// as in `QldbClient::default`.
+    #[cfg(all(feature = "rusoto", feature = "tokio"))]
+    pub async fn with_tokio(ledger_name: &str, max_sessions: u16) -> QldbResult<QldbClient> {
+        QldbClient::with_tokio_handle(ledger_name, max_sessions, tokio::runtime::Handle::current()).await
+    }
 
-        let credentials = ChainProvider::default();
+    /// Same as `with_tokio` but the session pool runs in the runtime
+    /// of `handle`, so it works from threads outside of the runtime.
+    #[cfg(all(feature = "rusoto", feature = "tokio"))]
+    pub async fn with_tokio_handle(
+        ledger_name: &str,
+        max_sessions: u16,
+        handle: tokio::runtime::Handle,
+    ) -> QldbResult<QldbClient> {
+        QldbClient::builder(ledger_name)
+            .max_sessions(max_sessions)
+            .build_with_tokio(handle)
+    }
 
-        // TODO: Map error correctly
-        let http_client = HttpClient::new()?;
+    /// Creates a new QldbClient with the session pool running in
+    /// async-std.
+    ///
+    /// The credentials and the region are taken from the environment,
+    /// as in `QldbClient::default`.
+    #[cfg(all(feature = "rusoto", feature = "async-std"))]
+    pub async fn with_async_std(ledger_name: &str, max_sessions: u16) -> QldbResult<QldbClient> {
+        QldbClient::builder(ledger_name)
+            .max_sessions(max_sessions)
+            .build_with_async_std()
+    }
 
-        let client = Arc::new(QldbSessionClient::new_with(http_client, credentials, region));
+    /// Returns a builder that allows to configure the transport, the
+    /// region, the credentials and the session pool. Use
+    /// it when the defaults taken from the environment aren't enough,
+    /// for example when connecting to ledgers in several regions from
+    /// the same process.
+    pub fn builder(ledger_name: &str) -> QldbClientBuilder {
+        QldbClientBuilder::new(ledger_name)
+    }
 
-        let session_pool = Arc::new(SpawnerSessionPool::new(
-            client.clone(),
+    /// Creates a client that takes the sessions from your own
+    /// `SessionPool`. The transport is used for the transactions, so
+    /// the pool may share it or use its own. `max_sessions` is the
+    /// maximum size of your pool, which is also how many transactions
+    /// can run at the same time (see `concurrency_limit`).
+    ///
+    /// Use `QldbClientBuilder::build_with_pool` for setting the retry
+    /// policy, acquire options, etc.
+    pub fn with_pool(
+        transport: Arc<dyn QldbTransport>,
+        ledger_name: &str,
+        session_pool: Arc<dyn SessionPool>,
+        max_sessions: u16,
+    ) -> QldbClient {
+        QldbClient::new(
+            transport,
             ledger_name,
-            max_sessions,
-            spawner,
-        ));
+            session_pool,
+            RetryPolicy::default(),
+            AcquireOptions::default(),
+            ConcurrencyLimiter::new(&ThrottlingPolicy::default(), max_sessions.into()),
+            BulkLimits::default(),
+        )
+    }
 
-        Ok(QldbClient {
+    pub(crate) fn new(
+        client: Arc<dyn QldbTransport>,
+        ledger_name: &str,
+        session_pool: Arc<dyn SessionPool>,
+        retry_policy: RetryPolicy,
+        acquire_options: AcquireOptions,
+        limiter: ConcurrencyLimiter,
+        bulk_limits: BulkLimits,
+    ) -> QldbClient {
+        QldbClient {
             client,
             _ledger_name: ledger_name.to_string(),
             session_pool,
-        })
+            retry_policy,
+            acquire_options,
+            limiter: Arc::new(limiter),
+            bulk_limits,
+        }
+    }
+
+    /// Replaces the retry policy used by `transaction_within_retry`.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> QldbClient {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Current state of the session pool: idle and in use sessions,
+    /// requests waiting for a session, acquire latency, etc.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.session_pool.stats()
+    }
+
+    /// How many transactions can run at the same time right now. It
+    /// goes down when QLDB throttles the client and recovers as
+    /// transactions succeed. See `ThrottlingPolicy`.
+    pub fn concurrency_limit(&self) -> usize {
+        self.limiter.limit()
     }
 
     /// Shorthand method that creates a transaction and executes a query.
@@ -124,32 +210,109 @@ impl QldbClient {
 
     /// Starts a transaction and returns you the transaction handler. When
     /// using this method the transaction won't automatically commit or rollback
-    /// when finished. If they are left open they will be canceled when the
-    /// transaction times out on the DB side (30 seconds).
+    /// when finished. If the transaction is dropped while open it is rolled
+    /// back in the background.
     ///
     /// Use this method if you really need to use the transaction handler
     /// directly. If not, you may be better off using the method
     /// `transaction_within`.
     pub async fn transaction(&self) -> QldbResult<Transaction> {
-        let session = self.session_pool.get().await.map_err(QldbError::SessionPoolClosed)?;
+        self.start_transaction(&self.acquire_options, false).await
+    }
 
-        Transaction::new(self.client.clone(), self.session_pool.clone(), session, false).await
+    /// Same as `transaction`, but the options set here replace the
+    /// client's [AcquireOptions](crate::AcquireOptions) while waiting
+    /// for a session. The ones not set are taken from the client.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{AcquireOptions, QldbClient, QldbError, QldbResult};
+    /// use std::time::Duration;
+    ///
+    /// # async fn test(client: QldbClient) -> QldbResult<()> {
+    /// let options = AcquireOptions::new().timeout(Duration::from_millis(200));
+    ///
+    /// match client.transaction_with_options(options).await {
+    ///     Ok(transaction) => transaction.commit().await?,
+    ///     Err(QldbError::SessionAcquireTimeout(_)) => println!("Too busy, try later"),
+    ///     Err(error) => return Err(error),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn transaction_with_options(&self, options: AcquireOptions) -> QldbResult<Transaction> {
+        self.start_transaction(&options.or(&self.acquire_options), false).await
     }
 
     pub(crate) async fn auto_rollback_transaction(&self) -> QldbResult<Transaction> {
-        let session = self.session_pool.get().await.map_err(QldbError::SessionPoolClosed)?;
+        self.start_transaction(&self.acquire_options, true).await
+    }
+
+    /// Waits for a slot in the concurrency limit and then for a session.
+    /// The acquire timeout covers both.
+    async fn start_transaction(&self, options: &AcquireOptions, auto_rollback: bool) -> QldbResult<Transaction> {
+        let started = Instant::now();
+        let timeout = options.get_timeout();
+        let permit = self.limiter.acquire(timeout).await?;
 
-        Transaction::new(self.client.clone(), self.session_pool.clone(), session, true).await
+        let remaining = match timeout {
+            Some(timeout) => options.clone().timeout(timeout.saturating_sub(started.elapsed())),
+            None => options.clone(),
+        };
+
+        let session = match self.session_pool.get(&remaining).await {
+            Ok(session) => session,
+            // It reports the whole timeout, not what was left of it
+            Err(QldbError::SessionAcquireTimeout(left)) => {
+                return Err(QldbError::SessionAcquireTimeout(timeout.unwrap_or(left)));
+            }
+            Err(error) => {
+                self.limiter.record_error(&error, permit.acquired_at());
+                return Err(error);
+            }
+        };
+
+        Transaction::new(
+            self.client.clone(),
+            self.session_pool.clone(),
+            session,
+            auto_rollback,
+            permit,
+            self.bulk_limits,
+        )
+        .await
     }
 
-    /// It closes the session pool. Current transaction which already have a
-    /// session can work as normal, but new transaction (requiring a new session
-    /// id) will return error.
+    /// It closes the session pool. New transactions return
+    /// `QldbError::SessionPoolClosed` while the current ones can work as
+    /// normal. It ends every session in QLDB, instead of leaving them to
+    /// expire, and waits for the transactions to finish, all of it in at
+    /// most 30 seconds.
     ///
-    /// Call this method only when you are sure that all important work is
-    /// already commited to QLDB.
-    pub async fn close(&mut self) {
-        self.session_pool.close().await;
+    /// The returned report says how many sessions were ended cleanly and
+    /// how many were still in use when the timeout expired.
+    pub async fn close(&mut self) -> PoolCloseReport {
+        self.close_with_timeout(Duration::from_secs(30)).await
+    }
+
+    /// Same as `close` but taking at most `timeout` instead of 30
+    /// seconds.
+    ///
+    /// ```rust,no_run
+    /// use qldb::QldbClient;
+    /// use std::time::Duration;
+    ///
+    /// # async fn test(mut client: QldbClient) {
+    /// let report = client.close_with_timeout(Duration::from_secs(5)).await;
+    ///
+    /// println!(
+    ///     "{} sessions closed, {} abandoned",
+    ///     report.closed_sessions(),
+    ///     report.abandoned_sessions()
+    /// );
+    /// # }
+    /// ```
+    pub async fn close_with_timeout(&mut self, timeout: Duration) -> PoolCloseReport {
+        self.session_pool.close(timeout).await
     }
 
     /// It call the closure providing an already made transaction. Once the
@@ -175,4 +338,246 @@ impl QldbClient {
             }
         }
     }
+
+    /// Same as `transaction_within` but the whole transaction, from
+    /// waiting for a session to the commit, must finish within
+    /// `timeout`. Otherwise the transaction is rolled back in the
+    /// background, the session goes back to the pool and it returns
+    /// `QldbError::Timeout` with the phase in which the deadline expired.
+    ///
+    /// When it expires during the commit QLDB may have committed the
+    /// transaction anyway.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{QldbClient, QldbError, QldbResult, TimeoutPhase};
+    /// use std::time::Duration;
+    ///
+    /// # async fn test(client: QldbClient) -> QldbResult<()> {
+    /// let result = client
+    ///     .transaction_within_timeout(Duration::from_secs(5), |tx| async move {
+    ///         tx.query("UPDATE Cars SET sold = true").execute().await?;
+    ///         Ok(())
+    ///     })
+    ///     .await;
+    ///
+    /// if let Err(QldbError::Timeout { phase: TimeoutPhase::Commit, .. }) = result {
+    ///     println!("It may be committed, check it before trying again");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn transaction_within_timeout<F, R, FR>(&self, timeout: Duration, clousure: F) -> QldbResult<R>
+    where
+        R: std::fmt::Debug,
+        FR: Future<Output = QldbResult<R>>,
+        F: FnOnce(Transaction) -> FR,
+    {
+        let started = Instant::now();
+
+        let transaction = match future::select(Box::pin(self.transaction()), Timer::at(started + timeout)).await {
+            Either::Left((transaction, _)) => transaction?,
+            Either::Right(_) => {
+                return Err(QldbError::Timeout {
+                    elapsed: started.elapsed(),
+                    phase: TimeoutPhase::Acquire,
+                })
+            }
+        };
+
+        let result = async {
+            match clousure(transaction.clone()).await {
+                Ok(result) => {
+                    transaction.silent_commit().await?;
+                    Ok(result)
+                }
+                Err(error) => {
+                    transaction.silent_rollback().await?;
+                    Err(error)
+                }
+            }
+        };
+
+        transaction.within_deadline(started, timeout, result).await
+    }
+
+    /// Inserts the documents in `table` using as many transactions as
+    /// needed to keep each one under the [BulkLimits](crate::BulkLimits)
+    /// of the client. Every transaction is retried as in
+    /// `transaction_within_retry`.
+    ///
+    /// It returns the amount of documents of the input loaded. When a
+    /// transaction fails it returns `QldbError::BulkLoadInterrupted`
+    /// with the documents committed before it, so the load can be
+    /// continued with `BulkLoadOptions::resume_from`.
+    ///
+    /// A commit that fails without an answer from QLDB, like on network
+    /// errors, isn't retried, as QLDB may have committed it and the
+    /// retry would insert its documents twice. In that case check
+    /// whether the documents of the last transaction were inserted
+    /// before resuming.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{BulkLoadOptions, QldbClient, QldbError, QldbResult};
+    /// use qldb::ion::IonValue;
+    /// use std::sync::Arc;
+    ///
+    /// # async fn test(client: QldbClient, documents: Vec<IonValue>) -> QldbResult<()> {
+    /// let options = BulkLoadOptions::new()
+    ///     .on_progress(Arc::new(|progress| println!("{} loaded", progress.loaded_documents())));
+    ///
+    /// match client.bulk_load("Cars", documents.clone(), options).await {
+    ///     Ok(loaded) => println!("Done, {} documents", loaded),
+    ///     Err(QldbError::BulkLoadInterrupted { loaded_documents, .. }) => {
+    ///         let options = BulkLoadOptions::new().resume_from(loaded_documents);
+    ///         client.bulk_load("Cars", documents, options).await?;
+    ///     }
+    ///     Err(error) => return Err(error),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn bulk_load<D: Into<IonValue>>(
+        &self,
+        table: &str,
+        documents: impl IntoIterator<Item = D>,
+        options: BulkLoadOptions,
+    ) -> QldbResult<usize> {
+        check_table_name(table)?;
+
+        let mut progress = BulkLoadProgress::new(options.get_resume_from());
+
+        let documents = documents
+            .into_iter()
+            .skip(options.get_resume_from())
+            .map(|document| EncodedDocument::new(document.into()));
+
+        let batches = Batches::new(
+            documents,
+            self.bulk_limits.get_documents_per_transaction(),
+            self.bulk_limits.get_bytes_per_transaction(),
+        );
+
+        for batch in batches {
+            let result = self
+                .retry_transaction(false, |tx| {
+                    let batch = batch.clone();
+                    async move { tx.insert_encoded(table, batch).await }
+                })
+                .await;
+
+            match result {
+                Ok(document_ids) => {
+                    progress.committed(document_ids);
+                    options.report(&progress);
+                }
+                Err(error) => {
+                    return Err(QldbError::BulkLoadInterrupted {
+                        loaded_documents: progress.loaded_documents(),
+                        source: Box::new(error),
+                    })
+                }
+            }
+        }
+
+        Ok(progress.loaded_documents())
+    }
+
+    /// Same as `transaction_within` but, when the transaction fails with
+    /// an OCC conflict, an invalid session or a transient transport
+    /// error, it runs the closure again with a new transaction. That is
+    /// why the closure is `Fn` instead of `FnOnce`.
+    ///
+    /// The number of retries and the delay between them are configured
+    /// with the `RetryPolicy` given to `with_retry_policy`. When all the
+    /// attempts fail it returns `QldbError::TransactionRetriesExhausted`
+    /// with the amount of attempts made and the last error. Errors that
+    /// aren't retryable are returned as they are.
+    ///
+    /// A commit that fails without an answer from QLDB may have been
+    /// applied, so its error is returned instead of retrying it, unless
+    /// `RetryPolicy::retry_unknown_commits` is enabled.
+    pub async fn transaction_within_retry<F, R, FR>(&self, clousure: F) -> QldbResult<R>
+    where
+        R: std::fmt::Debug,
+        FR: Future<Output = QldbResult<R>>,
+        F: Fn(Transaction) -> FR,
+    {
+        self.retry_transaction(self.retry_policy.get_retry_unknown_commits(), clousure)
+            .await
+    }
+
+    /// `transaction_within_retry`, optionally without retrying the
+    /// commits that may have been applied.
+    async fn retry_transaction<F, R, FR>(&self, retry_unknown_commits: bool, clousure: F) -> QldbResult<R>
+    where
+        R: std::fmt::Debug,
+        FR: Future<Output = QldbResult<R>>,
+        F: Fn(Transaction) -> FR,
+    {
+        let mut attempts: u32 = 0;
+
+        loop {
+            attempts = attempts.saturating_add(1);
+
+            let error = match self.transaction_within_attempt(&clousure).await {
+                Ok(result) => return Ok(result),
+                Err(AttemptError::UnknownCommit(error)) if !retry_unknown_commits => return Err(error),
+                Err(AttemptError::UnknownCommit(error) | AttemptError::Failed(error)) => error,
+            };
+
+            if !error.is_retryable() {
+                return Err(error);
+            }
+
+            if attempts > self.retry_policy.get_max_retries() {
+                return Err(QldbError::TransactionRetriesExhausted {
+                    attempts,
+                    source: Box::new(error),
+                });
+            }
+
+            Timer::after(self.retry_policy.backoff(attempts)).await;
+        }
+    }
+
+    async fn transaction_within_attempt<F, R, FR>(&self, clousure: &F) -> Result<R, AttemptError>
+    where
+        R: std::fmt::Debug,
+        FR: Future<Output = QldbResult<R>>,
+        F: Fn(Transaction) -> FR,
+    {
+        let transaction = self.transaction().await.map_err(AttemptError::Failed)?;
+
+        let result = clousure(transaction.clone()).await;
+
+        match result {
+            Ok(result) => match transaction.silent_commit().await {
+                Ok(_) => Ok(result),
+                Err(error) => {
+                    // QLDB already discarded the transaction, but the
+                    // abort makes the session available again.
+                    let _ = transaction.silent_rollback().await;
+
+                    match error {
+                        QldbError::SendCommandError(TransportError::Dispatch(_) | TransportError::Unknown { .. }) => {
+                            Err(AttemptError::UnknownCommit(error))
+                        }
+                        error => Err(AttemptError::Failed(error)),
+                    }
+                }
+            },
+            Err(error) => {
+                let _ = transaction.silent_rollback().await;
+                Err(AttemptError::Failed(error))
+            }
+        }
+    }
+}
+
+/// Why an attempt of `transaction_within_retry` failed.
+enum AttemptError {
+    /// The commit failed without an answer from QLDB, so the
+    /// transaction may have been committed anyway.
+    UnknownCommit(QldbError),
+    Failed(QldbError),
 }
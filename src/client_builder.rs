@@ -0,0 +1,329 @@
+use crate::bulk_load::BulkLimits;
+use crate::pool_stats::report_pool_stats;
+#[cfg(feature = "internal_pool_with_thread")]
+use crate::session_pool::ThreadedSessionPool;
+use crate::session_pool::{PoolConfig, SessionPool};
+#[cfg(feature = "internal_pool_with_spawner")]
+use crate::session_pool::{SpawnerFnMonoMultithread, SpawnerSessionPool};
+use crate::throttling::ConcurrencyLimiter;
+#[cfg(feature = "rusoto")]
+use crate::transport::RusotoTransport;
+use crate::{
+    AcquireOptions, PoolStats, PoolStatsCallback, QldbClient, QldbError, QldbResult, QldbTransport, RetryPolicy,
+    ThrottlingPolicy,
+};
+#[cfg(feature = "rusoto")]
+use rusoto_core::credential::{AwsCredentials, ChainProvider, CredentialsError, ProvideAwsCredentials};
+#[cfg(feature = "rusoto")]
+use rusoto_core::{request::HttpClient, Client, Region};
+#[cfg(feature = "rusoto")]
+use rusoto_qldb_session::QldbSessionClient;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Configures and creates a [QldbClient](crate::QldbClient).
+///
+/// Every setting is optional. When nothing is provided the builder
+/// behaves like `QldbClient::default`: the region and the credentials
+/// are taken from the environment.
+///
+/// ```rust,no_run
+/// use qldb::{QldbClient, Region};
+/// use rusoto_core::credential::StaticProvider;
+/// # use eyre::Result;
+///
+/// # async fn test() -> Result<()> {
+/// let client = QldbClient::builder("rust-crate-test")
+///     .region(Region::EuWest1)
+///     .credentials(StaticProvider::new_minimal(
+///         "access_key".to_string(),
+///         "secret_key".to_string(),
+///     ))
+///     .max_sessions(50)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct QldbClientBuilder {
+    ledger_name: String,
+    transport: Option<Arc<dyn QldbTransport>>,
+    #[cfg(feature = "rusoto")]
+    region: Option<Region>,
+    #[cfg(feature = "rusoto")]
+    credentials: Option<DynCredentialsProvider>,
+    #[cfg(feature = "rusoto")]
+    session_client: Option<QldbSessionClient>,
+    pool_config: PoolConfig,
+    retry_policy: RetryPolicy,
+    acquire_options: AcquireOptions,
+    throttling_policy: ThrottlingPolicy,
+    bulk_limits: BulkLimits,
+    pool_stats_callback: Option<(Duration, PoolStatsCallback)>,
+}
+
+impl QldbClientBuilder {
+    pub(crate) fn new(ledger_name: &str) -> QldbClientBuilder {
+        QldbClientBuilder {
+            ledger_name: ledger_name.to_string(),
+            transport: None,
+            #[cfg(feature = "rusoto")]
+            region: None,
+            #[cfg(feature = "rusoto")]
+            credentials: None,
+            #[cfg(feature = "rusoto")]
+            session_client: None,
+            pool_config: PoolConfig::default(),
+            retry_policy: RetryPolicy::default(),
+            acquire_options: AcquireOptions::default(),
+            throttling_policy: ThrottlingPolicy::default(),
+            bulk_limits: BulkLimits::default(),
+            pool_stats_callback: None,
+        }
+    }
+
+    /// Uses the given transport to send the commands to QLDB. When
+    /// provided, the `region`, `credentials` and `session_client`
+    /// settings are ignored.
+    ///
+    /// It is required when the `rusoto` feature is disabled.
+    pub fn transport(mut self, transport: Arc<dyn QldbTransport>) -> QldbClientBuilder {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Region of the ledger. Use `Region::Custom` in order to connect
+    /// to a different endpoint.
+    ///
+    /// Defaults to `Region::default()`, which reads AWS_DEFAULT_REGION,
+    /// AWS_REGION or the current profile.
+    #[cfg(feature = "rusoto")]
+    pub fn region(mut self, region: Region) -> QldbClientBuilder {
+        self.region = Some(region);
+        self
+    }
+
+    /// Credentials used to sign the requests. Any rusoto credentials
+    /// provider works: static, profile, web identity, assume role, etc.
+    ///
+    /// Defaults to `ChainProvider::default()`.
+    #[cfg(feature = "rusoto")]
+    pub fn credentials<P>(mut self, credentials: P) -> QldbClientBuilder
+    where
+        P: ProvideAwsCredentials + Send + Sync + 'static,
+    {
+        self.credentials = Some(DynCredentialsProvider(Arc::new(credentials)));
+        self
+    }
+
+    /// Uses an already created QldbSessionClient. When provided, the
+    /// `region` and `credentials` settings are ignored.
+    #[cfg(feature = "rusoto")]
+    pub fn session_client(mut self, session_client: QldbSessionClient) -> QldbClientBuilder {
+        self.session_client = Some(session_client);
+        self
+    }
+
+    /// Maximum amount of sessions the pool will open. Defaults to 200.
+    pub fn max_sessions(mut self, max_sessions: u16) -> QldbClientBuilder {
+        self.pool_config.max_sessions = max_sessions;
+        self
+    }
+
+    /// Sessions older than this are closed with `EndSession` and
+    /// replaced by new ones. Defaults to 10 minutes.
+    pub fn max_session_age(mut self, max_session_age: Duration) -> QldbClientBuilder {
+        self.pool_config.max_session_age = max_session_age;
+        self
+    }
+
+    /// Sessions that aren't used for this long are closed, so idle
+    /// services don't keep sessions open in QLDB. The pool still keeps
+    /// `min_idle` sessions. Disabled by default.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> QldbClientBuilder {
+        self.pool_config.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Amount of idle sessions the pool keeps ready, so the first
+    /// transaction after a quiet period doesn't need to wait for a new
+    /// session. They count against `max_sessions`. Defaults to 0.
+    pub fn min_idle(mut self, min_idle: u16) -> QldbClientBuilder {
+        self.pool_config.min_idle = min_idle;
+        self
+    }
+
+    /// Retry policy used by `transaction_within_retry`.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> QldbClientBuilder {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Limits for waiting for a session from the pool. By default
+    /// there are none. See [AcquireOptions](crate::AcquireOptions).
+    pub fn acquire_options(mut self, acquire_options: AcquireOptions) -> QldbClientBuilder {
+        self.acquire_options = acquire_options;
+        self
+    }
+
+    /// How the amount of concurrent transactions shrinks when QLDB
+    /// throttles the client. See [ThrottlingPolicy](crate::ThrottlingPolicy).
+    pub fn throttling_policy(mut self, throttling_policy: ThrottlingPolicy) -> QldbClientBuilder {
+        self.throttling_policy = throttling_policy;
+        self
+    }
+
+    /// How `Transaction::insert_many` and `QldbClient::bulk_load` split
+    /// the documents. See [BulkLimits](crate::BulkLimits).
+    pub fn bulk_limits(mut self, bulk_limits: BulkLimits) -> QldbClientBuilder {
+        self.bulk_limits = bulk_limits;
+        self
+    }
+
+    /// Calls `callback` with the [PoolStats](crate::PoolStats) every
+    /// `interval`, for example for sending them to a metrics system.
+    /// The callback runs in the executor of the session pool, so it
+    /// shouldn't block. It stops being called when the pool is closed.
+    ///
+    /// ```rust,no_run
+    /// use qldb::QldbClient;
+    /// use std::time::Duration;
+    /// # use eyre::Result;
+    ///
+    /// # async fn test() -> Result<()> {
+    /// let client = QldbClient::builder("rust-crate-test")
+    ///     .pool_stats_callback(Duration::from_secs(10), |stats| {
+    ///         println!("{} sessions waiting", stats.pending_requests());
+    ///     })
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pool_stats_callback<F>(mut self, interval: Duration, callback: F) -> QldbClientBuilder
+    where
+        F: Fn(PoolStats) + Send + Sync + 'static,
+    {
+        self.pool_stats_callback = Some((interval, Arc::new(callback)));
+        self
+    }
+
+    /// Creates the client with the session pool that runs in its own
+    /// thread. See `QldbClient::default`.
+    #[cfg(feature = "internal_pool_with_thread")]
+    pub fn build(self) -> QldbResult<QldbClient> {
+        let transport = self.create_transport()?;
+
+        let session_pool = Arc::new(ThreadedSessionPool::new(
+            transport.clone(),
+            &self.ledger_name,
+            self.pool_config.clone(),
+        ));
+
+        Ok(self.create_client(transport, session_pool))
+    }
+
+    /// Creates the client with the session pool that runs in the
+    /// provided spawner. See `QldbClient::default_with_spawner`.
+    #[cfg(feature = "internal_pool_with_spawner")]
+    pub fn build_with_spawner(self, spawner: SpawnerFnMonoMultithread) -> QldbResult<QldbClient> {
+        let transport = self.create_transport()?;
+
+        let session_pool = Arc::new(SpawnerSessionPool::new(
+            transport.clone(),
+            &self.ledger_name,
+            self.pool_config.clone(),
+            spawner,
+        ));
+
+        Ok(self.create_client(transport, session_pool))
+    }
+
+    /// Creates the client with the session pool running in the tokio
+    /// runtime of `handle`. It can be called from threads outside the
+    /// runtime. Once the runtime shuts down the pool stops and new
+    /// transactions fail.
+    #[cfg(feature = "tokio")]
+    pub fn build_with_tokio(self, handle: tokio::runtime::Handle) -> QldbResult<QldbClient> {
+        self.build_with_spawner(Arc::new(move |fut| {
+            handle.spawn(fut);
+        }))
+    }
+
+    /// Creates the client with the session pool running in async-std.
+    #[cfg(feature = "async-std")]
+    pub fn build_with_async_std(self) -> QldbResult<QldbClient> {
+        self.build_with_spawner(Arc::new(|fut| {
+            async_std::task::spawn(fut);
+        }))
+    }
+
+    /// Creates the client with your own session pool. The settings of
+    /// the internal pools, like `min_idle` or `idle_timeout`, are
+    /// ignored, except `max_sessions`, which still limits how many
+    /// transactions run at the same time, so set it to the size of
+    /// your pool. See `SessionPool`.
+    pub fn build_with_pool(self, session_pool: Arc<dyn SessionPool>) -> QldbResult<QldbClient> {
+        let transport = self.create_transport()?;
+
+        Ok(self.create_client(transport, session_pool))
+    }
+
+    fn create_client(self, transport: Arc<dyn QldbTransport>, session_pool: Arc<dyn SessionPool>) -> QldbClient {
+        if let Some((interval, callback)) = self.pool_stats_callback {
+            report_pool_stats(&session_pool, interval, callback);
+        }
+
+        QldbClient::new(
+            transport,
+            &self.ledger_name,
+            session_pool,
+            self.retry_policy,
+            self.acquire_options,
+            ConcurrencyLimiter::new(&self.throttling_policy, self.pool_config.max_sessions.into()),
+            self.bulk_limits,
+        )
+    }
+
+    #[cfg(feature = "rusoto")]
+    fn create_transport(&self) -> QldbResult<Arc<dyn QldbTransport>> {
+        if let Some(transport) = &self.transport {
+            return Ok(transport.clone());
+        }
+
+        if let Some(session_client) = &self.session_client {
+            return Ok(Arc::new(RusotoTransport::new(session_client.clone())));
+        }
+
+        let region = self.region.clone().unwrap_or_default();
+
+        let credentials = self
+            .credentials
+            .clone()
+            .unwrap_or_else(|| DynCredentialsProvider(Arc::new(ChainProvider::default())));
+
+        let http_client = HttpClient::new().map_err(QldbError::TlsError)?;
+
+        Ok(Arc::new(RusotoTransport::with_client(
+            Client::new_with(credentials, http_client),
+            region,
+        )))
+    }
+
+    #[cfg(not(feature = "rusoto"))]
+    fn create_transport(&self) -> QldbResult<Arc<dyn QldbTransport>> {
+        self.transport.clone().ok_or(QldbError::MissingTransport)
+    }
+}
+
+/// Rusoto only implements the credentials trait for sized types, so
+/// this wrapper allows to store any provider given to the builder.
+#[cfg(feature = "rusoto")]
+#[derive(Clone)]
+struct DynCredentialsProvider(Arc<dyn ProvideAwsCredentials + Send + Sync>);
+
+#[cfg(feature = "rusoto")]
+#[async_trait::async_trait]
+impl ProvideAwsCredentials for DynCredentialsProvider {
+    async fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
+        self.0.credentials().await
+    }
+}
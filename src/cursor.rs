@@ -1,6 +1,10 @@
-use crate::DocumentCollection;
-use crate::{QldbResult, QueryBuilder};
+use crate::query_builder::{PrefetchedPage, PrefetchedPages};
+use crate::{Document, DocumentCollection};
+use crate::{QldbResult, QueryBuilder, QueryStats};
+use futures::stream::{self, Stream, TryStreamExt};
+use ion_binary_rs::IonValue;
 use std::convert::TryInto;
+use std::time::Instant;
 
 /// Cursor allows to get all values from a statement page by page.
 ///
@@ -45,6 +49,9 @@ pub struct Cursor {
     query_builder: QueryBuilder,
     next_page: Option<String>,
     is_first_page: bool,
+    stats: QueryStats,
+    prefetch: usize,
+    prefetched: Option<PrefetchedPages>,
 }
 
 impl Cursor {
@@ -53,9 +60,22 @@ impl Cursor {
             query_builder,
             next_page: None,
             is_first_page: true,
+            stats: QueryStats::default(),
+            prefetch: 0,
+            prefetched: None,
         }
     }
 
+    pub(crate) fn with_prefetch(mut self, pages: usize) -> Cursor {
+        self.prefetch = pages;
+        self
+    }
+
+    /// IOs and processing time used by QLDB for the pages loaded so far.
+    pub fn stats(&self) -> QueryStats {
+        self.stats
+    }
+
     /// It loads the next page from a query. It automatically tracks
     /// the next_page_token, so you can call this method again and
     /// again in order to load all pages.
@@ -78,8 +98,35 @@ impl Cursor {
     ///
     /// ```
     pub async fn load_more(&mut self) -> QldbResult<Option<DocumentCollection>> {
-        let (values, next_page_token) = if self.is_first_page {
-            self.query_builder.execute_statement().await?
+        match self.query_builder.timeout_with_transaction() {
+            Some((tx, timeout)) => tx.within_deadline(Instant::now(), timeout, self.next_page()).await,
+            None => self.next_page().await,
+        }
+    }
+
+    async fn next_page(&mut self) -> QldbResult<Option<DocumentCollection>> {
+        match self.next_values().await? {
+            Some((values, stats)) => {
+                let values: DocumentCollection = values.try_into()?;
+                Ok(Some(values.with_stats(stats)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Next page as returned by QLDB, without converting the values to
+    /// documents.
+    async fn next_values(&mut self) -> QldbResult<Option<(Vec<IonValue>, QueryStats)>> {
+        let (values, next_page_token, stats) = if self.is_first_page {
+            let result = self.query_builder.execute_statement().await?;
+
+            if let (Some(page), true) = (&result.1, self.prefetch > 0) {
+                self.prefetched = Some(self.query_builder.prefetch_pages(page.clone(), self.prefetch));
+            }
+
+            result
+        } else if let Some(page) = self.next_prefetched().await {
+            page?
         } else if let Some(page) = &self.next_page {
             self.query_builder.execute_get_page(page).await?
         } else {
@@ -90,16 +137,64 @@ impl Cursor {
         self.is_first_page = false;
 
         self.next_page = next_page_token;
+        self.stats += stats;
+
+        Ok(Some((values, stats)))
+    }
+
+    /// Next page loaded in the background. It returns None once the
+    /// prefetch has finished, or has been stopped by the transaction for
+    /// sending another command, so the remaining pages, if any, are
+    /// loaded with `next_page`.
+    async fn next_prefetched(&mut self) -> Option<PrefetchedPage> {
+        let page = self.prefetched.as_ref()?.next().await;
+
+        if page.is_none() {
+            self.prefetched = None;
+        }
 
-        Ok(Some(values.try_into()?))
+        page
     }
 
     /// Loads all pages from the cursor and consumes it in the process.
-    pub async fn load_all(mut self) -> QldbResult<DocumentCollection> {
+    pub async fn load_all(self) -> QldbResult<DocumentCollection> {
+        match self.query_builder.timeout_with_transaction() {
+            Some((tx, timeout)) => tx.within_deadline(Instant::now(), timeout, self.load_remaining()).await,
+            None => self.load_remaining().await,
+        }
+    }
+
+    async fn load_remaining(mut self) -> QldbResult<DocumentCollection> {
         let mut result = DocumentCollection::new(vec![]);
 
-        while let Some(values) = self.load_more().await? {
-            result.extend(values.into_iter());
+        while let Some(values) = self.next_page().await? {
+            result.extend(values);
+
+            if self.next_page.is_none() {
+                break;
+            }
+        }
+
+        Ok(result.with_stats(self.stats))
+    }
+
+    /// Same as `load_all` but returning the values as they are, for
+    /// statements that don't return documents, like `SELECT VALUE`.
+    pub(crate) async fn load_all_values(self) -> QldbResult<Vec<IonValue>> {
+        match self.query_builder.timeout_with_transaction() {
+            Some((tx, timeout)) => {
+                tx.within_deadline(Instant::now(), timeout, self.load_remaining_values())
+                    .await
+            }
+            None => self.load_remaining_values().await,
+        }
+    }
+
+    async fn load_remaining_values(mut self) -> QldbResult<Vec<IonValue>> {
+        let mut result = vec![];
+
+        while let Some((values, _)) = self.next_values().await? {
+            result.extend(values);
 
             if self.next_page.is_none() {
                 break;
@@ -108,4 +203,53 @@ impl Cursor {
 
         Ok(result)
     }
+
+    /// Converts the cursor into a stream of pages. Each page is loaded
+    /// when the stream is polled, so dropping the stream stops fetching
+    /// more pages. The stream ends after the first error.
+    ///
+    /// Only the statement is part of the transaction's commit digest,
+    /// not the pages, so stopping early doesn't affect the transaction,
+    /// which can still be used and committed.
+    pub fn into_page_stream(self) -> impl Stream<Item = QldbResult<DocumentCollection>> + Send + Unpin {
+        Box::pin(stream::try_unfold(self, |mut cursor| async move {
+            Ok(cursor.load_more().await?.map(|page| (page, cursor)))
+        }))
+    }
+
+    /// Converts the cursor into a stream of documents. Pages are loaded
+    /// lazily as in `into_page_stream`, so combinators like `take` stop
+    /// fetching once they are done.
+    ///
+    /// ```rust,no_run
+    /// use futures::{StreamExt, TryStreamExt};
+    /// use qldb::{QldbResult, Transaction};
+    ///
+    /// # async fn test(tx: Transaction) -> QldbResult<()> {
+    /// let first_red_cars = tx
+    ///     .query("SELECT * FROM Cars")
+    ///     .get_cursor()?
+    ///     .into_stream()
+    ///     .try_filter(|car| futures::future::ready(car.get_value::<String>("color").ok() == Some("red".to_string())))
+    ///     .take(10)
+    ///     .try_collect::<Vec<_>>()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_stream(self) -> impl Stream<Item = QldbResult<Document>> + Send + Unpin {
+        self.into_page_stream()
+            .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+            .try_flatten()
+    }
+}
+
+impl Drop for Cursor {
+    fn drop(&mut self) {
+        // Stops the prefetch, so it doesn't keep loading pages nobody
+        // is going to read
+        if let Some(prefetched) = &self.prefetched {
+            prefetched.close();
+        }
+    }
 }
@@ -1,5 +1,7 @@
+use crate::serialization::{from_ion_value, SerializationError};
 use crate::types::{QldbExtractError, QldbExtractResult};
 use ion_binary_rs::IonValue;
+use serde::de::DeserializeOwned;
 use std::{collections::HashMap, convert::TryFrom};
 
 /// It contains the IonValue representing the QLDB Document.
@@ -57,6 +59,38 @@ impl Document {
         self.document.get(name)
     }
 
+    /// Deserializes the whole document into any type implementing
+    /// `serde::Deserialize`.
+    ///
+    /// Nested structs, lists, options and blobs (as `Vec<u8>`) are
+    /// supported. Timestamps and decimals are provided as strings, so
+    /// `chrono` and `bigdecimal` types work when their `serde` feature
+    /// is enabled. In case of error, `QldbExtractError::Deserialize`
+    /// contains the path of the failing field.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{Document, QldbExtractResult};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Car {
+    ///     plate: String,
+    ///     owner: Option<String>,
+    ///     points: u64,
+    /// }
+    ///
+    /// fn test(document: Document) -> QldbExtractResult<Car> {
+    ///     document.deserialize::<Car>()
+    /// }
+    /// ```
+    pub fn deserialize<T: DeserializeOwned>(&self) -> QldbExtractResult<T> {
+        Ok(self.deserialize_with_path()?)
+    }
+
+    pub(crate) fn deserialize_with_path<T: DeserializeOwned>(&self) -> Result<T, SerializationError> {
+        from_ion_value(IonValue::Struct(self.document.clone()))
+    }
+
     /// Same as `extract_value` but it returns None if the property is not there.
     pub fn get_optional_value<T>(&self, name: &str) -> QldbExtractResult<Option<T>>
     where
@@ -1,5 +1,7 @@
-use crate::{document::Document, types::QldbExtractError};
+use crate::serialization::SerializationError;
+use crate::{document::Document, types::QldbExtractError, QldbExtractResult, QueryStats};
 use ion_binary_rs::IonValue;
+use serde::de::DeserializeOwned;
 use std::convert::TryFrom;
 use std::ops::Index;
 
@@ -37,9 +39,10 @@ use std::ops::Index;
 ///     Ok(result)
 /// }
 /// ```
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct DocumentCollection {
     documents: Vec<Document>,
+    stats: QueryStats,
 }
 
 impl TryFrom<Vec<IonValue>> for DocumentCollection {
@@ -59,7 +62,21 @@ impl TryFrom<Vec<IonValue>> for DocumentCollection {
 
 impl DocumentCollection {
     pub fn new(documents: Vec<Document>) -> DocumentCollection {
-        DocumentCollection { documents }
+        DocumentCollection {
+            documents,
+            stats: QueryStats::default(),
+        }
+    }
+
+    pub(crate) fn with_stats(mut self, stats: QueryStats) -> DocumentCollection {
+        self.stats = stats;
+        self
+    }
+
+    /// IOs and processing time used by QLDB for returning these
+    /// documents, summed across all the pages.
+    pub fn stats(&self) -> QueryStats {
+        self.stats
     }
 
     pub fn into_inner(self) -> Vec<Document> {
@@ -77,6 +94,28 @@ impl DocumentCollection {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Deserializes every document using `Document::deserialize`. The
+    /// path in the error starts with the index of the failing document.
+    pub fn deserialize_all<T: DeserializeOwned>(&self) -> QldbExtractResult<Vec<T>> {
+        self.documents
+            .iter()
+            .enumerate()
+            .map(|(index, document)| {
+                document
+                    .deserialize_with_path()
+                    .map_err(|error: SerializationError| error.in_index(index).into())
+            })
+            .collect()
+    }
+}
+
+// The stats are not part of the data, two queries returning the same
+// documents are equal even if they consumed different IOs.
+impl PartialEq for DocumentCollection {
+    fn eq(&self, other: &DocumentCollection) -> bool {
+        self.documents == other.documents
+    }
 }
 
 impl Default for DocumentCollection {
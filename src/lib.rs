@@ -37,18 +37,163 @@
 //! # }
 //! ```
 //!
+//! Besides `execute`, `QueryBuilder` has `fetch_one`, `fetch_optional`,
+//! `execute_scalar` (for `SELECT VALUE` statements) and `execute_as`, which
+//! return `QldbError::UnexpectedRowCount` when the statement doesn't return
+//! the amount of documents expected.
+//!
+//! ## Named parameters
+//!
+//! Besides the positional `?` params of PartiQL, `QueryBuilder::named_param`
+//! binds values to `:name` placeholders, which the driver rewrites to `?`
+//! before sending the statement. Unknown, unused and duplicated names are
+//! returned as errors.
+//!
+//! ## Bulk loading
+//!
+//! `Transaction::insert_many` inserts many documents with as few bag
+//! inserts (`INSERT INTO Cars << ?, ?, ... >>`) as possible and returns
+//! their ids. `QldbClient::bulk_load` also splits the documents in several
+//! transactions, reports the progress after every commit and can resume
+//! an interrupted load. The builder method `bulk_limits` changes how many
+//! documents and bytes go in each statement and transaction. Table names
+//! can only have letters, digits and underscores, and `insert_many`
+//! returns an error instead of going over the transaction limits.
+//!
+//! ## Configuring the client
+//!
+//! `QldbClient::default` takes the region and the credentials from the
+//! environment. When you need something else, for example connecting to
+//! ledgers in different regions from the same process, use the builder.
+//! It accepts any `Region` (including `Region::Custom` endpoints), any
+//! rusoto credentials provider or an already created `QldbSessionClient`.
+//!
+//! ```rust,no_run
+//! use qldb::{QldbClient, Region};
+//! use rusoto_core::credential::ProfileProvider;
+//! # use eyre::Result;
+//!
+//! # async fn test() -> Result<()> {
+//! let client = QldbClient::builder("rust-crate-test")
+//!     .region(Region::EuCentral1)
+//!     .credentials(ProfileProvider::new()?)
+//!     .max_sessions(50)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Retrying transactions
+//!
+//! QLDB uses optimistic concurrency control, so a transaction can fail
+//! on commit because another transaction modified the same data. The
+//! method `transaction_within_retry` runs the closure again with a new
+//! transaction when that happens (and on invalid sessions or transient
+//! network errors), following the given `RetryPolicy`.
+//!
+//! ```rust,no_run
+//! use qldb::{QldbClient, RetryPolicy};
+//! # use eyre::Result;
+//!
+//! # async fn test() -> Result<()> {
+//! let client = QldbClient::default("rust-crate-test", 200)
+//!     .await?
+//!     .with_retry_policy(RetryPolicy::new(4));
+//!
+//! client
+//!     .transaction_within_retry(|client| async move {
+//!         client
+//!             .query("UPDATE TestTable SET counter = counter + 1")
+//!             .execute()
+//!             .await?;
+//!         Ok(())
+//!     })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A commit that fails without an answer from QLDB, like when the
+//! connection drops, may have been applied anyway. Its error is returned
+//! instead of running the transaction again, unless
+//! `RetryPolicy::retry_unknown_commits` is enabled for transactions that
+//! are harmless to apply twice.
+//!
+//! `QldbError::kind` classifies any error (OCC conflict, invalid session,
+//! throttling, transport failure…) without depending on the transport, and
+//! `QldbError::is_retryable` tells whether it is worth trying again.
+//!
+//! ## Throttling
+//!
+//! When QLDB answers with `RateExceededException`, `LimitExceededException`
+//! or `CapacityExceededException`, the client reduces how many
+//! transactions run at the same time, so the pool stops opening sessions
+//! while the ledger is overloaded, and it raises the limit again as
+//! transactions succeed. `QldbClient::concurrency_limit` returns the
+//! current limit and the builder method `throttling_policy` changes how
+//! it behaves (see `ThrottlingPolicy`).
+//!
+//! ## Using serde
+//!
+//! Documents can be deserialized into any type implementing
+//! `serde::Deserialize` with `Document::deserialize` and
+//! `DocumentCollection::deserialize_all`, and any type implementing
+//! `serde::Serialize` can be given as a parameter with
+//! `QueryBuilder::param_serde`. The modules in `qldb::serialization`
+//! store chrono dates and big decimals as Ion timestamps and decimals,
+//! and `None` as a typed null, like `null.string`, instead of `null`.
+//!
+//! ```rust,no_run
+//! use qldb::QldbClient;
+//! use serde::Deserialize;
+//! # use eyre::Result;
+//!
+//! #[derive(Deserialize)]
+//! struct Car {
+//!     plate: String,
+//!     points: u64,
+//! }
+//!
+//! # async fn test() -> Result<()> {
+//! let client = QldbClient::default("rust-crate-test", 200).await?;
+//!
+//! let cars: Vec<Car> = client
+//!     .read_query("SELECT * FROM Cars")
+//!     .await?
+//!     .execute()
+//!     .await?
+//!     .deserialize_all()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! # Session Pool
 //!
 //! The driver has a session pool. The second parameter in the
 //! QldbClient::default is the maximum size of the connection pool.
 //!
 //! The pool will be auto-populated as parallel transaction are being
-//! requested until it reaches the provided maximum.
+//! requested until it reaches the provided maximum. The maximum counts
+//! the sessions in use too, so when all of them are used new transactions
+//! wait until one is given back to the pool.
+//!
+//! Sessions are closed after 10 minutes and replaced by new ones. The
+//! builder methods `max_session_age`, `idle_timeout` and `min_idle` change
+//! how long sessions live, close the ones not used for a while and keep
+//! some sessions ready for the next transactions.
 //!
 //! The pool uses one independent thread with a single-threaded
 //! executor ([async-executor](https://crates.io/crates/async-executor))
 //! in order to be able to spawn tasks after the session has been returned.
 //!
+//! `QldbClient::pool_stats` returns the state of the pool (idle and in
+//! use sessions, waiting transactions, acquire latency percentiles…),
+//! which helps finding out whether `max_sessions` is too small. The
+//! builder method `pool_stats_callback` reports them periodically.
+//!
+//! Call `QldbClient::close` before exiting. It waits for the running
+//! transactions, ends every session in QLDB and stops the pool thread.
+//!
 //! ## Alternative session Pool
 //!
 //! There is an alternative session pool that will require an spawner
@@ -96,6 +241,32 @@
 //! # }
 //! ```
 //!
+//! The `tokio` and `async-std` features add constructors that do it for
+//! you. `QldbClient::with_tokio_handle` and
+//! `QldbClientBuilder::build_with_tokio` spawn the pool tasks in the
+//! runtime of the given `Handle`, so they work from threads outside of it.
+//!
+//! ```toml,no_code
+//! qldb = { version = "3", features = ["tokio"] }
+//! ```
+//!
+//! ```rust,no_run
+//! use qldb::QldbClient;
+//! # use eyre::Result;
+//!
+//! # async fn test() -> Result<()> {
+//! let client = QldbClient::with_tokio("rust-crate-test", 200).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Custom session pool
+//!
+//! You can manage the sessions yourself by implementing the `SessionPool`
+//! trait and creating the client with `QldbClient::with_pool` or
+//! `QldbClientBuilder::build_with_pool`. The trait documentation explains
+//! what the driver expects from `get`, `give_back` and `close`.
+//!
 //! ## Select the pool you want to use
 //!
 //! By default, both pools are available by using the methods `QldbClient::default`
@@ -105,12 +276,38 @@
 //!
 //! This will only enable the default pool, the one that uses one thread.
 //! ```toml,no_code
-//! qldb = { version = "3", default_features = false, features = ["internal_pool_with_thread"]}
+//! qldb = { version = "3", default_features = false, features = ["rusoto", "internal_pool_with_thread"]}
 //! ```
 //!
 //! This will only enable the alternative pool, the one that requires an spawner
 //! ```toml,no_code
-//! qldb = { version = "3", default_features = false, features = ["internal_pool_with_spawner"]}
+//! qldb = { version = "3", default_features = false, features = ["rusoto", "internal_pool_with_spawner"]}
+//! ```
+//!
+//! # Transport
+//!
+//! The driver sends the commands to QLDB through the `QldbTransport` trait,
+//! so it isn't tied to a specific AWS SDK. There are two implementations:
+//!
+//!  - `RusotoTransport`, using [rusoto](https://crates.io/crates/rusoto_qldb_session).
+//!    It is enabled by default with the `rusoto` feature and it is the one
+//!    used by `QldbClient::default`.
+//!  - `AwsSdkTransport`, using the official
+//!    [aws-sdk-qldbsession](https://crates.io/crates/aws-sdk-qldbsession).
+//!    It requires the `aws-sdk` feature.
+//!
+//! Any transport can be given to the client builder, including your own
+//! implementation (for example, a fake one for tests):
+//!
+//! ```rust,ignore
+//! use qldb::{transport::AwsSdkTransport, QldbClient};
+//! use std::sync::Arc;
+//!
+//! let config = aws_config::load_from_env().await;
+//!
+//! let client = QldbClient::builder("rust-crate-test")
+//!     .transport(Arc::new(AwsSdkTransport::new(aws_sdk_qldbsession::Client::new(&config))))
+//!     .build()?;
 //! ```
 //!
 //! # Underlying Ion Format Implementation
@@ -130,23 +327,56 @@
 //! ```sh
 //! RUST_TEST_THREADS=1 cargo test
 //! ```
+//!
+//! The tests in the `tests/fake_*.rs` files don't need AWS. They run
+//! against `qldb::testing::FakeQldb`, an in-memory fake of the QLDB
+//! session service that understands a small subset of PartiQL, wrapped
+//! in `qldb::testing::InterceptingTransport` when they need QLDB to be
+//! slow or to fail. You can use them for testing your own code by
+//! enabling the `testing` feature:
+//!
+//! ```toml,no_code
+//! qldb = { version = "3", features = ["testing"] }
+//! ```
 
+mod acquire_options;
+mod bulk_load;
 mod client;
+mod client_builder;
 mod cursor;
 mod document;
 mod document_collection;
+mod named_params;
+mod pool_stats;
 mod query_builder;
+mod query_stats;
+mod retry;
+pub mod serialization;
 mod session_pool;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod throttling;
 mod transaction;
+pub mod transport;
 mod types;
 
+pub use acquire_options::AcquireOptions;
+pub use bulk_load::{BulkLimits, BulkLoadCallback, BulkLoadOptions, BulkLoadProgress};
 pub use client::QldbClient;
+pub use client_builder::QldbClientBuilder;
 pub use cursor::Cursor;
 pub use document::Document;
 pub use document_collection::DocumentCollection;
 pub use ion_binary_rs as ion;
+pub use pool_stats::{AcquireLatency, PoolCloseReport, PoolStats, PoolStatsCallback};
 pub use query_builder::QueryBuilder;
+pub use query_stats::QueryStats;
+pub use retry::RetryPolicy;
+#[cfg(feature = "rusoto")]
 pub use rusoto_core::Region;
+pub use session_pool::{BackgroundTask, Session, SessionPool};
+pub use throttling::ThrottlingPolicy;
 pub use transaction::Transaction;
-pub use types::{QldbError, QldbResult};
+pub use transport::QldbTransport;
+pub use types::{QldbError, QldbErrorKind, QldbResult, TimeoutPhase};
 pub use types::{QldbExtractError, QldbExtractResult};
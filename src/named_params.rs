@@ -0,0 +1,235 @@
+use crate::types::{QldbError, QldbResult};
+use ion_binary_rs::IonValue;
+use std::collections::{HashMap, HashSet};
+
+/// Rewrites the `:name` placeholders of `statement` to the positional
+/// `?` placeholders QLDB understands and returns the values in the
+/// order they appear.
+///
+/// String literals, quoted identifiers, Ion literals between backticks
+/// and comments are copied as they are. A `:` only starts a placeholder
+/// when it is followed by a name and it isn't preceded by a name, a
+/// quote or another `:`, so struct literals like `{'a':1}` are kept.
+pub(crate) fn bind_named_params(
+    statement: &str,
+    named_params: Vec<(String, IonValue)>,
+) -> QldbResult<(String, Vec<IonValue>)> {
+    let mut names = Vec::with_capacity(named_params.len());
+    let mut values = HashMap::with_capacity(named_params.len());
+
+    for (name, value) in named_params {
+        if values.insert(name.clone(), value).is_some() {
+            return Err(QldbError::DuplicatedNamedParam(name));
+        }
+
+        names.push(name);
+    }
+
+    let bytes = statement.as_bytes();
+    let mut rewritten = String::with_capacity(statement.len());
+    let mut params = vec![];
+    let mut used = HashSet::new();
+    // Everything before it is already in `rewritten`
+    let mut copied = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        index = match bytes[index] {
+            quote @ (b'\'' | b'"') => skip_quoted(bytes, index + 1, quote),
+            b'`' => skip_past(bytes, index + 1, b"`"),
+            b'-' if bytes.get(index + 1) == Some(&b'-') => skip_past(bytes, index + 2, b"\n"),
+            b'/' if bytes.get(index + 1) == Some(&b'*') => skip_past(bytes, index + 2, b"*/"),
+            b'?' => return Err(QldbError::MixedParams),
+            b':' if is_placeholder(bytes, index) => {
+                let end = name_end(bytes, index + 1);
+                let name = &statement[index + 1..end];
+
+                let value = values
+                    .get(name)
+                    .ok_or_else(|| QldbError::MissingNamedParam(name.to_string()))?;
+
+                rewritten.push_str(&statement[copied..index]);
+                rewritten.push('?');
+                params.push(value.clone());
+                used.insert(name);
+                copied = end;
+
+                end
+            }
+            _ => index + 1,
+        };
+    }
+
+    rewritten.push_str(&statement[copied..]);
+
+    if let Some(unused) = names.iter().find(|name| !used.contains(name.as_str())) {
+        return Err(QldbError::UnusedNamedParam(unused.clone()));
+    }
+
+    Ok((rewritten, params))
+}
+
+fn is_name_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+fn is_placeholder(bytes: &[u8], colon: usize) -> bool {
+    let starts_name = bytes
+        .get(colon + 1)
+        .is_some_and(|next| next.is_ascii_alphabetic() || *next == b'_');
+
+    let follows_value = colon > 0 && {
+        let previous = bytes[colon - 1];
+        is_name_char(previous) || matches!(previous, b':' | b'\'' | b'"' | b'`')
+    };
+
+    starts_name && !follows_value
+}
+
+fn name_end(bytes: &[u8], start: usize) -> usize {
+    let length = bytes[start..].iter().take_while(|byte| is_name_char(**byte)).count();
+    start + length
+}
+
+/// Index after the closing quote. Doubled quotes are escaped quotes.
+fn skip_quoted(bytes: &[u8], mut index: usize, quote: u8) -> usize {
+    while index < bytes.len() {
+        if bytes[index] == quote {
+            if bytes.get(index + 1) != Some(&quote) {
+                return index + 1;
+            }
+
+            index += 1;
+        }
+
+        index += 1;
+    }
+
+    bytes.len()
+}
+
+/// Index after the next `end`, or the end of the statement.
+fn skip_past(bytes: &[u8], index: usize, end: &[u8]) -> usize {
+    bytes[index.min(bytes.len())..]
+        .windows(end.len())
+        .position(|window| window == end)
+        .map(|position| index + position + end.len())
+        .unwrap_or(bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind(statement: &str, names: &[&str]) -> QldbResult<(String, Vec<IonValue>)> {
+        let named_params = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.to_string(), IonValue::Integer(index as i64)))
+            .collect();
+
+        bind_named_params(statement, named_params)
+    }
+
+    #[test]
+    fn placeholders_in_order_of_appearance() {
+        let (statement, params) = bind(
+            "SELECT * FROM Cars WHERE owner = :owner AND plate = :plate",
+            &["plate", "owner"],
+        )
+        .unwrap();
+
+        assert_eq!(statement, "SELECT * FROM Cars WHERE owner = ? AND plate = ?");
+        assert_eq!(params, vec![IonValue::Integer(1), IonValue::Integer(0)]);
+    }
+
+    #[test]
+    fn repeated_placeholders() {
+        let (statement, params) = bind("SELECT * FROM Cars WHERE owner = :name OR seller = :name", &["name"]).unwrap();
+
+        assert_eq!(statement, "SELECT * FROM Cars WHERE owner = ? OR seller = ?");
+        assert_eq!(params, vec![IonValue::Integer(0), IonValue::Integer(0)]);
+    }
+
+    #[test]
+    fn quoted_strings_are_kept() {
+        let (statement, params) = bind(
+            "SELECT * FROM Cars WHERE owner = ':owner' AND plate = :plate",
+            &["plate"],
+        )
+        .unwrap();
+
+        assert_eq!(statement, "SELECT * FROM Cars WHERE owner = ':owner' AND plate = ?");
+        assert_eq!(params.len(), 1);
+    }
+
+    #[test]
+    fn doubled_quotes_dont_end_strings() {
+        let (statement, _) = bind(
+            r#"SELECT * FROM Cars WHERE owner = 'O''Brien :owner' AND "odd "":column" = :plate"#,
+            &["plate"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            statement,
+            r#"SELECT * FROM Cars WHERE owner = 'O''Brien :owner' AND "odd "":column" = ?"#
+        );
+    }
+
+    #[test]
+    fn backtick_literals_are_kept() {
+        let (statement, _) = bind(
+            "SELECT * FROM Cars WHERE data = `{a::b: :c}` AND plate = :plate",
+            &["plate"],
+        )
+        .unwrap();
+
+        assert_eq!(statement, "SELECT * FROM Cars WHERE data = `{a::b: :c}` AND plate = ?");
+    }
+
+    #[test]
+    fn comments_are_kept() {
+        let (statement, _) = bind(
+            "SELECT * FROM Cars -- by :owner?\nWHERE /* :plate? */ plate = :plate",
+            &["plate"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            statement,
+            "SELECT * FROM Cars -- by :owner?\nWHERE /* :plate? */ plate = ?"
+        );
+    }
+
+    #[test]
+    fn struct_literals_are_kept() {
+        let (statement, params) = bind("INSERT INTO Cars VALUE {'plate':'1111', 'owner': :owner}", &["owner"]).unwrap();
+
+        assert_eq!(statement, "INSERT INTO Cars VALUE {'plate':'1111', 'owner': ?}");
+        assert_eq!(params.len(), 1);
+
+        let (statement, _) = bind("INSERT INTO Cars VALUE {plate:1111}", &[]).unwrap();
+
+        assert_eq!(statement, "INSERT INTO Cars VALUE {plate:1111}");
+    }
+
+    #[test]
+    fn positional_and_named_params_cannot_be_mixed() {
+        let error = bind("SELECT * FROM Cars WHERE owner = :owner AND plate = ?", &["owner"]).unwrap_err();
+
+        assert!(matches!(error, QldbError::MixedParams));
+    }
+
+    #[test]
+    fn missing_unused_and_duplicated_params() {
+        let error = bind("SELECT * FROM Cars WHERE owner = :owner", &[]).unwrap_err();
+        assert!(matches!(error, QldbError::MissingNamedParam(name) if name == "owner"));
+
+        let error = bind("SELECT * FROM Cars", &["owner"]).unwrap_err();
+        assert!(matches!(error, QldbError::UnusedNamedParam(name) if name == "owner"));
+
+        let error = bind("SELECT * FROM Cars WHERE owner = :owner", &["owner", "owner"]).unwrap_err();
+        assert!(matches!(error, QldbError::DuplicatedNamedParam(name) if name == "owner"));
+    }
+}
@@ -0,0 +1,295 @@
+use crate::session_pool::SessionPool;
+use async_io::Timer;
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+use std::collections::VecDeque;
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed};
+use std::sync::Arc;
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Amount of recent session acquisitions used for the latency percentiles.
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+const LATENCY_SAMPLES: usize = 1024;
+
+/// Snapshot of the state of the session pool, returned by
+/// [QldbClient::pool_stats](crate::QldbClient::pool_stats).
+///
+/// Useful for finding out whether `max_sessions` is too small: many
+/// pending requests and a high acquire latency with no idle sessions
+/// mean that transactions are waiting for a session.
+///
+/// ```rust,no_run
+/// use qldb::QldbClient;
+///
+/// # fn test(client: QldbClient) {
+/// let stats = client.pool_stats();
+///
+/// println!(
+///     "{} sessions in use, {} idle, {} waiting. p99 acquire latency: {:?}",
+///     stats.in_use_sessions(),
+///     stats.idle_sessions(),
+///     stats.pending_requests(),
+///     stats.acquire_latency().p99(),
+/// );
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    idle_sessions: usize,
+    in_use_sessions: usize,
+    created_sessions: u64,
+    closed_sessions: u64,
+    pending_requests: usize,
+    creation_failures: u64,
+    acquire_latency: AcquireLatency,
+}
+
+impl PoolStats {
+    /// Sessions waiting in the pool to be used.
+    pub fn idle_sessions(&self) -> usize {
+        self.idle_sessions
+    }
+
+    /// Sessions currently used by a transaction.
+    pub fn in_use_sessions(&self) -> usize {
+        self.in_use_sessions
+    }
+
+    /// Sessions opened since the pool was created.
+    pub fn created_sessions(&self) -> u64 {
+        self.created_sessions
+    }
+
+    /// Sessions closed since the pool was created.
+    pub fn closed_sessions(&self) -> u64 {
+        self.closed_sessions
+    }
+
+    /// Transactions waiting for a session.
+    pub fn pending_requests(&self) -> usize {
+        self.pending_requests
+    }
+
+    /// Times QLDB failed to open a new session, after retrying.
+    pub fn creation_failures(&self) -> u64 {
+        self.creation_failures
+    }
+
+    /// Time transactions waited for a session, for the latest
+    /// acquisitions.
+    pub fn acquire_latency(&self) -> AcquireLatency {
+        self.acquire_latency
+    }
+}
+
+/// Percentiles of the time spent waiting for a session. They are
+/// calculated from the latest 1024 acquisitions and are zero until the
+/// first one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AcquireLatency {
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+impl AcquireLatency {
+    #[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+    fn from_samples(samples: &VecDeque<Duration>) -> AcquireLatency {
+        let mut samples: Vec<Duration> = samples.iter().copied().collect();
+        samples.sort_unstable();
+
+        let percentile = |percentile: usize| match samples.len() {
+            0 => Duration::default(),
+            len => samples[(len * percentile / 100).min(len - 1)],
+        };
+
+        AcquireLatency {
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: samples.last().copied().unwrap_or_default(),
+        }
+    }
+
+    /// Median.
+    pub fn p50(&self) -> Duration {
+        self.p50
+    }
+
+    /// 90th percentile.
+    pub fn p90(&self) -> Duration {
+        self.p90
+    }
+
+    /// 99th percentile.
+    pub fn p99(&self) -> Duration {
+        self.p99
+    }
+
+    /// Longest wait.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+}
+
+/// Result of closing the session pool with
+/// [QldbClient::close](crate::QldbClient::close).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolCloseReport {
+    closed_sessions: usize,
+    failed_sessions: usize,
+    abandoned_sessions: usize,
+}
+
+impl PoolCloseReport {
+    /// For reporting the result of custom `SessionPool` implementations.
+    pub fn new(closed_sessions: usize, failed_sessions: usize, abandoned_sessions: usize) -> PoolCloseReport {
+        PoolCloseReport {
+            closed_sessions,
+            failed_sessions,
+            abandoned_sessions,
+        }
+    }
+
+    /// Sessions ended cleanly with `EndSession`.
+    pub fn closed_sessions(&self) -> usize {
+        self.closed_sessions
+    }
+
+    /// Sessions that QLDB had already invalidated, that couldn't be
+    /// ended or that were still being ended when the timeout expired.
+    /// QLDB will expire them.
+    pub fn failed_sessions(&self) -> usize {
+        self.failed_sessions
+    }
+
+    /// Sessions still in use by a transaction when the timeout
+    /// expired. They aren't ended when given back.
+    pub fn abandoned_sessions(&self) -> usize {
+        self.abandoned_sessions
+    }
+}
+
+/// Function called periodically with the stats of the pool. See
+/// [QldbClientBuilder::pool_stats_callback](crate::QldbClientBuilder::pool_stats_callback).
+pub type PoolStatsCallback = Arc<dyn Fn(PoolStats) + Send + Sync>;
+
+/// Calls the callback with the stats of the pool every `interval`
+/// until the pool is closed or dropped.
+pub(crate) fn report_pool_stats(session_pool: &Arc<dyn SessionPool>, interval: Duration, callback: PoolStatsCallback) {
+    let session_pool_weak = Arc::downgrade(session_pool);
+
+    session_pool.spawn(Box::pin(async move {
+        loop {
+            Timer::after(interval).await;
+
+            match session_pool_weak.upgrade() {
+                Some(session_pool) if !session_pool.is_closed() => callback(session_pool.stats()),
+                _ => break,
+            }
+        }
+    }));
+}
+
+/// Counters updated by the session pools. They are shared between the
+/// pool handle and its background tasks, which may run in another
+/// thread.
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+#[derive(Debug, Default)]
+pub(crate) struct PoolMetrics {
+    idle_sessions: AtomicUsize,
+    in_use_sessions: AtomicUsize,
+    created_sessions: AtomicU64,
+    closed_sessions: AtomicU64,
+    pending_requests: AtomicUsize,
+    creation_failures: AtomicU64,
+    acquire_latencies: Mutex<VecDeque<Duration>>,
+}
+
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+impl PoolMetrics {
+    pub(crate) fn stats(&self) -> PoolStats {
+        let acquire_latency = match self.acquire_latencies.lock() {
+            Ok(latencies) => AcquireLatency::from_samples(&latencies),
+            Err(_) => AcquireLatency::default(),
+        };
+
+        PoolStats {
+            idle_sessions: self.idle_sessions.load(Relaxed),
+            in_use_sessions: self.in_use_sessions.load(Relaxed),
+            created_sessions: self.created_sessions.load(Relaxed),
+            closed_sessions: self.closed_sessions.load(Relaxed),
+            pending_requests: self.pending_requests.load(Relaxed),
+            creation_failures: self.creation_failures.load(Relaxed),
+            acquire_latency,
+        }
+    }
+
+    pub(crate) fn set_idle_sessions(&self, idle_sessions: usize) {
+        self.idle_sessions.store(idle_sessions, Relaxed);
+    }
+
+    pub(crate) fn session_created(&self) {
+        self.created_sessions.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn session_creation_failed(&self) {
+        self.creation_failures.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn session_closed(&self) {
+        self.closed_sessions.fetch_add(1, Relaxed);
+    }
+
+    /// Counts a request as pending until the returned guard is dropped,
+    /// so cancelled requests aren't counted forever. It returns None
+    /// when there are already `max_pending` requests.
+    pub(crate) fn pending_request(&self, max_pending: Option<usize>) -> Option<PendingRequest<'_>> {
+        let max_pending = max_pending.unwrap_or(usize::MAX);
+
+        self.pending_requests
+            .fetch_update(Relaxed, Relaxed, |pending| {
+                if pending < max_pending {
+                    Some(pending + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()?;
+
+        Some(PendingRequest { metrics: self })
+    }
+
+    pub(crate) fn session_acquired(&self, latency: Duration) {
+        self.in_use_sessions.fetch_add(1, Relaxed);
+
+        if let Ok(mut latencies) = self.acquire_latencies.lock() {
+            if latencies.len() == LATENCY_SAMPLES {
+                latencies.pop_front();
+            }
+
+            latencies.push_back(latency);
+        }
+    }
+
+    pub(crate) fn session_returned(&self) {
+        let _ = self
+            .in_use_sessions
+            .fetch_update(Relaxed, Relaxed, |in_use| in_use.checked_sub(1));
+    }
+}
+
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+pub(crate) struct PendingRequest<'a> {
+    metrics: &'a PoolMetrics,
+}
+
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        self.metrics.pending_requests.fetch_sub(1, Relaxed);
+    }
+}
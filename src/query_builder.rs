@@ -1,50 +1,242 @@
-use crate::{Cursor, DocumentCollection, QldbError, QldbResult, Transaction};
+use crate::bulk_load::EncodedDocument;
+use crate::named_params::bind_named_params;
+use crate::serialization::to_ion_value;
+use crate::transport::{ExecuteStatementRequest, SendCommandRequest, ValueHolder};
+use crate::types::QldbExtractError;
+use crate::{Cursor, Document, DocumentCollection, QldbError, QldbResult, QueryStats, Transaction};
+use async_channel::{bounded, Receiver};
 use ion_binary_rs::{IonEncoder, IonParser, IonValue};
-use rusoto_qldb_session::{
-    ExecuteStatementRequest, FetchPageRequest, QldbSession, QldbSessionClient, SendCommandRequest, ValueHolder,
-};
+use serde::Serialize;
+use std::convert::TryFrom;
 use std::fmt::Debug;
 use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
 use std::sync::Arc;
+use std::time::Duration;
+
+/// A page loaded in the background by `QueryBuilder::prefetch_pages`.
+pub(crate) type PrefetchedPage = QldbResult<(Vec<IonValue>, Option<String>, QueryStats)>;
+
+/// Pages loaded by `QueryBuilder::prefetch_pages`, read by the cursor.
+#[derive(Debug)]
+pub(crate) struct PrefetchedPages {
+    pages: Receiver<PrefetchedPage>,
+    // The page the task was holding when it was stopped
+    leftover: Arc<std::sync::Mutex<Option<PrefetchedPage>>>,
+    finished: Receiver<()>,
+}
+
+impl PrefetchedPages {
+    /// Next page in order. It returns None once the task has ended and
+    /// every page it loaded has been returned.
+    pub(crate) async fn next(&self) -> Option<PrefetchedPage> {
+        match self.pages.recv().await {
+            Ok(page) => Some(page),
+            Err(_) => {
+                // The leftover is set just before the task ends
+                let _ = self.finished.recv().await;
+                self.leftover.lock().ok()?.take()
+            }
+        }
+    }
+
+    /// Stops the task, for when the pages aren't going to be read.
+    pub(crate) fn close(&self) {
+        self.pages.close();
+    }
+}
+
+/// The background task started by `QueryBuilder::prefetch_pages`, kept
+/// by the transaction so it can stop it before sending other commands.
+pub(crate) struct Prefetch {
+    pages: Receiver<PrefetchedPage>,
+    finished: Receiver<()>,
+}
+
+impl Prefetch {
+    /// Stops the task and waits until the page it may be loading has
+    /// been answered by QLDB.
+    pub(crate) async fn stop(self) {
+        self.pages.close();
+
+        // Nothing is sent through it, it fails when the task ends
+        let _ = self.finished.recv().await;
+    }
+}
+
+/// A positional param and, when already known, its Ion binary
+/// encoding.
+#[derive(Debug)]
+pub(crate) struct Param {
+    pub(crate) value: IonValue,
+    encoded: Option<Vec<u8>>,
+}
+
+impl Param {
+    fn new(value: IonValue) -> Param {
+        Param { value, encoded: None }
+    }
+}
 
 /// Represents the query being built. It allows to add parameters
 /// and to execute the query.
 pub struct QueryBuilder {
     tx: Transaction,
-    client: Arc<QldbSessionClient>,
     statement: Arc<String>,
-    params: Vec<IonValue>,
+    params: Vec<Param>,
+    named_params: Vec<(String, IonValue)>,
     auto_rollback: bool,
     is_executed: Arc<AtomicBool>,
+    timeout: Option<Duration>,
 }
 
 impl QueryBuilder {
-    pub(crate) fn new(
-        client: Arc<QldbSessionClient>,
-        tx: Transaction,
-        statement: &str,
-        auto_rollback: bool,
-    ) -> QueryBuilder {
+    pub(crate) fn new(tx: Transaction, statement: &str, auto_rollback: bool) -> QueryBuilder {
         QueryBuilder {
-            client,
             tx,
             statement: Arc::new(statement.to_string()),
             params: vec![],
+            named_params: vec![],
             auto_rollback,
             is_executed: Arc::new(AtomicBool::from(false)),
+            timeout: None,
         }
     }
 
+    /// Maximum time for executing the statement and loading all its
+    /// pages with `execute`, `count` or `Cursor::load_all`, or for each
+    /// `Cursor::load_more` call.
+    ///
+    /// When it expires the whole transaction is rolled back, the
+    /// session goes back to the pool and it returns
+    /// `QldbError::Timeout`, telling whether it fired while executing
+    /// the statement or loading a page.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{QldbResult, Transaction};
+    /// use std::time::Duration;
+    ///
+    /// # async fn test(tx: Transaction) -> QldbResult<()> {
+    /// let cars = tx
+    ///     .query("SELECT * FROM Cars")
+    ///     .timeout(Duration::from_secs(2))
+    ///     .execute()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The transaction and the timeout, when there is a timeout.
+    pub(crate) fn timeout_with_transaction(&self) -> Option<(Transaction, Duration)> {
+        self.timeout.map(|timeout| (self.tx.clone(), timeout))
+    }
+
     /// Adds a param to the query. Params in PartiQL are deoned by
     /// the character `?`. For example, the query:
     /// `SELECT * FROM Autos WHERE id = ? AND model = ?`
     /// will need 2 calls to this method. The first will refer to
     /// the first `?` and the second to the second `?`.
     pub fn param<P: Into<IonValue> + Clone>(mut self, param: P) -> Self {
-        self.params.push(param.into());
+        self.params.push(Param::new(param.into()));
         self
     }
 
+    /// Same as `param` for a document already encoded, so it isn't
+    /// encoded again.
+    pub(crate) fn encoded_param(mut self, document: EncodedDocument) -> Self {
+        self.params.push(Param {
+            value: document.value,
+            encoded: Some(document.bytes),
+        });
+        self
+    }
+
+    /// Same as `param`, but it accepts any type implementing
+    /// `serde::Serialize`, which is converted to an IonValue.
+    ///
+    /// Structs and maps become Ion structs, `None` becomes an untyped
+    /// Ion null and `Vec<u8>` a blob. chrono dates and big decimals need
+    /// the helpers in [serialization](crate::serialization) in order to
+    /// be stored as Ion timestamps and decimals, and `None` needs the
+    /// ones in [serialization::null](crate::serialization::null) in
+    /// order to be stored as a typed null, like `null.string`. It fails with
+    /// `QldbError::ParamSerializationError` when the value cannot be
+    /// represented in Ion, like maps with non string keys.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{QldbResult, Transaction};
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Car {
+    ///     plate: String,
+    ///     owner: Option<String>,
+    /// }
+    ///
+    /// # async fn test(tx: Transaction, car: Car) -> QldbResult<()> {
+    /// tx.query("INSERT INTO Cars VALUE ?")
+    ///     .param_serde(&car)?
+    ///     .execute()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn param_serde<P: Serialize + ?Sized>(mut self, param: &P) -> QldbResult<Self> {
+        self.params.push(Param::new(to_ion_value(param)?));
+        Ok(self)
+    }
+
+    /// Adds a param referred by its name. PartiQL only has positional
+    /// params, so the driver replaces every `:name` in the statement by
+    /// `?` and sends the values in the right order. The same name can
+    /// be used several times in the statement.
+    ///
+    /// Placeholders inside string literals, quoted identifiers, Ion
+    /// literals (between backticks) and comments are ignored. When the
+    /// query is executed, a name used in the statement without a value
+    /// returns `QldbError::MissingNamedParam`, a value not used returns
+    /// `QldbError::UnusedNamedParam` and a name given twice returns
+    /// `QldbError::DuplicatedNamedParam`. Named params cannot be mixed
+    /// with `?` and `param` in the same statement.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{QldbResult, Transaction};
+    ///
+    /// # async fn test(tx: Transaction) -> QldbResult<()> {
+    /// tx.query("UPDATE Cars SET owner = :owner WHERE plate = :plate AND owner <> :owner")
+    ///     .named_param("plate", "1234-ABC")
+    ///     .named_param("owner", "Jane")
+    ///     .execute()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn named_param<P: Into<IonValue> + Clone>(mut self, name: &str, param: P) -> Self {
+        self.named_params.push((name.to_string(), param.into()));
+        self
+    }
+
+    /// Replaces the named params in the statement by positional ones.
+    fn bind_named_params(mut self) -> QldbResult<Self> {
+        if self.named_params.is_empty() {
+            return Ok(self);
+        }
+
+        if !self.params.is_empty() {
+            return Err(QldbError::MixedParams);
+        }
+
+        let (statement, params) = bind_named_params(&self.statement, std::mem::take(&mut self.named_params))?;
+
+        self.statement = Arc::new(statement);
+        self.params = params.into_iter().map(Param::new).collect();
+
+        Ok(self)
+    }
+
     /// Executes the query in QLDBwith the parameter provided by
     /// the `param` method. It will return a Vector of Ion Values,
     /// one for each document returned.
@@ -67,33 +259,165 @@ impl QueryBuilder {
         Ok(result)
     }
 
-    pub(crate) async fn execute_get_page(&mut self, page_token: &str) -> QldbResult<(Vec<IonValue>, Option<String>)> {
-        let result = self
-            .client
-            .send_command(create_next_page_command(
-                self.tx.session.get_session_id(),
-                &self.tx.transaction_id,
-                page_token,
-            ))
-            .await?;
+    /// Executes the query and returns its only document. It fails with
+    /// `QldbError::UnexpectedRowCount` when the statement returns no
+    /// documents or more than one.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{QldbResult, Transaction};
+    ///
+    /// # async fn test(tx: Transaction) -> QldbResult<()> {
+    /// let car = tx
+    ///     .query("SELECT * FROM Cars WHERE plate = ?")
+    ///     .param("1234-ABC")
+    ///     .fetch_one()
+    ///     .await?;
+    ///
+    /// let owner: String = car.get_value("owner")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch_one(self) -> QldbResult<Document> {
+        let mut documents = self.execute().await?.into_inner();
+
+        match documents.len() {
+            1 => Ok(documents.remove(0)),
+            got => Err(QldbError::UnexpectedRowCount { expected: 1, got }),
+        }
+    }
 
-        let (values, next_page_token) = result
-            .fetch_page
-            .and_then(|page| page.page)
-            .map(|page| {
-                // Default of Vec is empty Vec
-                let values = page.values.unwrap_or_default();
+    /// Same as `fetch_one`, but it returns `None` when the statement
+    /// returns no documents.
+    pub async fn fetch_optional(self) -> QldbResult<Option<Document>> {
+        let mut documents = self.execute().await?.into_inner();
 
-                (values, page.next_page_token)
+        match documents.len() {
+            0 | 1 => Ok(documents.pop()),
+            got => Err(QldbError::UnexpectedRowCount { expected: 1, got }),
+        }
+    }
+
+    /// Executes a query returning a single value, like `SELECT VALUE
+    /// owner FROM Cars WHERE plate = ?`, and converts it to `T`.
+    ///
+    /// It fails with `QldbError::UnexpectedRowCount` when there isn't
+    /// exactly one value and with `QldbExtractError::BadDataType` when it
+    /// cannot be converted.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{QldbResult, Transaction};
+    ///
+    /// # async fn test(tx: Transaction) -> QldbResult<()> {
+    /// let points: i64 = tx
+    ///     .query("SELECT VALUE c.points FROM Cars AS c WHERE c.plate = ?")
+    ///     .param("1234-ABC")
+    ///     .execute_scalar()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn execute_scalar<T>(self) -> QldbResult<T>
+    where
+        T: TryFrom<IonValue>,
+        <T as TryFrom<IonValue>>::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let auto_rollback = self.auto_rollback;
+        let tx = self.tx.clone();
+
+        let mut values = self.get_cursor()?.load_all_values().await?;
+
+        if auto_rollback {
+            tx.rollback().await?;
+        }
+
+        if values.len() != 1 {
+            return Err(QldbError::UnexpectedRowCount {
+                expected: 1,
+                got: values.len(),
+            });
+        }
+
+        T::try_from(values.remove(0)).map_err(|error| QldbExtractError::BadDataType(Box::new(error)).into())
+    }
+
+    /// Same as `execute`, but converting every document to `T`.
+    pub async fn execute_as<T>(self) -> QldbResult<Vec<T>>
+    where
+        T: TryFrom<Document>,
+        <T as TryFrom<Document>>::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.execute()
+            .await?
+            .into_iter()
+            .map(|document| {
+                T::try_from(document).map_err(|error| QldbExtractError::BadDataType(Box::new(error)).into())
             })
-            .unwrap_or((vec![], None));
+            .collect()
+    }
 
-        let values = valueholders_to_ionvalues(values)?;
+    pub(crate) async fn execute_get_page(
+        &mut self,
+        page_token: &str,
+    ) -> QldbResult<(Vec<IonValue>, Option<String>, QueryStats)> {
+        self.tx.fetch_page(page_token).await
+    }
 
-        Ok((values, next_page_token))
+    /// Starts a background task that loads the pages following
+    /// `page_token`. The channel holds up to `depth` pages, so the task
+    /// waits for the cursor when it gets that far ahead. The task stops
+    /// when the channel is closed, on the last page or after an error.
+    ///
+    /// The transaction stops it before sending any other command. The
+    /// page already loaded then is kept for the cursor, which loads the
+    /// remaining ones itself.
+    pub(crate) fn prefetch_pages(&self, page_token: String, depth: usize) -> PrefetchedPages {
+        let (sender, receiver) = bounded::<PrefetchedPage>(depth);
+        let (finished_sender, finished) = bounded::<()>(1);
+        let leftover = Arc::new(std::sync::Mutex::new(None));
+        let task_leftover = leftover.clone();
+        let tx = self.tx.clone();
+
+        self.tx.set_prefetch(Prefetch {
+            pages: receiver.clone(),
+            finished: finished.clone(),
+        });
+
+        self.tx.spawn(Box::pin(async move {
+            // Dropped when the task ends, which tells `Prefetch::stop`
+            let _finished = finished_sender;
+            let mut next_page_token = Some(page_token);
+
+            while let Some(page_token) = next_page_token.take() {
+                if sender.is_closed() || tx.is_completed().await {
+                    break;
+                }
+
+                let page = tx.load_page(&page_token).await;
+
+                if let Ok((_, next, _)) = &page {
+                    next_page_token = next.clone();
+                }
+
+                if let Err(error) = sender.send(page).await {
+                    if let Ok(mut leftover) = task_leftover.lock() {
+                        *leftover = Some(error.into_inner());
+                    }
+
+                    break;
+                }
+            }
+        }));
+
+        PrefetchedPages {
+            pages: receiver,
+            leftover,
+            finished,
+        }
     }
 
-    pub(crate) async fn execute_statement(&mut self) -> QldbResult<(Vec<IonValue>, Option<String>)> {
+    pub(crate) async fn execute_statement(&mut self) -> QldbResult<(Vec<IonValue>, Option<String>, QueryStats)> {
+        self.tx.stop_prefetch().await;
+
         if self.tx.is_completed().await {
             return Err(QldbError::TransactionCompleted);
         }
@@ -112,7 +436,7 @@ impl QueryBuilder {
         self.is_executed.store(true, Relaxed);
 
         let result = self
-            .client
+            .tx
             .send_command(create_send_command(
                 self.tx.session.get_session_id(),
                 &self.tx.transaction_id,
@@ -121,9 +445,13 @@ impl QueryBuilder {
             ))
             .await?;
 
-        let (values, next_page_token) = result
-            .execute_statement
-            .and_then(|result| result.first_page)
+        let execute_statement = result.execute_statement.unwrap_or_default();
+
+        let stats = QueryStats::from_result(execute_statement.consumed_ios, execute_statement.timing_information);
+        self.tx.add_stats(stats).await;
+
+        let (values, next_page_token) = execute_statement
+            .first_page
             .map(|result| {
                 // Default of Vec is empty Vec
                 let values = result.values.unwrap_or_default();
@@ -134,7 +462,7 @@ impl QueryBuilder {
 
         let values = valueholders_to_ionvalues(values)?;
 
-        Ok((values, next_page_token))
+        Ok((values, next_page_token, stats))
     }
 
     /// Creates a cursor for this query, allowing to load values
@@ -144,11 +472,33 @@ impl QueryBuilder {
             return Err(QldbError::QueryAlreadyExecuted);
         }
 
-        Ok(Cursor::new(self))
+        Ok(Cursor::new(self.bind_named_params()?))
+    }
+
+    /// Creates a cursor that loads the following pages in the
+    /// background while the current one is being processed. Up to
+    /// `pages` pages wait in the cursor, plus the one the background
+    /// task has just loaded, so memory use stays bounded. With `0` it
+    /// behaves as `get_cursor`.
+    ///
+    /// Pages are fetched through the same transaction. Sending another
+    /// statement, committing or rolling back first waits for the page
+    /// being loaded and stops the prefetch, and the cursor loads the
+    /// remaining pages on demand after that.
+    pub fn get_cursor_with_prefetch(self, pages: usize) -> QldbResult<Cursor> {
+        if self.is_executed.load(Relaxed) {
+            return Err(QldbError::QueryAlreadyExecuted);
+        }
+
+        Ok(Cursor::new(self.bind_named_params()?).with_prefetch(pages))
     }
 
-    /// Sends a query to QLDB that returns a count. Keep in mind that there isn't
-    /// any filter to fail is another kind of statement is given.
+    /// Sends a query to QLDB that returns a count, either `SELECT COUNT(*)`
+    /// or `SELECT VALUE COUNT(*)`.
+    ///
+    /// Like `execute_scalar`, it fails with `QldbError::UnexpectedRowCount`
+    /// when the statement doesn't return exactly one value, and with
+    /// `QldbError::NonValidCountStatementResult` when that value isn't a count.
     ///
     /// Be careful with COUNT statements as they "block" the whole table and other
     /// transactions affecting the same table will return an OCC error when committed.
@@ -158,11 +508,10 @@ impl QueryBuilder {
     ///
     /// It consumes the QueryBuilder in the process.
     pub async fn count(self) -> QldbResult<i64> {
-        let result = self.execute().await?;
-
-        match result.into_inner().last() {
-            Some(doc) => match doc.get("_1") {
-                Some(IonValue::Integer(count)) => Ok(*count),
+        match self.execute_scalar::<IonValue>().await? {
+            IonValue::Integer(count) => Ok(count),
+            IonValue::Struct(mut values) => match values.remove("_1") {
+                Some(IonValue::Integer(count)) if values.is_empty() => Ok(count),
                 _ => Err(QldbError::NonValidCountStatementResult),
             },
             _ => Err(QldbError::NonValidCountStatementResult),
@@ -177,12 +526,14 @@ impl Debug for QueryBuilder {
             .field("tx", &self.tx)
             .field("statement", &self.statement)
             .field("params", &self.params)
+            .field("named_params", &self.named_params)
             .field("auto_rollback", &self.auto_rollback)
+            .field("timeout", &self.timeout)
             .finish()
     }
 }
 
-fn valueholders_to_ionvalues(values: Vec<ValueHolder>) -> QldbResult<Vec<IonValue>> {
+pub(crate) fn valueholders_to_ionvalues(values: Vec<ValueHolder>) -> QldbResult<Vec<IonValue>> {
     let mut decoded_values = vec![];
 
     for value in values {
@@ -205,42 +556,28 @@ fn valueholders_to_ionvalues(values: Vec<ValueHolder>) -> QldbResult<Vec<IonValu
     Ok(decoded_values)
 }
 
-fn create_send_command(
-    session: &str,
-    transaction_id: &str,
-    statement: &str,
-    params: Vec<IonValue>,
-) -> SendCommandRequest {
+fn create_send_command(session: &str, transaction_id: &str, statement: &str, params: Vec<Param>) -> SendCommandRequest {
     SendCommandRequest {
         session_token: Some(session.to_string()),
         execute_statement: Some(ExecuteStatementRequest {
             statement: statement.to_string(),
-            parameters: Some(params.into_iter().map(ionvalue_to_valueholder).collect()),
-            transaction_id: transaction_id.to_string(),
-        }),
-        ..Default::default()
-    }
-}
-
-fn create_next_page_command(session: &str, transaction_id: &str, next_page_token: &str) -> SendCommandRequest {
-    SendCommandRequest {
-        session_token: Some(session.to_string()),
-        fetch_page: Some(FetchPageRequest {
+            parameters: Some(params.into_iter().map(param_to_valueholder).collect()),
             transaction_id: transaction_id.to_string(),
-            next_page_token: next_page_token.to_string(),
         }),
         ..Default::default()
     }
 }
 
-fn ionvalue_to_valueholder(value: IonValue) -> ValueHolder {
+fn param_to_valueholder(param: Param) -> ValueHolder {
     // TODO: Add impl From<IonValue> for ValueHolder in ion_binary_rs
-    let mut encoder = IonEncoder::new();
-    encoder.add(value);
-    let bytes = encoder.encode();
+    let bytes = param.encoded.unwrap_or_else(|| {
+        let mut encoder = IonEncoder::new();
+        encoder.add(param.value);
+        encoder.encode()
+    });
 
     ValueHolder {
         ion_text: None,
-        ion_binary: Some(bytes.into()),
+        ion_binary: Some(bytes),
     }
 }
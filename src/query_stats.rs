@@ -0,0 +1,93 @@
+use crate::transport::{IoUsage, TimingInformation};
+use std::ops::{Add, AddAssign};
+use std::time::Duration;
+
+/// Consumed IOs and server processing time reported by QLDB.
+///
+/// Every page of every statement, and the commit, report their own
+/// values. [DocumentCollection](crate::DocumentCollection) and
+/// [Cursor](crate::Cursor) carry the sum for their pages and
+/// [Transaction](crate::Transaction) the sum for the whole transaction.
+///
+/// ```rust,no_run
+/// use qldb::{QldbClient, QldbResult};
+///
+/// # async fn test(client: QldbClient) -> QldbResult<()> {
+/// let transaction = client.transaction().await?;
+///
+/// let cars = transaction.query("SELECT * FROM Cars").execute().await?;
+///
+/// println!("Select used {} read IOs", cars.stats().read_ios());
+///
+/// transaction.commit().await?;
+///
+/// let total = transaction.stats().await;
+///
+/// println!("Transaction used {} read IOs and {} write IOs", total.read_ios(), total.write_ios());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    read_ios: u64,
+    write_ios: u64,
+    processing_time: Duration,
+}
+
+impl QueryStats {
+    pub(crate) fn from_result(
+        consumed_ios: Option<IoUsage>,
+        timing_information: Option<TimingInformation>,
+    ) -> QueryStats {
+        let consumed_ios = consumed_ios.unwrap_or_default();
+
+        let processing_time = timing_information
+            .and_then(|timing| timing.processing_time_milliseconds)
+            .unwrap_or_default();
+
+        QueryStats {
+            read_ios: to_u64(consumed_ios.read_ios),
+            write_ios: to_u64(consumed_ios.write_ios),
+            processing_time: Duration::from_millis(to_u64(Some(processing_time))),
+        }
+    }
+
+    /// IOs used for reading documents.
+    pub fn read_ios(&self) -> u64 {
+        self.read_ios
+    }
+
+    /// IOs used for writing documents.
+    pub fn write_ios(&self) -> u64 {
+        self.write_ios
+    }
+
+    /// Time QLDB spent processing the requests. It doesn't include the
+    /// network round trips.
+    pub fn processing_time(&self) -> Duration {
+        self.processing_time
+    }
+}
+
+impl Add for QueryStats {
+    type Output = QueryStats;
+
+    fn add(self, other: QueryStats) -> QueryStats {
+        QueryStats {
+            read_ios: self.read_ios + other.read_ios,
+            write_ios: self.write_ios + other.write_ios,
+            processing_time: self.processing_time + other.processing_time,
+        }
+    }
+}
+
+impl AddAssign for QueryStats {
+    fn add_assign(&mut self, other: QueryStats) {
+        *self = *self + other;
+    }
+}
+
+// QLDB uses signed numbers, but they are never negative.
+fn to_u64(value: Option<i64>) -> u64 {
+    value.unwrap_or_default().max(0) as u64
+}
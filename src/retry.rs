@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+/// Controls how `QldbClient::transaction_within_retry` retries a
+/// transaction when it fails with an OCC conflict, an invalid session
+/// or a transient transport error.
+///
+/// The delay between attempts grows exponentially from `base_delay`
+/// and it is capped at `max_delay`, in the same way the official QLDB
+/// drivers do. Each delay is randomized between half and the whole of
+/// it, so transactions that conflicted with each other don't retry at
+/// the same time again.
+///
+/// A commit that fails without an answer from QLDB, like when the
+/// connection is lost, may have been applied anyway. These commits are
+/// not retried unless `retry_unknown_commits` is enabled.
+///
+/// ```rust
+/// use qldb::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(4)
+///     .base_delay(Duration::from_millis(10))
+///     .max_delay(Duration::from_millis(5000));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    retry_unknown_commits: bool,
+}
+
+impl RetryPolicy {
+    /// Creates a policy that retries a transaction up to `max_retries`
+    /// times after the first attempt.
+    pub fn new(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// A policy that never retries.
+    pub fn no_retries() -> RetryPolicy {
+        RetryPolicy::new(0)
+    }
+
+    /// Delay before the first retry. Every following retry doubles it.
+    pub fn base_delay(mut self, base_delay: Duration) -> RetryPolicy {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Upper bound for the delay between two attempts.
+    pub fn max_delay(mut self, max_delay: Duration) -> RetryPolicy {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Retries the transactions whose commit failed without an answer
+    /// from QLDB. Disabled by default.
+    ///
+    /// QLDB may have committed the transaction before the answer was
+    /// lost, so the retry can apply the closure twice. Enable it only
+    /// when running the transaction again is harmless, like when it
+    /// checks first whether its changes are already there.
+    pub fn retry_unknown_commits(mut self, retry_unknown_commits: bool) -> RetryPolicy {
+        self.retry_unknown_commits = retry_unknown_commits;
+        self
+    }
+
+    pub fn get_max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    pub fn get_retry_unknown_commits(&self) -> bool {
+        self.retry_unknown_commits
+    }
+
+    /// Returns how long to wait before the given attempt, starting at 1
+    /// for the first retry.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(31);
+
+        let delay = self
+            .base_delay
+            .checked_mul(1u32 << exponent)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        delay / 2 + (delay / 2).mul_f64(fastrand::f64())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 4,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(5000),
+            retry_unknown_commits: false,
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::serialization::SerializationError;
+use ion_binary_rs::IonValue;
+use num_traits::ToPrimitive;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+use std::collections::hash_map;
+use std::vec;
+
+/// Deserializes any type implementing `serde::Deserialize` from an
+/// IonValue.
+///
+/// Timestamps are given to serde as RFC 3339 strings and decimals as
+/// strings, which is what chrono and bigdecimal expect when their
+/// `serde` feature is enabled. Blobs and clobs can be deserialized as
+/// `Vec<u8>` or as bytes.
+pub(crate) fn from_ion_value<T: DeserializeOwned>(value: IonValue) -> Result<T, SerializationError> {
+    T::deserialize(IonDeserializer(value))
+}
+
+struct IonDeserializer(IonValue);
+
+impl<'de> de::Deserializer<'de> for IonDeserializer {
+    type Error = SerializationError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            IonValue::Null(_) => visitor.visit_unit(),
+            IonValue::Bool(value) => visitor.visit_bool(value),
+            IonValue::Integer(value) => visitor.visit_i64(value),
+            IonValue::BigInteger(value) => {
+                if let Some(value) = value.to_i64() {
+                    visitor.visit_i64(value)
+                } else if let Some(value) = value.to_u64() {
+                    visitor.visit_u64(value)
+                } else if let Some(value) = value.to_i128() {
+                    visitor.visit_i128(value)
+                } else if let Some(value) = value.to_u128() {
+                    visitor.visit_u128(value)
+                } else {
+                    visitor.visit_string(value.to_string())
+                }
+            }
+            IonValue::Float(value) => visitor.visit_f64(value),
+            IonValue::Decimal(value) => visitor.visit_string(value.to_string()),
+            IonValue::DateTime(value) => visitor.visit_string(value.to_rfc3339()),
+            IonValue::String(value) | IonValue::Symbol(value) => visitor.visit_string(value),
+            IonValue::Clob(value) | IonValue::Blob(value) => visitor.visit_byte_buf(value),
+            IonValue::List(values) | IonValue::SExpr(values) => visit_seq(values, visitor),
+            IonValue::Struct(values) => visitor.visit_map(StructAccess {
+                fields: values.into_iter(),
+                value: None,
+            }),
+            IonValue::Annotation(_, value) => IonDeserializer(*value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            IonValue::Decimal(value) => match value.to_f64() {
+                Some(value) => visitor.visit_f64(value),
+                None => Err(de::Error::custom(format!("decimal {} doesn't fit in a float", value))),
+            },
+            IonValue::Annotation(_, value) => IonDeserializer(*value).deserialize_f64(visitor),
+            value => IonDeserializer(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            IonValue::Null(_) => visitor.visit_none(),
+            value => visitor.visit_some(IonDeserializer(value)),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            IonValue::Clob(bytes) | IonValue::Blob(bytes) => visit_seq(
+                bytes.into_iter().map(|byte| IonValue::Integer(byte.into())).collect(),
+                visitor,
+            ),
+            IonValue::Annotation(_, value) => IonDeserializer(*value).deserialize_seq(visitor),
+            value => IonDeserializer(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            IonValue::String(variant) | IonValue::Symbol(variant) => visitor.visit_enum(variant.into_deserializer()),
+            IonValue::Struct(values) if values.len() == 1 => {
+                let (variant, value) = values.into_iter().next().expect("Struct has one field");
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            IonValue::Annotation(_, value) => IonDeserializer(*value).deserialize_enum(_name, _variants, visitor),
+            value => Err(de::Error::custom(format!(
+                "expected a string or a struct with a single field for an enum, found {:?}",
+                value
+            ))),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string
+        bytes byte_buf unit unit_struct map struct identifier
+    }
+}
+
+fn visit_seq<'de, V: Visitor<'de>>(values: Vec<IonValue>, visitor: V) -> Result<V::Value, SerializationError> {
+    let length = values.len();
+
+    let mut access = ListAccess {
+        values: values.into_iter(),
+        index: 0,
+    };
+
+    let result = visitor.visit_seq(&mut access)?;
+
+    if access.values.len() == 0 {
+        Ok(result)
+    } else {
+        Err(de::Error::invalid_length(length, &"fewer elements in the list"))
+    }
+}
+
+struct ListAccess {
+    values: vec::IntoIter<IonValue>,
+    index: usize,
+}
+
+impl<'de> SeqAccess<'de> for ListAccess {
+    type Error = SerializationError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> {
+        let value = match self.values.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let index = self.index;
+        self.index += 1;
+
+        seed.deserialize(IonDeserializer(value))
+            .map(Some)
+            .map_err(|error| error.in_index(index))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+struct StructAccess {
+    fields: hash_map::IntoIter<String, IonValue>,
+    value: Option<(String, IonValue)>,
+}
+
+impl<'de> MapAccess<'de> for StructAccess {
+    type Error = SerializationError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        let (name, value) = match self.fields.next() {
+            Some(field) => field,
+            None => return Ok(None),
+        };
+
+        let key = seed
+            .deserialize(name.clone().into_deserializer())
+            .map_err(|error: SerializationError| error.in_field(&name))?;
+
+        self.value = Some((name, value));
+
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        let (name, value) = self
+            .value
+            .take()
+            .ok_or_else(|| <SerializationError as de::Error>::custom("value requested before its key"))?;
+
+        seed.deserialize(IonDeserializer(value))
+            .map_err(|error| error.in_field(&name))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: IonValue,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = SerializationError;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(self.variant.clone().into_deserializer())?;
+
+        Ok((
+            variant,
+            VariantDeserializer {
+                name: self.variant,
+                value: self.value,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer {
+    name: String,
+    value: IonValue,
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = SerializationError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        de::Deserialize::deserialize(IonDeserializer(self.value))
+            .map_err(|error: SerializationError| error.in_field(&self.name))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Self::Error> {
+        seed.deserialize(IonDeserializer(self.value))
+            .map_err(|error| error.in_field(&self.name))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_seq(IonDeserializer(self.value), visitor)
+            .map_err(|error| error.in_field(&self.name))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_map(IonDeserializer(self.value), visitor)
+            .map_err(|error| error.in_field(&self.name))
+    }
+}
@@ -0,0 +1,58 @@
+//! Stores a `BigDecimal` as an Ion decimal.
+//!
+//! bigdecimal serializes its values as strings. Use this module with
+//! `#[serde(with = "qldb::serialization::decimal")]` in order to store
+//! them as Ion decimals instead. Other serializers keep receiving the
+//! string.
+//!
+//! ```rust
+//! use bigdecimal::BigDecimal;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Car {
+//!     #[serde(with = "qldb::serialization::decimal")]
+//!     price: BigDecimal,
+//! }
+//! ```
+
+use crate::serialization::DECIMAL_TOKEN;
+use bigdecimal::BigDecimal;
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+use std::fmt;
+use std::str::FromStr;
+
+pub fn serialize<S: Serializer>(value: &BigDecimal, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_newtype_struct(DECIMAL_TOKEN, &value.to_string())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigDecimal, D::Error> {
+    deserializer.deserialize_any(DecimalVisitor)
+}
+
+struct DecimalVisitor;
+
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = BigDecimal;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a decimal number")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<BigDecimal, E> {
+        BigDecimal::from_str(value).map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<BigDecimal, E> {
+        Ok(BigDecimal::from(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<BigDecimal, E> {
+        Ok(BigDecimal::from(value))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<BigDecimal, E> {
+        BigDecimal::try_from(value).map_err(E::custom)
+    }
+}
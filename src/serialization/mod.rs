@@ -0,0 +1,117 @@
+//! Conversion between serde types and Ion.
+//!
+//! Documents can be deserialized with `Document::deserialize` and
+//! parameters serialized with `QueryBuilder::param_serde`. The modules
+//! `timestamp` and `decimal` are meant for `#[serde(with = "...")]`
+//! attributes, so chrono dates and big decimals are stored with their
+//! Ion types instead of as strings, and the ones in `null` for storing
+//! `None` as a typed null.
+
+mod de;
+pub mod decimal;
+pub mod null;
+mod ser;
+pub mod timestamp;
+
+use crate::{QldbError, QldbExtractError};
+use std::fmt::{self, Display};
+
+pub(crate) use de::from_ion_value;
+pub(crate) use ser::to_ion_value;
+
+// Newtype names used for telling the Ion serializer that the string it
+// receives is a timestamp or a decimal.
+const TIMESTAMP_TOKEN: &str = "$qldb::private::Timestamp";
+const DECIMAL_TOKEN: &str = "$qldb::private::Decimal";
+
+/// Error raised by serde while converting to or from IonValue. It keeps
+/// the path of the field that failed, so `QldbExtractError` can point
+/// to it, e.g. `owner.addresses[2].zip_code`.
+#[derive(Debug)]
+pub(crate) struct SerializationError {
+    path: Vec<PathSegment>,
+    message: String,
+}
+
+#[derive(Debug)]
+enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl SerializationError {
+    pub(crate) fn in_field(mut self, field: &str) -> SerializationError {
+        self.path.insert(0, PathSegment::Field(field.to_string()));
+        self
+    }
+
+    pub(crate) fn in_index(mut self, index: usize) -> SerializationError {
+        self.path.insert(0, PathSegment::Index(index));
+        self
+    }
+
+    fn path(&self) -> String {
+        let mut path = String::new();
+
+        for segment in &self.path {
+            match segment {
+                PathSegment::Field(field) if path.is_empty() => path.push_str(field),
+                PathSegment::Field(field) => {
+                    path.push('.');
+                    path.push_str(field);
+                }
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+
+        path
+    }
+}
+
+impl Display for SerializationError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(formatter, "{}", self.message)
+        } else {
+            write!(formatter, "{}: {}", self.path(), self.message)
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+impl serde::de::Error for SerializationError {
+    fn custom<T: Display>(message: T) -> SerializationError {
+        SerializationError {
+            path: vec![],
+            message: message.to_string(),
+        }
+    }
+}
+
+impl serde::ser::Error for SerializationError {
+    fn custom<T: Display>(message: T) -> SerializationError {
+        SerializationError {
+            path: vec![],
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<SerializationError> for QldbError {
+    fn from(error: SerializationError) -> QldbError {
+        QldbError::ParamSerializationError {
+            path: error.path(),
+            message: error.message,
+        }
+    }
+}
+
+impl From<SerializationError> for QldbExtractError {
+    fn from(error: SerializationError) -> QldbExtractError {
+        QldbExtractError::Deserialize {
+            path: error.path(),
+            message: error.message,
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Stores `None` as a typed Ion null, like `null.string`, instead of the
+//! untyped `null`.
+//!
+//! serde has a single kind of null, so `Option` fields serialize `None`
+//! as `null`. Use the module of the Ion type with
+//! `#[serde(with = "qldb::serialization::null::<type>")]` when the
+//! ledger needs to know the type of missing values. `Some` values are
+//! serialized as usual and other serializers, like serde_json, keep
+//! receiving a plain null.
+//!
+//! ```rust
+//! use bigdecimal::BigDecimal;
+//! use chrono::{DateTime, Utc};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Car {
+//!     #[serde(with = "qldb::serialization::null::string")]
+//!     owner: Option<String>,
+//!     #[serde(with = "qldb::serialization::null::integer")]
+//!     doors: Option<u8>,
+//!     #[serde(with = "qldb::serialization::null::timestamp")]
+//!     sold_on: Option<DateTime<Utc>>,
+//!     #[serde(with = "qldb::serialization::null::decimal")]
+//!     price: Option<BigDecimal>,
+//! }
+//! ```
+//!
+//! The `timestamp` and `decimal` modules store `Some` values as the
+//! [timestamp](crate::serialization::timestamp) and
+//! [decimal](crate::serialization::decimal) modules do.
+
+use ion_binary_rs::NullIonValue;
+
+// Newtype names used for telling the Ion serializer the type of the
+// null it receives.
+const NULL_BOOL_TOKEN: &str = "$qldb::private::Null::Bool";
+const NULL_INTEGER_TOKEN: &str = "$qldb::private::Null::Integer";
+const NULL_FLOAT_TOKEN: &str = "$qldb::private::Null::Float";
+const NULL_DECIMAL_TOKEN: &str = "$qldb::private::Null::Decimal";
+const NULL_TIMESTAMP_TOKEN: &str = "$qldb::private::Null::Timestamp";
+const NULL_STRING_TOKEN: &str = "$qldb::private::Null::String";
+const NULL_BLOB_TOKEN: &str = "$qldb::private::Null::Blob";
+const NULL_LIST_TOKEN: &str = "$qldb::private::Null::List";
+const NULL_STRUCT_TOKEN: &str = "$qldb::private::Null::Struct";
+
+/// The typed null a newtype name stands for, if any.
+pub(crate) fn typed_null(name: &str) -> Option<NullIonValue> {
+    match name {
+        NULL_BOOL_TOKEN => Some(NullIonValue::Bool),
+        NULL_INTEGER_TOKEN => Some(NullIonValue::Integer),
+        NULL_FLOAT_TOKEN => Some(NullIonValue::Float),
+        NULL_DECIMAL_TOKEN => Some(NullIonValue::Decimal),
+        NULL_TIMESTAMP_TOKEN => Some(NullIonValue::DateTime),
+        NULL_STRING_TOKEN => Some(NullIonValue::String),
+        NULL_BLOB_TOKEN => Some(NullIonValue::Blob),
+        NULL_LIST_TOKEN => Some(NullIonValue::List),
+        NULL_STRUCT_TOKEN => Some(NullIonValue::Struct),
+        _ => None,
+    }
+}
+
+macro_rules! typed_null_module {
+    ($module:ident, $token:ident, $ion_type:literal) => {
+        #[doc = concat!("Serializes `None` as `", $ion_type, "`.")]
+        pub mod $module {
+            use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+            pub fn serialize<S, T>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+                T: Serialize,
+            {
+                match value {
+                    Some(value) => value.serialize(serializer),
+                    None => serializer.serialize_newtype_struct(super::$token, &()),
+                }
+            }
+
+            pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+            where
+                D: Deserializer<'de>,
+                T: Deserialize<'de>,
+            {
+                Option::<T>::deserialize(deserializer)
+            }
+        }
+    };
+}
+
+typed_null_module!(boolean, NULL_BOOL_TOKEN, "null.bool");
+typed_null_module!(integer, NULL_INTEGER_TOKEN, "null.int");
+typed_null_module!(float, NULL_FLOAT_TOKEN, "null.float");
+typed_null_module!(string, NULL_STRING_TOKEN, "null.string");
+typed_null_module!(blob, NULL_BLOB_TOKEN, "null.blob");
+typed_null_module!(list, NULL_LIST_TOKEN, "null.list");
+typed_null_module!(structure, NULL_STRUCT_TOKEN, "null.struct");
+
+/// Serializes `None` as `null.timestamp` and `Some` as an Ion timestamp.
+pub mod timestamp {
+    use chrono::{DateTime, FixedOffset, TimeZone};
+    use serde::{Deserializer, Serializer};
+    use std::fmt::Display;
+
+    pub fn serialize<S, Tz>(value: &Option<DateTime<Tz>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        Tz: TimeZone,
+        Tz::Offset: Display,
+    {
+        match value {
+            Some(value) => crate::serialization::timestamp::serialize(value, serializer),
+            None => serializer.serialize_newtype_struct(super::NULL_TIMESTAMP_TOKEN, &()),
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: From<DateTime<FixedOffset>>,
+    {
+        crate::serialization::timestamp::option::deserialize(deserializer)
+    }
+}
+
+/// Serializes `None` as `null.decimal` and `Some` as an Ion decimal.
+pub mod decimal {
+    use bigdecimal::BigDecimal;
+    use serde::de::{Deserializer, Visitor};
+    use serde::Serializer;
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(value: &Option<BigDecimal>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => crate::serialization::decimal::serialize(value, serializer),
+            None => serializer.serialize_newtype_struct(super::NULL_DECIMAL_TOKEN, &()),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<BigDecimal>, D::Error> {
+        deserializer.deserialize_option(OptionVisitor)
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<BigDecimal>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an optional decimal number")
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            crate::serialization::decimal::deserialize(deserializer).map(Some)
+        }
+    }
+}
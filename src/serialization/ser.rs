@@ -0,0 +1,400 @@
+use crate::serialization::null::typed_null;
+use crate::serialization::{SerializationError, DECIMAL_TOKEN, TIMESTAMP_TOKEN};
+use bigdecimal::BigDecimal;
+use chrono::DateTime;
+use ion_binary_rs::{IonValue, NullIonValue};
+use num_bigint::BigInt;
+use serde::ser::{self, Serialize};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Serializes any type implementing `serde::Serialize` into an IonValue.
+///
+/// `None` and unit values become untyped Ion nulls, unless the `null`
+/// helper modules are used, sequences of `u8` become blobs and enums
+/// follow the serde's externally tagged representation. Timestamps and
+/// decimals need the `timestamp` and `decimal` helper modules, otherwise
+/// they are serialized as strings.
+pub(crate) fn to_ion_value<T: Serialize + ?Sized>(value: &T) -> Result<IonValue, SerializationError> {
+    value.serialize(IonSerializer::default())
+}
+
+#[derive(Default)]
+struct IonSerializer<'a> {
+    // Set when the value serialized is an u8, so sequences made only of
+    // them can be stored as blobs.
+    is_byte: Option<&'a Cell<bool>>,
+}
+
+impl<'a> ser::Serializer for IonSerializer<'a> {
+    type Ok = IonValue;
+    type Error = SerializationError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = StructSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = VariantSerializer<StructSerializer>;
+
+    fn serialize_bool(self, value: bool) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::Bool(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<IonValue, Self::Error> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<IonValue, Self::Error> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<IonValue, Self::Error> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::Integer(value))
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<IonValue, Self::Error> {
+        Ok(match i64::try_from(value) {
+            Ok(value) => IonValue::Integer(value),
+            Err(_) => IonValue::BigInteger(BigInt::from(value)),
+        })
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<IonValue, Self::Error> {
+        if let Some(is_byte) = self.is_byte {
+            is_byte.set(true);
+        }
+
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<IonValue, Self::Error> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<IonValue, Self::Error> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<IonValue, Self::Error> {
+        self.serialize_i128(value.into())
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<IonValue, Self::Error> {
+        Ok(match i64::try_from(value) {
+            Ok(value) => IonValue::Integer(value),
+            Err(_) => IonValue::BigInteger(BigInt::from(value)),
+        })
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<IonValue, Self::Error> {
+        self.serialize_f64(value.into())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::Float(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::String(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::String(value.to_string()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::Blob(value.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::Null(NullIonValue::Null))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<IonValue, Self::Error> {
+        // A sequence of Option<u8> can hold nulls, so it is never a blob
+        value.serialize(IonSerializer { is_byte: None })
+    }
+
+    fn serialize_unit(self) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::Null(NullIonValue::Null))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<IonValue, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<IonValue, Self::Error> {
+        if let Some(null) = typed_null(name) {
+            return Ok(IonValue::Null(null));
+        }
+
+        let inner = value.serialize(IonSerializer::default())?;
+
+        match (name, inner) {
+            (TIMESTAMP_TOKEN, IonValue::String(value)) => DateTime::parse_from_rfc3339(&value)
+                .map(IonValue::DateTime)
+                .map_err(|error| ser::Error::custom(format!("invalid timestamp {}: {}", value, error))),
+            (DECIMAL_TOKEN, IonValue::String(value)) => BigDecimal::from_str(&value)
+                .map(IonValue::Decimal)
+                .map_err(|error| ser::Error::custom(format!("invalid decimal {}: {}", value, error))),
+            (_, inner) => Ok(inner),
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<IonValue, Self::Error> {
+        let value = value
+            .serialize(IonSerializer::default())
+            .map_err(|error| error.in_field(variant))?;
+
+        Ok(wrap_variant(variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SeqSerializer {
+            values: Vec::with_capacity(len.unwrap_or_default()),
+            all_bytes: true,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(StructSerializer {
+            fields: HashMap::with_capacity(len.unwrap_or_default()),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SeqSerializer {
+    values: Vec<IonValue>,
+    all_bytes: bool,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = IonValue;
+    type Error = SerializationError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let is_byte = Cell::new(false);
+        let index = self.values.len();
+
+        let value = value
+            .serialize(IonSerializer {
+                is_byte: Some(&is_byte),
+            })
+            .map_err(|error| error.in_index(index))?;
+
+        self.all_bytes &= is_byte.get();
+        self.values.push(value);
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<IonValue, Self::Error> {
+        if !self.all_bytes || self.values.is_empty() {
+            return Ok(IonValue::List(self.values));
+        }
+
+        let bytes = self
+            .values
+            .into_iter()
+            .map(|value| match value {
+                IonValue::Integer(byte) => byte as u8,
+                _ => unreachable!("Only u8 set the is_byte flag"),
+            })
+            .collect();
+
+        Ok(IonValue::Blob(bytes))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = IonValue;
+    type Error = SerializationError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<IonValue, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = IonValue;
+    type Error = SerializationError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<IonValue, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct StructSerializer {
+    fields: HashMap<String, IonValue>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for StructSerializer {
+    type Ok = IonValue;
+    type Error = SerializationError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        match key.serialize(IonSerializer::default())? {
+            IonValue::String(key) | IonValue::Symbol(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            key => Err(ser::Error::custom(format!(
+                "Ion struct keys must be strings, found {:?}",
+                key
+            ))),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <SerializationError as ser::Error>::custom("value serialized before its key"))?;
+
+        let value = value
+            .serialize(IonSerializer::default())
+            .map_err(|error| error.in_field(&key))?;
+
+        self.fields.insert(key, value);
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::Struct(self.fields))
+    }
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = IonValue;
+    type Error = SerializationError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> {
+        let value = value
+            .serialize(IonSerializer::default())
+            .map_err(|error| error.in_field(key))?;
+
+        self.fields.insert(key.to_string(), value);
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<IonValue, Self::Error> {
+        Ok(IonValue::Struct(self.fields))
+    }
+}
+
+/// Enum variants with content are stored as a struct with a single
+/// field, named like the variant.
+struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = IonValue;
+    type Error = SerializationError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value).map_err(|error| error.in_field(self.variant))
+    }
+
+    fn end(self) -> Result<IonValue, Self::Error> {
+        Ok(wrap_variant(self.variant, ser::SerializeSeq::end(self.inner)?))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<StructSerializer> {
+    type Ok = IonValue;
+    type Error = SerializationError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value).map_err(|error| error.in_field(self.variant))
+    }
+
+    fn end(self) -> Result<IonValue, Self::Error> {
+        Ok(wrap_variant(self.variant, ser::SerializeStruct::end(self.inner)?))
+    }
+}
+
+fn wrap_variant(variant: &'static str, value: IonValue) -> IonValue {
+    IonValue::Struct(HashMap::from([(variant.to_string(), value)]))
+}
@@ -0,0 +1,109 @@
+//! Stores a chrono `DateTime` as an Ion timestamp.
+//!
+//! serde has no timestamp type, so chrono serializes dates as strings.
+//! Use this module with `#[serde(with = "qldb::serialization::timestamp")]`
+//! in order to store them as Ion timestamps instead. Other serializers,
+//! like serde_json, keep receiving the RFC 3339 string.
+//!
+//! ```rust
+//! use chrono::{DateTime, Utc};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Car {
+//!     #[serde(with = "qldb::serialization::timestamp")]
+//!     registered_on: DateTime<Utc>,
+//!     #[serde(with = "qldb::serialization::timestamp::option")]
+//!     sold_on: Option<DateTime<Utc>>,
+//! }
+//! ```
+
+use crate::serialization::TIMESTAMP_TOKEN;
+use chrono::{DateTime, FixedOffset, TimeZone};
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+use std::fmt::{self, Display};
+
+pub fn serialize<S, Tz>(value: &DateTime<Tz>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    Tz: TimeZone,
+    Tz::Offset: Display,
+{
+    serializer.serialize_newtype_struct(TIMESTAMP_TOKEN, &value.to_rfc3339())
+}
+
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: From<DateTime<FixedOffset>>,
+{
+    deserializer.deserialize_str(TimestampVisitor).map(T::from)
+}
+
+struct TimestampVisitor;
+
+impl<'de> Visitor<'de> for TimestampVisitor {
+    type Value = DateTime<FixedOffset>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an RFC 3339 timestamp")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        DateTime::parse_from_rfc3339(value).map_err(E::custom)
+    }
+}
+
+/// Same as the parent module, for `Option<DateTime>`.
+pub mod option {
+    use super::TimestampVisitor;
+    use chrono::{DateTime, FixedOffset, TimeZone};
+    use serde::de::{Deserializer, Visitor};
+    use serde::ser::Serializer;
+    use std::fmt::{self, Display};
+
+    pub fn serialize<S, Tz>(value: &Option<DateTime<Tz>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        Tz: TimeZone,
+        Tz::Offset: Display,
+    {
+        match value {
+            Some(value) => super::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: From<DateTime<FixedOffset>>,
+    {
+        deserializer
+            .deserialize_option(OptionVisitor)
+            .map(|value| value.map(T::from))
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<DateTime<FixedOffset>>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an optional RFC 3339 timestamp")
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_str(TimestampVisitor).map(Some)
+        }
+    }
+}
@@ -1,165 +1,168 @@
-use crate::session_pool::agnostic_async_pool_shared::{create_session, provide_session, qldb_close_session};
+use crate::pool_stats::PoolMetrics;
+use crate::session_pool::agnostic_async_pool_shared::{
+    create_session, drain_sessions, end_session, PoolChanges, PoolMessage, PoolState, SessionSender,
+};
 use crate::session_pool::{Session, SpawnerFnMonoMultithread};
-use async_channel::Receiver;
-use async_channel::Sender;
+use crate::transport::QldbTransport;
+use crate::PoolCloseReport;
+use async_channel::{unbounded, Receiver, Sender};
 use async_io::Timer;
 use log::error;
-use rusoto_qldb_session::QldbSessionClient;
-use std::collections::VecDeque;
-use std::sync::atomic::Ordering::Relaxed;
-use std::sync::{
-    atomic::{AtomicBool, AtomicU16},
-    Arc, Mutex,
-};
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-#[allow(clippy::too_many_arguments)]
-pub fn receiver_task(
-    spawner: SpawnerFnMonoMultithread,
-    max_sessions: u16,
-    ledger_name: &str,
-    sessions: &Arc<Mutex<VecDeque<Session>>>,
-    session_count: &Arc<AtomicU16>,
-    qldb_client: &Arc<QldbSessionClient>,
-    is_closed: &Arc<AtomicBool>,
-    requesting_receiver: Receiver<Sender<Session>>,
-    requesting_sender: Sender<Sender<Session>>,
-) {
-    let is_closed = is_closed.clone();
-    let qldb_client = qldb_client.clone();
-    let sessions = sessions.clone();
-    let session_count = session_count.clone();
-    let ledger_name = ledger_name.to_owned();
-
-    spawner.clone()(Box::pin(async move {
+/// Everything the pool tasks need, shared between them and the tasks
+/// creating and closing sessions.
+#[derive(Clone)]
+pub struct PoolContext {
+    pub spawner: SpawnerFnMonoMultithread,
+    pub ledger_name: Arc<String>,
+    pub state: Arc<Mutex<PoolState>>,
+    pub qldb_client: Arc<dyn QldbTransport>,
+    pub is_closed: Arc<AtomicBool>,
+    pub metrics: Arc<PoolMetrics>,
+}
+
+pub fn receiver_task(context: &PoolContext, requesting_receiver: Receiver<SessionSender>) {
+    let context = context.clone();
+
+    context.spawner.clone()(Box::pin(async move {
         while let Ok(sender) = requesting_receiver.recv().await {
-            if is_closed.load(Relaxed) {
+            if context.is_closed.load(Relaxed) {
                 break;
             }
 
-            loop {
-                let (session, pooled_sessions_count) = match sessions.lock() {
-                    Ok(mut sessions) => (sessions.pop_back(), sessions.len()),
-                    Err(err) => {
-                        // Means that something went really wrong
-                        is_closed.store(true, Relaxed);
-                        error!("QLDB driver internal fatal error. Cannot get lock at sessions when requesting a session: {:?}", err);
-                        break;
-                    }
-                };
+            if !update_state(&context, |state| state.add_waiter(sender)) {
+                break;
+            }
+        }
+    }));
+}
+
+/// Takes back the sessions of the finished transactions. It keeps
+/// running while the pool closes, as the sessions still in use must be
+/// ended when given back.
+pub fn returning_task(context: &PoolContext, returning_receiver: Receiver<PoolMessage>) {
+    let context = context.clone();
 
-                if let Some(session) = session {
-                    if session.is_valid() {
-                        provide_session(&sender, session);
+    context.spawner.clone()(Box::pin(async move {
+        while let Ok(message) = returning_receiver.recv().await {
+            match message {
+                PoolMessage::GiveBack(session) => {
+                    if !update_state(&context, |state| state.give_back(session)) {
                         break;
-                    } else {
-                        close_session(spawner.clone(), &qldb_client, session, &session_count);
-                        // Continue so we try next available session
-                        continue;
-                    }
-                } else {
-                    if pooled_sessions_count < max_sessions.into() {
-                        refill_session(&qldb_client.clone(), &ledger_name, &sessions).await;
-                        continue;
-                    } else {
-                        requeue_session_request(&requesting_sender, sender);
                     }
-                    break;
                 }
+                PoolMessage::Close { timeout, reply } => drain_task(&context, timeout, reply),
             }
         }
     }));
 }
 
-pub fn returning_task(
-    spawner: SpawnerFnMonoMultithread,
-    sessions: &Arc<Mutex<VecDeque<Session>>>,
-    session_count: &Arc<AtomicU16>,
-    qldb_client: &Arc<QldbSessionClient>,
-    is_closed: &Arc<AtomicBool>,
-    returning_receiver: Receiver<Session>,
-) {
-    let is_closed = is_closed.clone();
-    let qldb_client = qldb_client.clone();
-    let sessions = sessions.clone();
-    let session_count = session_count.clone();
-
-    spawner.clone()(Box::pin(async move {
-        while let Ok(session) = returning_receiver.recv().await {
-            if is_closed.load(Relaxed) {
-                break;
-            }
+/// Ends every session of the pool, waiting up to `timeout` for the
+/// ones in use, and replies with the result.
+fn drain_task(context: &PoolContext, timeout: Duration, reply: Sender<PoolCloseReport>) {
+    let context = context.clone();
+    let (drain_sender, drain_receiver) = unbounded();
+
+    let (idle, remaining) = match context.state.lock() {
+        Ok(mut state) => state.close(drain_sender),
+        // The reply channel is dropped, so the pool reports nothing
+        Err(_) => return,
+    };
+
+    context.metrics.set_idle_sessions(0);
+
+    context.spawner.clone()(Box::pin(async move {
+        let report = drain_sessions(
+            context.qldb_client.as_ref(),
+            &context.metrics,
+            idle,
+            remaining,
+            drain_receiver,
+            timeout,
+        )
+        .await;
+
+        let _ = reply.try_send(report);
+    }));
+}
+
+/// Periodically retires expired sessions and creates the ones needed
+/// for `min_idle`, until the pool is closed or dropped.
+pub fn maintenance_task(context: &PoolContext, interval: Duration, requesting_receiver: Receiver<SessionSender>) {
+    let context = context.clone();
 
-            if !session.is_valid() {
-                close_session(spawner.clone(), &qldb_client, session, &session_count);
+    context.spawner.clone()(Box::pin(async move {
+        // The channel closes when every handle of the pool is dropped
+        while !context.is_closed.load(Relaxed) && !requesting_receiver.is_closed() {
+            if !update_state(&context, |state| state.maintain()) {
                 break;
             }
 
-            match sessions.lock() {
-                Ok(mut sessions) => sessions.push_front(session),
-                Err(err) => {
-                    // Means that something went really wrong
-                    is_closed.store(true, Relaxed);
-                    error!(
-                        "QLDB driver internal fatal error. Cannot get lock at sessions when returning a session: {:?}",
-                        err
-                    );
-                    close_session(spawner.clone(), &qldb_client, session, &session_count);
-                    break;
-                }
-            };
+            Timer::after(interval).await;
         }
     }));
 }
 
-fn close_session(
-    spawner: SpawnerFnMonoMultithread,
-    qldb_client: &Arc<QldbSessionClient>,
-    session: Session,
-    session_count: &Arc<AtomicU16>,
-) {
-    let qldb_client = qldb_client.clone();
-    let session_count = session_count.clone();
-
-    spawner(Box::pin(async move {
-        let mut tries: u32 = 0;
-
-        loop {
-            tries = tries.saturating_add(1);
-
-            match qldb_close_session(&qldb_client, &session).await {
-                Ok(_) => break,
-                Err(_) if tries > 10 => break,
-                Err(_) => {
-                    Timer::after(Duration::from_millis(
-                        tries.saturating_mul(tries).saturating_mul(75).into(),
-                    ))
-                    .await;
-                }
-            }
+/// Applies the update to the pool state and starts the work it
+/// requires. It returns false when the state cannot be used anymore.
+fn update_state(context: &PoolContext, update: impl FnOnce(&mut PoolState) -> PoolChanges) -> bool {
+    let changes = match context.state.lock() {
+        Ok(mut state) => {
+            let changes = update(&mut state);
+            context.metrics.set_idle_sessions(state.idle_sessions());
+            changes
+        }
+        Err(err) => {
+            // Means that something went really wrong
+            context.is_closed.store(true, Relaxed);
+            error!(
+                "QLDB driver internal fatal error. Cannot get lock at the session pool state: {:?}",
+                err
+            );
+            return false;
         }
+    };
 
-        session_count.store(session_count.load(Relaxed).saturating_sub(1), Relaxed);
-    }));
-}
+    for _ in 0..changes.forgotten {
+        context.metrics.session_closed();
+    }
+
+    for session in changes.to_close {
+        close_session(context, session);
+    }
 
-fn requeue_session_request(session_requests: &Sender<Sender<Session>>, sender: Sender<Session>) {
-    if let Err(err) = session_requests.try_send(sender) {
-        error!(
-            "QLDB driver internal error. Cannot enqueue session due pool bug: {:?}",
-            err
-        );
+    for _ in 0..changes.to_create {
+        refill_session(context);
     }
+
+    true
+}
+
+fn close_session(context: &PoolContext, session: Session) {
+    let context = context.clone();
+
+    context.spawner.clone()(Box::pin(async move {
+        end_session(context.qldb_client.as_ref(), &session).await;
+        context.metrics.session_closed();
+    }));
 }
 
-async fn refill_session(
-    qldb_client: &Arc<QldbSessionClient>,
-    ledger_name: &str,
-    sessions: &Arc<Mutex<VecDeque<Session>>>,
-) {
-    if let Ok(session) = create_session(&qldb_client.clone(), ledger_name).await {
-        if let Ok(mut sessions) = sessions.lock() {
-            sessions.push_back(session);
+fn refill_session(context: &PoolContext) {
+    let context = context.clone();
+
+    context.spawner.clone()(Box::pin(async move {
+        match create_session(context.qldb_client.as_ref(), &context.ledger_name).await {
+            Ok(session) => {
+                context.metrics.session_created();
+                update_state(&context, |state| state.session_created(session));
+            }
+            Err(error) => {
+                context.metrics.session_creation_failed();
+                update_state(&context, |state| state.session_creation_failed(error.into()));
+            }
         }
-    }
+    }));
 }
@@ -1,24 +1,337 @@
-use crate::session_pool::{GetSessionError, Session};
-use async_channel::Sender;
-use async_compat::CompatExt;
+use crate::pool_stats::{PoolCloseReport, PoolMetrics};
+use crate::session_pool::{GetSessionError, PoolConfig, Session};
+use crate::transport::{EndSessionRequest, QldbTransport, SendCommandRequest, StartSessionRequest, TransportError};
+use crate::{AcquireOptions, QldbError, QldbResult};
+use async_channel::{bounded, Receiver, RecvError, Sender, TryRecvError};
 use async_io::Timer;
-use log::error;
-use rusoto_core::RusotoError;
-use rusoto_qldb_session::{EndSessionRequest, QldbSession, QldbSessionClient, SendCommandRequest, StartSessionRequest};
-use std::time::Duration;
-
-pub(crate) fn provide_session(sender: &Sender<Session>, session: Session) {
-    // This channel should never be full or closed
-    if let Err(err) = sender.try_send(session) {
-        error!(
-            "QLDB driver internal error. Cannot return session due to channel issue: {:?}",
-            err
-        );
+use eyre::WrapErr;
+use futures::future::{self, Either};
+use futures::stream::{self, StreamExt};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::time::{Duration, Instant};
+
+/// Sender used by the pool for answering a request for a session.
+pub(crate) type SessionSender = Sender<QldbResult<Session>>;
+
+/// Messages handled by the returning task of the pools.
+pub(crate) enum PoolMessage {
+    GiveBack(Session),
+    Close {
+        timeout: Duration,
+        reply: Sender<PoolCloseReport>,
+    },
+}
+
+/// While the pool closes, the sessions given back or created are sent
+/// here to be ended. None means that a session failed to be created.
+pub(crate) type DrainSender = Sender<Option<Session>>;
+
+/// Bookkeeping of a session pool. It counts every live session, idle,
+/// in use or being created, against `max_sessions`. Requests that
+/// cannot be served are parked until a session is given back or
+/// created.
+///
+/// It doesn't do any IO. Every method returns the sessions that must
+/// be closed and the amount of sessions to create, and the pool
+/// implementations do it in their executor.
+#[derive(Debug)]
+pub(crate) struct PoolState {
+    config: PoolConfig,
+    live_sessions: u16,
+    creating_sessions: usize,
+    // After a failure, sessions for min_idle wait for the next maintenance
+    prewarm_paused: bool,
+    // Most recently used first
+    idle: VecDeque<IdleSession>,
+    waiters: VecDeque<SessionSender>,
+    closing: Option<DrainSender>,
+}
+
+#[derive(Debug)]
+struct IdleSession {
+    session: Session,
+    idle_since: Instant,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct PoolChanges {
+    pub(crate) to_close: Vec<Session>,
+    pub(crate) to_create: usize,
+    // Poisoned sessions, dropped without sending EndSession
+    pub(crate) forgotten: usize,
+}
+
+impl PoolState {
+    pub(crate) fn new(config: PoolConfig) -> PoolState {
+        PoolState {
+            idle: VecDeque::with_capacity(config.max_sessions.into()),
+            config,
+            live_sessions: 0,
+            creating_sessions: 0,
+            prewarm_paused: false,
+            waiters: VecDeque::new(),
+            closing: None,
+        }
+    }
+
+    pub(crate) fn idle_sessions(&self) -> usize {
+        self.idle.len()
+    }
+
+    pub(crate) fn add_waiter(&mut self, waiter: SessionSender) -> PoolChanges {
+        // Dropping the waiter makes the request fail as the pool is closed
+        if self.closing.is_none() {
+            self.waiters.push_back(waiter);
+        }
+
+        self.dispatch(PoolChanges::default())
+    }
+
+    /// Stops giving and creating sessions. Waiters get an error, and
+    /// the sessions given back or created from now on are sent to
+    /// `drain`. It returns the idle sessions and the amount of sessions
+    /// that will be sent to `drain`.
+    pub(crate) fn close(&mut self, drain: DrainSender) -> (Vec<Session>, usize) {
+        self.waiters.clear();
+        self.closing = Some(drain);
+
+        let idle: Vec<Session> = self.idle.drain(..).map(|idle| idle.session).collect();
+        self.live_sessions = self.live_sessions.saturating_sub(idle.len() as u16);
+
+        (idle, self.live_sessions.into())
+    }
+
+    pub(crate) fn give_back(&mut self, session: Session) -> PoolChanges {
+        let mut changes = PoolChanges::default();
+
+        if let Some(drain) = &self.closing {
+            self.live_sessions = self.live_sessions.saturating_sub(1);
+            let _ = drain.try_send(Some(session));
+        } else if session.is_poisoned() {
+            self.live_sessions = self.live_sessions.saturating_sub(1);
+            changes.forgotten += 1;
+        } else if self.is_expired(&session) {
+            self.discard(session, &mut changes);
+        } else {
+            self.push_idle(session);
+        }
+
+        self.dispatch(changes)
+    }
+
+    pub(crate) fn session_created(&mut self, session: Session) -> PoolChanges {
+        self.creating_sessions = self.creating_sessions.saturating_sub(1);
+
+        if let Some(drain) = &self.closing {
+            self.live_sessions = self.live_sessions.saturating_sub(1);
+            let _ = drain.try_send(Some(session));
+        } else {
+            self.push_idle(session);
+        }
+
+        self.dispatch(PoolChanges::default())
+    }
+
+    /// Retires the idle sessions that are too old or that have been
+    /// idle for longer than `idle_timeout`, keeping at least
+    /// `min_idle`, and creates the sessions missing for `min_idle`.
+    pub(crate) fn maintain(&mut self) -> PoolChanges {
+        let mut changes = PoolChanges::default();
+
+        if self.closing.is_some() {
+            return changes;
+        }
+
+        let mut kept = VecDeque::with_capacity(self.idle.len());
+
+        // From the least recently used, so those are evicted first
+        while let Some(idle) = self.idle.pop_back() {
+            let is_idle_too_long = self
+                .config
+                .idle_timeout
+                .is_some_and(|idle_timeout| idle.idle_since.elapsed() >= idle_timeout);
+
+            let remaining = self.idle.len() + kept.len() + 1;
+
+            if self.is_expired(&idle.session) || (is_idle_too_long && remaining > self.config.min_idle.into()) {
+                self.discard(idle.session, &mut changes);
+            } else {
+                kept.push_front(idle);
+            }
+        }
+
+        self.idle = kept;
+        self.prewarm_paused = false;
+
+        self.dispatch(changes)
+    }
+
+    /// The first waiter gets the error, as otherwise the pool would keep
+    /// trying to create sessions when QLDB cannot give them.
+    pub(crate) fn session_creation_failed(&mut self, mut error: QldbError) -> PoolChanges {
+        self.creating_sessions = self.creating_sessions.saturating_sub(1);
+        self.live_sessions = self.live_sessions.saturating_sub(1);
+        self.prewarm_paused = true;
+
+        if let Some(drain) = &self.closing {
+            let _ = drain.try_send(None);
+        }
+
+        while let Some(waiter) = self.waiters.pop_front() {
+            match waiter.try_send(Err(error)).map_err(|err| err.into_inner()) {
+                Err(Err(returned_error)) => error = returned_error,
+                _ => break,
+            }
+        }
+
+        self.dispatch(PoolChanges::default())
+    }
+
+    /// Gives idle sessions to the waiters and decides how many
+    /// sessions are needed for the rest.
+    fn dispatch(&mut self, mut changes: PoolChanges) -> PoolChanges {
+        if self.closing.is_some() {
+            return changes;
+        }
+
+        // Requests that timed out or were dropped
+        self.waiters.retain(|waiter| !waiter.is_closed());
+
+        while !self.waiters.is_empty() {
+            let idle = match self.idle.pop_front() {
+                Some(idle) => idle,
+                None => break,
+            };
+
+            if self.is_expired(&idle.session) {
+                self.discard(idle.session, &mut changes);
+                continue;
+            }
+
+            if let Some(waiter) = self.waiters.pop_front() {
+                // It only fails if the waiter stopped waiting just now
+                if let Err(err) = waiter.try_send(Ok(idle.session)) {
+                    if let Ok(session) = err.into_inner() {
+                        self.push_idle(session);
+                    }
+                }
+            }
+        }
+
+        // A session for every waiter plus the ones missing for min_idle
+        let missing_idle = if self.prewarm_paused {
+            0
+        } else {
+            usize::from(self.config.min_idle).saturating_sub(self.idle.len())
+        };
+
+        let needed_sessions = self.waiters.len() + missing_idle;
+
+        while self.creating_sessions < needed_sessions && self.live_sessions < self.config.max_sessions {
+            self.creating_sessions += 1;
+            self.live_sessions += 1;
+            changes.to_create += 1;
+        }
+
+        changes
+    }
+
+    fn push_idle(&mut self, session: Session) {
+        self.idle.push_front(IdleSession {
+            session,
+            idle_since: Instant::now(),
+        });
+    }
+
+    fn is_expired(&self, session: &Session) -> bool {
+        session.age() >= self.config.max_session_age
+    }
+
+    fn discard(&mut self, session: Session, changes: &mut PoolChanges) {
+        self.live_sessions = self.live_sessions.saturating_sub(1);
+        changes.to_close.push(session);
+    }
+}
+
+/// Enqueues a request for a session in the pool and waits for it,
+/// applying the limits in `options`.
+pub(crate) async fn request_session(
+    session_requests: &Sender<SessionSender>,
+    session_returns: &Sender<PoolMessage>,
+    metrics: &PoolMetrics,
+    options: &AcquireOptions,
+) -> QldbResult<Session> {
+    let requested_at = Instant::now();
+
+    let _pending_request = metrics
+        .pending_request(options.get_max_waiters())
+        .ok_or_else(|| QldbError::PoolExhausted(options.get_max_waiters().unwrap_or_default()))?;
+
+    let (sender, receiver) = bounded::<QldbResult<Session>>(1);
+
+    let receiver = SessionReceiver {
+        receiver,
+        session_returns,
+    };
+
+    session_requests
+        .try_send(sender)
+        .wrap_err("Session pool closed")
+        .map_err(QldbError::SessionPoolClosed)?;
+
+    let session = match options.get_timeout() {
+        Some(timeout) => match future::select(Box::pin(receiver.receiver.recv()), Timer::after(timeout)).await {
+            Either::Left((session, _)) => session,
+            // The pool may have sent the session right when the timer fired
+            Either::Right(_) => match receiver.receiver.try_recv() {
+                Ok(session) => Ok(session),
+                Err(TryRecvError::Empty) => return Err(QldbError::SessionAcquireTimeout(timeout)),
+                Err(TryRecvError::Closed) => Err(RecvError),
+            },
+        },
+        None => receiver.receiver.recv().await,
+    }
+    .wrap_err("Session pool closed")
+    .map_err(QldbError::SessionPoolClosed)??;
+
+    metrics.session_acquired(requested_at.elapsed());
+
+    Ok(session)
+}
+
+/// Asks the pool tasks to end every session and waits for the result.
+pub(crate) async fn request_close(session_returns: &Sender<PoolMessage>, timeout: Duration) -> PoolCloseReport {
+    let (reply, receiver) = bounded::<PoolCloseReport>(1);
+
+    if session_returns.try_send(PoolMessage::Close { timeout, reply }).is_err() {
+        return PoolCloseReport::default();
+    }
+
+    receiver.recv().await.unwrap_or_default()
+}
+
+/// Receives the session requested to the pool. When the request is
+/// cancelled right after the pool sent the session, the session goes
+/// back to the pool instead of being lost.
+struct SessionReceiver<'a> {
+    receiver: Receiver<QldbResult<Session>>,
+    session_returns: &'a Sender<PoolMessage>,
+}
+
+impl Drop for SessionReceiver<'_> {
+    fn drop(&mut self) {
+        // Afterwards the pool cannot send anything else
+        self.receiver.close();
+
+        if let Ok(Ok(session)) = self.receiver.try_recv() {
+            let _ = self.session_returns.try_send(PoolMessage::GiveBack(session));
+        }
     }
 }
 
 pub(crate) async fn create_session(
-    qldb_client: &QldbSessionClient,
+    qldb_client: &dyn QldbTransport,
     ledger_name: &str,
 ) -> Result<Session, GetSessionError> {
     let mut tries: u32 = 0;
@@ -42,7 +355,84 @@ pub(crate) async fn create_session(
     Ok(Session::new(session))
 }
 
-pub(crate) async fn qldb_close_session(qldb_client: &QldbSessionClient, session: &Session) -> Result<(), eyre::Report> {
+/// Ends the session in QLDB, retrying on errors. Returns whether it
+/// was ended.
+pub(crate) async fn end_session(qldb_client: &dyn QldbTransport, session: &Session) -> bool {
+    let mut tries: u32 = 0;
+
+    loop {
+        tries = tries.saturating_add(1);
+
+        match qldb_close_session(qldb_client, session).await {
+            Ok(_) => break true,
+            Err(_) if tries > 10 => break false,
+            Err(_) => {
+                Timer::after(Duration::from_millis(
+                    tries.saturating_mul(tries).saturating_mul(75).into(),
+                ))
+                .await;
+            }
+        }
+    }
+}
+
+/// Ends the sessions of a closing pool, all at the same time: the idle
+/// ones right away and the rest as they are given back. Everything has
+/// to be done by `timeout`, the sessions still being ended then count
+/// as failed.
+pub(crate) async fn drain_sessions(
+    qldb_client: &dyn QldbTransport,
+    metrics: &PoolMetrics,
+    idle: Vec<Session>,
+    remaining: usize,
+    drain: Receiver<Option<Session>>,
+    timeout: Duration,
+) -> PoolCloseReport {
+    let deadline = Instant::now() + timeout;
+    let received = AtomicUsize::new(0);
+    let started = AtomicUsize::new(0);
+    let closed = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+
+    // None is a session that failed to be created, there is nothing to end
+    let given_back = drain
+        .take(remaining)
+        .inspect(|_| {
+            received.fetch_add(1, Relaxed);
+        })
+        .filter_map(future::ready);
+
+    let (started_ref, closed_ref, failed_ref) = (&started, &closed, &failed);
+
+    let draining = stream::iter(idle)
+        .chain(given_back)
+        .for_each_concurrent(None, |session| async move {
+            started_ref.fetch_add(1, Relaxed);
+            metrics.session_closed();
+
+            // Invalid sessions are already gone in QLDB
+            if !session.is_poisoned() && end_session(qldb_client, &session).await {
+                closed_ref.fetch_add(1, Relaxed);
+            } else {
+                failed_ref.fetch_add(1, Relaxed);
+            }
+        });
+
+    future::select(Box::pin(draining), Timer::at(deadline)).await;
+
+    let closed_sessions = closed.into_inner();
+    let failed_sessions = failed.into_inner();
+    // The sessions still being ended at the deadline
+    let pending_sessions = started.into_inner() - closed_sessions - failed_sessions;
+
+    PoolCloseReport::new(
+        closed_sessions,
+        failed_sessions + pending_sessions,
+        remaining - received.into_inner(),
+    )
+}
+
+pub(crate) async fn qldb_close_session(qldb_client: &dyn QldbTransport, session: &Session) -> Result<(), eyre::Report> {
     qldb_client
         .send_command(SendCommandRequest {
             session_token: Some(session.get_session_id().to_string()),
@@ -55,7 +445,7 @@ pub(crate) async fn qldb_close_session(qldb_client: &QldbSessionClient, session:
 }
 
 pub(crate) async fn qldb_request_session(
-    qldb_client: &QldbSessionClient,
+    qldb_client: &dyn QldbTransport,
     ledger_name: &str,
 ) -> Result<String, GetSessionError> {
     match qldb_client
@@ -65,7 +455,6 @@ pub(crate) async fn qldb_request_session(
             }),
             ..Default::default()
         })
-        .compat()
         .await
     {
         Ok(response) => match response.start_session {
@@ -80,7 +469,7 @@ pub(crate) async fn qldb_request_session(
             ))),
         },
         Err(err) => match err {
-            RusotoError::Credentials(_) => Err(GetSessionError::Unrecoverable(eyre::eyre!(err))),
+            TransportError::Credentials(_) => Err(GetSessionError::Unrecoverable(eyre::eyre!(err))),
             _ => Err(GetSessionError::Recoverable(eyre::eyre!(err))),
         },
     }
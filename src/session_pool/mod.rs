@@ -1,37 +1,51 @@
+#[cfg(feature = "internal_pool_with_thread")]
 mod agnostic_async_pool_monothread;
+#[cfg(feature = "internal_pool_with_spawner")]
 mod agnostic_async_pool_multithread;
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
 mod agnostic_async_pool_shared;
 #[cfg(feature = "internal_pool_with_spawner")]
 mod session_pool_spawner;
 #[cfg(feature = "internal_pool_with_thread")]
 mod session_pool_thread;
 
-use log::error;
+use crate::pool_stats::{PoolCloseReport, PoolStats};
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+use crate::QldbError;
+use crate::{AcquireOptions, QldbResult};
 #[cfg(feature = "internal_pool_with_spawner")]
 pub use session_pool_spawner::SpawnerSessionPool;
 #[cfg(feature = "internal_pool_with_thread")]
 pub use session_pool_thread::ThreadedSessionPool;
+use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
 use std::sync::Arc;
-use std::{future::Future, time::Instant};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 struct InnerSession {
     created_on_instant: Instant,
     session_id: String,
+    poisoned: AtomicBool,
 }
 
+/// A QLDB session, given by the session pool to a transaction.
+///
+/// Clones share the same session.
 #[derive(Debug, Clone)]
 pub struct Session {
     inner: Arc<InnerSession>,
 }
 
 impl Session {
+    /// Wraps the session token returned by a `StartSession` command.
     pub fn new(session_id: String) -> Session {
         Session {
             inner: Arc::new(InnerSession {
                 created_on_instant: Instant::now(),
                 session_id,
+                poisoned: AtomicBool::new(false),
             }),
         }
     }
@@ -40,28 +54,127 @@ impl Session {
         &self.inner.session_id
     }
 
-    pub fn is_valid(&self) -> bool {
-        self.inner.created_on_instant.elapsed().as_secs() < 10 * 60
+    /// Time since the session was created.
+    pub fn age(&self) -> Duration {
+        self.inner.created_on_instant.elapsed()
+    }
+
+    /// Marks the session as not usable anymore, so the pool discards
+    /// it when it is given back.
+    pub fn poison(&self) {
+        self.inner.poisoned.store(true, Relaxed);
+    }
+
+    /// Whether QLDB reported the session as invalid. Pools must not
+    /// give it again and there is no need to end it.
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.poisoned.load(Relaxed)
+    }
+}
+
+/// Settings of the session pools, given by the client builder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PoolConfig {
+    pub(crate) max_sessions: u16,
+    pub(crate) max_session_age: Duration,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) min_idle: u16,
+}
+
+impl PoolConfig {
+    /// How often the pool retires expired sessions and creates the
+    /// ones needed for `min_idle`.
+    #[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+    pub(crate) fn maintenance_interval(&self) -> Duration {
+        let shortest = match self.idle_timeout {
+            Some(idle_timeout) => idle_timeout.min(self.max_session_age),
+            None => self.max_session_age,
+        };
+
+        (shortest / 4).clamp(Duration::from_millis(10), Duration::from_secs(1))
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_sessions: 200,
+            max_session_age: Duration::from_secs(10 * 60),
+            idle_timeout: None,
+            min_idle: 0,
+        }
     }
 }
 
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum GetSessionError {
-    #[error("The QLDB command returned an error")]
+    #[error("QLDB cannot start a session: {0}")]
     Unrecoverable(eyre::Report),
-    #[error("The QLDB command returned an error")]
+    #[error("QLDB failed starting a session, it can be tried again: {0}")]
     Recoverable(eyre::Report),
 }
 
+#[cfg(any(feature = "internal_pool_with_spawner", feature = "internal_pool_with_thread"))]
+impl From<GetSessionError> for QldbError {
+    fn from(error: GetSessionError) -> QldbError {
+        match error {
+            GetSessionError::Unrecoverable(report) | GetSessionError::Recoverable(report) => {
+                QldbError::SessionStartError(report)
+            }
+        }
+    }
+}
+
+/// Source of the sessions used by the transactions of a `QldbClient`.
+///
+/// The driver comes with two implementations, used by
+/// `QldbClientBuilder::build` and `QldbClientBuilder::build_with_spawner`.
+/// Implement it for managing the sessions yourself, for example for
+/// sharing a budget of sessions between the clients of several ledgers,
+/// and give it to `QldbClient::with_pool` or
+/// `QldbClientBuilder::build_with_pool`.
+///
+/// Sessions are opened by sending a `StartSession` command through the
+/// [QldbTransport](crate::QldbTransport) and wrapping the returned token
+/// with `Session::new`. Sessions can only run one transaction at a time,
+/// so a session must not be given by `get` again until it is given back.
 #[async_trait::async_trait]
 pub trait SessionPool: Send + Sync {
-    async fn close(&self);
+    /// Stops giving sessions and ends all of them with an `EndSession`
+    /// command, waiting up to `timeout` for the ones in use by
+    /// transactions. Called by `QldbClient::close`.
+    async fn close(&self, timeout: Duration) -> PoolCloseReport;
 
-    async fn get(&self) -> eyre::Result<Session>;
+    /// Gives a session for a new transaction. It should wait at most
+    /// `options.get_timeout()`, failing with
+    /// `QldbError::SessionAcquireTimeout`, and fail with
+    /// `QldbError::SessionPoolClosed` once the pool is closed.
+    async fn get(&self, options: &AcquireOptions) -> QldbResult<Session>;
 
+    /// Called when the transaction using the session finishes, even
+    /// when it failed. It must not block. Poisoned sessions must be
+    /// discarded instead of given again.
     fn give_back(&self, session: Session);
+
+    /// Runs a background task of the driver, like the prefetching of
+    /// cursor pages, in the executor used by the pool.
+    fn spawn(&self, task: BackgroundTask);
+
+    fn is_closed(&self) -> bool;
+
+    /// State of the pool for `QldbClient::pool_stats`. By default
+    /// everything is zero.
+    fn stats(&self) -> PoolStats {
+        PoolStats::default()
+    }
 }
 
+/// Task given to `SessionPool::spawn`.
+pub type BackgroundTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+#[cfg(feature = "internal_pool_with_thread")]
 pub type SpawnerFnMonothread = Arc<dyn Fn(Pin<Box<dyn Future<Output = ()>>>)>;
 
+#[cfg(feature = "internal_pool_with_spawner")]
 pub type SpawnerFnMonoMultithread = Arc<dyn Fn(Pin<Box<dyn Future<Output = ()> + Send>>) + Send + Sync>;
@@ -1,99 +1,143 @@
+use crate::pool_stats::{PoolCloseReport, PoolMetrics, PoolStats};
+use crate::session_pool::agnostic_async_pool_shared::{
+    request_close, request_session, PoolMessage, PoolState, SessionSender,
+};
 use crate::session_pool::{
-    agnostic_async_pool_multithread::{receiver_task, returning_task},
-    Session, SessionPool, SpawnerFnMonoMultithread,
+    agnostic_async_pool_multithread::{maintenance_task, receiver_task, returning_task, PoolContext},
+    BackgroundTask, PoolConfig, Session, SessionPool, SpawnerFnMonoMultithread,
 };
-use async_channel::{bounded, unbounded, Sender};
-use eyre::WrapErr;
-use rusoto_qldb_session::QldbSessionClient;
-use std::collections::VecDeque;
-use std::sync::atomic::{AtomicBool, AtomicU16, Ordering::Relaxed};
+use crate::transport::QldbTransport;
+use crate::{AcquireOptions, QldbError, QldbResult};
+use async_channel::{unbounded, Sender};
+use std::fmt::{self, Debug};
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct SpawnerSessionPool {
-    sender_request: Sender<Sender<Session>>,
-    sender_return: Sender<Session>,
+    sender_request: Sender<SessionSender>,
+    sender_return: Sender<PoolMessage>,
     is_closed: Arc<AtomicBool>,
+    metrics: Arc<PoolMetrics>,
+    spawner: SpawnerFnMonoMultithread,
 }
 
 impl SpawnerSessionPool {
     pub fn new(
-        qldb_client: Arc<QldbSessionClient>,
+        qldb_client: Arc<dyn QldbTransport>,
         ledger_name: &str,
-        max_sessions: u16,
+        config: PoolConfig,
         spawner: SpawnerFnMonoMultithread,
     ) -> SpawnerSessionPool {
-        let (requesting_sender, requesting_receiver) = unbounded::<Sender<Session>>();
-        let (returning_sender, returning_receiver) = unbounded::<Session>();
+        let (requesting_sender, requesting_receiver) = unbounded::<SessionSender>();
+        let (returning_sender, returning_receiver) = unbounded::<PoolMessage>();
         let ledger_name = ledger_name.to_owned();
+        let maintenance_interval = config.maintenance_interval();
 
         let is_closed = Arc::new(AtomicBool::from(false));
 
         let is_closed_return = is_closed.clone();
-        let requesting_sender_return = requesting_sender.clone();
-
-        let sessions = Arc::new(Mutex::new(VecDeque::<Session>::with_capacity(max_sessions.into())));
-        let session_count = Arc::new(AtomicU16::new(0));
-
-        receiver_task(
-            spawner.clone(),
-            max_sessions,
-            &ledger_name,
-            &sessions,
-            &session_count,
-            &qldb_client,
-            &is_closed,
-            requesting_receiver,
-            requesting_sender,
-        );
-
-        returning_task(
-            spawner,
-            &sessions,
-            &session_count,
-            &qldb_client,
-            &is_closed,
-            returning_receiver,
-        );
+        let metrics = Arc::new(PoolMetrics::default());
+        let metrics_return = metrics.clone();
+
+        let context = PoolContext {
+            spawner: spawner.clone(),
+            ledger_name: Arc::new(ledger_name),
+            state: Arc::new(Mutex::new(PoolState::new(config))),
+            qldb_client,
+            is_closed,
+            metrics,
+        };
+
+        maintenance_task(&context, maintenance_interval, requesting_receiver.clone());
+
+        receiver_task(&context, requesting_receiver);
+
+        returning_task(&context, returning_receiver);
 
         SpawnerSessionPool {
-            sender_request: requesting_sender_return,
+            sender_request: requesting_sender,
             sender_return: returning_sender,
             is_closed: is_closed_return,
+            metrics: metrics_return,
+            spawner,
         }
     }
 
-    pub async fn close(&self) {
-        self.is_closed.store(true, Relaxed);
-    }
-
-    pub async fn get(&self) -> eyre::Result<Session> {
-        let (sender, receiver) = bounded::<Session>(1);
+    /// Stops giving sessions and ends all of them, waiting up to
+    /// `timeout` for the ones in use. Closing it again reports nothing.
+    pub async fn close(&self, timeout: Duration) -> PoolCloseReport {
+        if self.is_closed.swap(true, Relaxed) {
+            return PoolCloseReport::default();
+        }
 
-        self.sender_request.try_send(sender).wrap_err("Session pool closed")?;
+        request_close(&self.sender_return, timeout).await
+    }
 
-        let session = receiver.recv().await.wrap_err("Session pool closed")?;
+    pub async fn get(&self, options: &AcquireOptions) -> QldbResult<Session> {
+        if self.is_closed() {
+            return Err(QldbError::SessionPoolClosed(eyre::eyre!("Session pool closed")));
+        }
 
-        Ok(session)
+        request_session(&self.sender_request, &self.sender_return, &self.metrics, options).await
     }
 
     pub fn give_back(&self, session: Session) {
+        self.metrics.session_returned();
+
         // TODO: We maybe shouldn't be ignoring this error
-        let _ = self.sender_return.try_send(session);
+        let _ = self.sender_return.try_send(PoolMessage::GiveBack(session));
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed.load(Relaxed)
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.metrics.stats()
+    }
+
+    pub fn spawn(&self, task: BackgroundTask) {
+        (self.spawner)(task)
+    }
+}
+
+impl Debug for SpawnerSessionPool {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("SpawnerSessionPool")
+            .field("sender_request", &self.sender_request)
+            .field("sender_return", &self.sender_return)
+            .field("is_closed", &self.is_closed)
+            .field("metrics", &self.metrics)
+            .finish()
     }
 }
 
 #[async_trait::async_trait]
 impl SessionPool for SpawnerSessionPool {
-    async fn close(&self) {
-        self.close().await
+    async fn close(&self, timeout: Duration) -> PoolCloseReport {
+        self.close(timeout).await
     }
 
-    async fn get(&self) -> eyre::Result<Session> {
-        self.get().await
+    async fn get(&self, options: &AcquireOptions) -> QldbResult<Session> {
+        self.get(options).await
     }
 
     fn give_back(&self, session: Session) {
         self.give_back(session)
     }
+
+    fn spawn(&self, task: BackgroundTask) {
+        self.spawn(task)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.is_closed()
+    }
+
+    fn stats(&self) -> PoolStats {
+        self.stats()
+    }
 }
@@ -1,104 +1,170 @@
+use crate::pool_stats::{PoolCloseReport, PoolMetrics, PoolStats};
+use crate::session_pool::agnostic_async_pool_shared::{
+    request_close, request_session, PoolMessage, PoolState, SessionSender,
+};
 use crate::session_pool::{
-    agnostic_async_pool_monothread::{receiver_task, returning_task},
-    Session, SessionPool,
+    agnostic_async_pool_monothread::{maintenance_task, receiver_task, returning_task, PoolContext},
+    BackgroundTask, PoolConfig, Session, SessionPool,
 };
-use async_channel::{bounded, unbounded, Sender};
+use crate::transport::QldbTransport;
+use crate::{AcquireOptions, QldbError, QldbResult};
+use async_channel::{bounded, unbounded, Receiver, Sender};
 use async_executor::LocalExecutor;
-use eyre::WrapErr;
-use rusoto_qldb_session::QldbSessionClient;
 use std::cell::RefCell;
-use std::collections::VecDeque;
 use std::rc::Rc;
-use std::sync::atomic::{AtomicBool, AtomicU16, Ordering::Relaxed};
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct ThreadedSessionPool {
-    sender_request: Sender<Sender<Session>>,
-    sender_return: Sender<Session>,
+    sender_request: Sender<SessionSender>,
+    sender_return: Sender<PoolMessage>,
+    sender_task: Sender<BackgroundTask>,
+    // Closing it stops the pool thread
+    sender_shutdown: Sender<()>,
+    // Fails once the pool thread has ended
+    thread_finished: Receiver<()>,
     is_closed: Arc<AtomicBool>,
+    metrics: Arc<PoolMetrics>,
 }
 
 impl ThreadedSessionPool {
-    pub fn new(qldb_client: Arc<QldbSessionClient>, ledger_name: &str, max_sessions: u16) -> ThreadedSessionPool {
-        let (requesting_sender, requesting_receiver) = unbounded::<Sender<Session>>();
-        let (returning_sender, returning_receiver) = unbounded::<Session>();
+    pub fn new(qldb_client: Arc<dyn QldbTransport>, ledger_name: &str, config: PoolConfig) -> ThreadedSessionPool {
+        let (requesting_sender, requesting_receiver) = unbounded::<SessionSender>();
+        let (returning_sender, returning_receiver) = unbounded::<PoolMessage>();
+        let (task_sender, task_receiver) = unbounded::<BackgroundTask>();
+        let (shutdown_sender, shutdown_receiver) = bounded::<()>(1);
+        let (finished_sender, finished_receiver) = bounded::<()>(1);
         let ledger_name = ledger_name.to_owned();
+        let maintenance_interval = config.maintenance_interval();
 
         let is_closed = Arc::new(AtomicBool::from(false));
 
         let is_closed_return = is_closed.clone();
-        let requesting_sender_return = requesting_sender.clone();
+        let metrics = Arc::new(PoolMetrics::default());
+        let metrics_return = metrics.clone();
 
         std::thread::spawn(move || {
+            // Dropped last, once the executor and its tasks are gone
+            let _finished = finished_sender;
+
+            // SpawnerFnMonothread is public, so it stays an Arc even if it
+            // never leaves this thread
+            #[allow(clippy::arc_with_non_send_sync)]
             let executor = Arc::new(LocalExecutor::new());
             let executor2 = executor.clone();
             let executor3 = executor.clone();
-            let sessions = Rc::new(RefCell::new(VecDeque::<Session>::with_capacity(max_sessions.into())));
-            let session_count = Rc::new(AtomicU16::new(0));
-
-            receiver_task(
-                Arc::new(move |fut| executor.spawn(Box::pin(fut)).detach()),
-                max_sessions,
-                &ledger_name,
-                &sessions,
-                &session_count,
-                &qldb_client,
-                &is_closed,
-                requesting_receiver,
-                requesting_sender,
-            );
-
-            returning_task(
-                Arc::new(move |fut| executor2.spawn(Box::pin(fut)).detach()),
-                &sessions,
-                &session_count,
-                &qldb_client,
-                &is_closed,
-                returning_receiver,
-            );
-
-            futures::executor::block_on(executor3.run(futures::future::pending::<()>()));
+            let executor4 = executor.clone();
+
+            #[allow(clippy::arc_with_non_send_sync)]
+            let context = PoolContext {
+                spawner: Arc::new(move |fut| executor2.spawn(Box::pin(fut)).detach()),
+                ledger_name: Rc::new(ledger_name),
+                state: Rc::new(RefCell::new(PoolState::new(config))),
+                qldb_client,
+                is_closed,
+                metrics,
+            };
+
+            maintenance_task(&context, maintenance_interval, requesting_receiver.clone());
+
+            receiver_task(&context, requesting_receiver);
+
+            returning_task(&context, returning_receiver);
+
+            executor3
+                .spawn(async move {
+                    while let Ok(task) = task_receiver.recv().await {
+                        executor4.spawn(task).detach();
+                    }
+                })
+                .detach();
+
+            // Runs until the pool is closed or every handle is dropped
+            let _ = futures::executor::block_on(executor.run(shutdown_receiver.recv()));
         });
 
         ThreadedSessionPool {
-            sender_request: requesting_sender_return,
+            sender_request: requesting_sender,
             sender_return: returning_sender,
+            sender_task: task_sender,
+            sender_shutdown: shutdown_sender,
+            thread_finished: finished_receiver,
             is_closed: is_closed_return,
+            metrics: metrics_return,
         }
     }
 
-    pub async fn close(&self) {
-        self.is_closed.store(true, Relaxed);
-    }
+    /// Stops giving sessions and ends all of them, waiting up to
+    /// `timeout` for the ones in use. Closing it again reports nothing.
+    pub async fn close(&self, timeout: Duration) -> PoolCloseReport {
+        if self.is_closed.swap(true, Relaxed) {
+            return PoolCloseReport::default();
+        }
 
-    pub async fn get(&self) -> eyre::Result<Session> {
-        let (sender, receiver) = bounded::<Session>(1);
+        let report = request_close(&self.sender_return, timeout).await;
 
-        self.sender_request.try_send(sender).wrap_err("Session pool closed")?;
+        self.sender_shutdown.close();
 
-        let session = receiver.recv().await.wrap_err("Session pool closed")?;
+        // Nothing is sent through it, it fails when the thread ends
+        let _ = self.thread_finished.recv().await;
 
-        Ok(session)
+        report
+    }
+
+    pub async fn get(&self, options: &AcquireOptions) -> QldbResult<Session> {
+        if self.is_closed() {
+            return Err(QldbError::SessionPoolClosed(eyre::eyre!("Session pool closed")));
+        }
+
+        request_session(&self.sender_request, &self.sender_return, &self.metrics, options).await
     }
 
     pub fn give_back(&self, session: Session) {
+        self.metrics.session_returned();
+
         // TODO: We maybe shouldn't be ignoring this error
-        let _ = self.sender_return.try_send(session);
+        let _ = self.sender_return.try_send(PoolMessage::GiveBack(session));
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed.load(Relaxed)
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.metrics.stats()
+    }
+
+    pub fn spawn(&self, task: BackgroundTask) {
+        // Once the pool is closed the tasks are dropped
+        let _ = self.sender_task.try_send(task);
     }
 }
 
 #[async_trait::async_trait]
 impl SessionPool for ThreadedSessionPool {
-    async fn close(&self) {
-        self.close().await
+    async fn close(&self, timeout: Duration) -> PoolCloseReport {
+        self.close(timeout).await
     }
 
-    async fn get(&self) -> eyre::Result<Session> {
-        self.get().await
+    async fn get(&self, options: &AcquireOptions) -> QldbResult<Session> {
+        self.get(options).await
     }
 
     fn give_back(&self, session: Session) {
         self.give_back(session)
     }
+
+    fn spawn(&self, task: BackgroundTask) {
+        self.spawn(task)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.is_closed()
+    }
+
+    fn stats(&self) -> PoolStats {
+        self.stats()
+    }
 }
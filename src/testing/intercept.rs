@@ -0,0 +1,87 @@
+use crate::transport::{QldbTransport, SendCommandRequest, SendCommandResult, TransportError};
+use async_io::Timer;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What [InterceptingTransport] does with a command.
+#[derive(Debug)]
+pub enum Intercept {
+    /// Sends the command to the wrapped transport.
+    Forward,
+    /// Waits before sending the command to the wrapped transport.
+    Delay(Duration),
+    /// Returns the error without sending the command.
+    Fail(TransportError),
+    /// Sends the command to the wrapped transport but returns the error
+    /// instead of its result, as when the connection is lost after QLDB
+    /// received the command.
+    LoseResponse(TransportError),
+}
+
+type Interceptor = dyn Fn(&SendCommandRequest) -> Intercept + Send + Sync;
+
+/// Wraps a transport, usually a [FakeQldb](crate::testing::FakeQldb),
+/// and calls a function with every command before sending it. The
+/// function can record the command, delay it or fail it, which allows
+/// testing slow or failing ledgers and unreliable networks.
+///
+/// ```rust
+/// use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+/// use qldb::transport::{ServiceError, TransportError};
+/// use qldb::QldbClient;
+/// use std::sync::Arc;
+/// # use eyre::Result;
+///
+/// # fn test() -> Result<()> {
+/// let fake = FakeQldb::new();
+///
+/// let throttled = InterceptingTransport::new(fake.clone(), |request| {
+///     if request.execute_statement.is_some() {
+///         Intercept::Fail(ServiceError::RateExceeded("Slow down".to_string()).into())
+///     } else {
+///         Intercept::Forward
+///     }
+/// });
+///
+/// let client = QldbClient::builder("fake-ledger")
+///     .transport(Arc::new(throttled))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct InterceptingTransport {
+    transport: Arc<dyn QldbTransport>,
+    interceptor: Box<Interceptor>,
+}
+
+impl InterceptingTransport {
+    pub fn new<T, F>(transport: T, interceptor: F) -> InterceptingTransport
+    where
+        T: QldbTransport + 'static,
+        F: Fn(&SendCommandRequest) -> Intercept + Send + Sync + 'static,
+    {
+        InterceptingTransport {
+            transport: Arc::new(transport),
+            interceptor: Box::new(interceptor),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl QldbTransport for InterceptingTransport {
+    async fn send_command(&self, request: SendCommandRequest) -> Result<SendCommandResult, TransportError> {
+        match (self.interceptor)(&request) {
+            Intercept::Forward => {}
+            Intercept::Delay(delay) => {
+                Timer::after(delay).await;
+            }
+            Intercept::Fail(error) => return Err(error),
+            Intercept::LoseResponse(error) => {
+                let _ = self.transport.send_command(request).await;
+                return Err(error);
+            }
+        }
+
+        self.transport.send_command(request).await
+    }
+}
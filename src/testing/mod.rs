@@ -0,0 +1,757 @@
+//! In-memory fake of the QLDB session service, for running tests
+//! without AWS. Enabled with the `testing` feature.
+//!
+//! [FakeQldb] implements [QldbTransport](crate::QldbTransport), so the
+//! client, transactions and cursors run the same code they run against
+//! a real ledger:
+//!
+//! ```rust
+//! use qldb::testing::FakeQldb;
+//! use qldb::{ion::IonValue, QldbClient};
+//! use std::collections::HashMap;
+//! use std::sync::Arc;
+//! # use eyre::Result;
+//!
+//! # async fn test() -> Result<()> {
+//! let fake = FakeQldb::new();
+//!
+//! let client = QldbClient::builder("fake-ledger")
+//!     .transport(Arc::new(fake.clone()))
+//!     .build()?;
+//!
+//! client
+//!     .transaction_within(|tx| async move {
+//!         tx.query("CREATE TABLE Cars").execute().await?;
+//!
+//!         let car = HashMap::from([("plate".to_string(), IonValue::from("1234 ABC"))]);
+//!
+//!         tx.query("INSERT INTO Cars VALUE ?").param(car).execute().await?;
+//!
+//!         Ok(())
+//!     })
+//!     .await?;
+//!
+//! assert_eq!(fake.documents("Cars").len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only a small subset of PartiQL is understood:
+//!
+//! - `CREATE TABLE t` and `CREATE INDEX ON t (field)`.
+//! - `INSERT INTO t VALUE ?` and `INSERT INTO t << ?, ? >>`.
+//! - `SELECT * FROM t`, `SELECT VALUE a.b FROM t` and `SELECT COUNT(*) FROM t`.
+//! - `UPDATE t SET a = ?, b.c = ?` and `DELETE FROM t`.
+//!
+//! SELECT, UPDATE and DELETE accept an optional alias and a `WHERE`
+//! clause made of equalities joined by `AND`. Values can be `?`
+//! parameters, strings, integers, booleans or `null`.
+//!
+//! Transactions work on their own copy of the tables and are checked
+//! on commit. A transaction fails with an OCC conflict when any
+//! document it read or modified, or any table it scanned completely
+//! (statements without `WHERE` and counts), was changed by another
+//! transaction committed after it started.
+//!
+//! Every statement reports one read IO per document in the table it
+//! scans and one write IO per document it writes. Commits report one
+//! write IO per change and every command 1ms of processing time.
+//!
+//! [InterceptingTransport] wraps the fake for testing what happens when
+//! QLDB is slow or fails.
+
+mod intercept;
+mod partiql;
+
+pub use intercept::{Intercept, InterceptingTransport};
+
+use crate::transport::{
+    AbortTransactionResult, CommitTransactionResult, EndSessionResult, ExecuteStatementResult, FetchPageResult,
+    IoUsage, Page, QldbTransport, SendCommandRequest, SendCommandResult, ServiceError, StartSessionResult,
+    StartTransactionResult, TimingInformation, TransportError, ValueHolder,
+};
+use ion_binary_rs::{IonEncoder, IonHash, IonParser, IonValue};
+use partiql::{Expression, Path, Projection, Statement};
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Same page size used by QLDB.
+const DEFAULT_PAGE_SIZE: usize = 200;
+
+/// Version of the documents written by a transaction that is not
+/// committed yet.
+const UNCOMMITTED: u64 = u64::MAX;
+
+/// In-memory fake of the QLDB session service. Cloning it returns a
+/// handle to the same ledger, so a clone can be given to the client
+/// and another one kept for inspecting the data.
+#[derive(Clone)]
+pub struct FakeQldb {
+    state: Arc<Mutex<FakeState>>,
+    page_size: usize,
+}
+
+#[derive(Default)]
+struct FakeState {
+    ledger: Ledger,
+    sessions: HashMap<String, Option<FakeTransaction>>,
+}
+
+#[derive(Default)]
+struct Ledger {
+    next_id: u64,
+    version: u64,
+    tables: HashMap<String, Table>,
+}
+
+#[derive(Clone, Default)]
+struct Table {
+    last_write: u64,
+    documents: BTreeMap<u64, StoredDocument>,
+}
+
+#[derive(Clone)]
+struct StoredDocument {
+    id: String,
+    version: u64,
+    data: HashMap<String, IonValue>,
+}
+
+struct FakeTransaction {
+    id: String,
+    start_version: u64,
+    hasher: IonHash,
+    tables: HashMap<String, Table>,
+    created_tables: HashSet<String>,
+    scanned_tables: HashSet<String>,
+    read_documents: HashSet<(String, u64)>,
+    changes: Vec<Change>,
+    pages: HashMap<String, Vec<IonValue>>,
+    next_page: u64,
+}
+
+enum Change {
+    Put { table: String, key: u64 },
+    Delete { table: String, key: u64 },
+}
+
+impl Default for FakeQldb {
+    fn default() -> FakeQldb {
+        FakeQldb::new()
+    }
+}
+
+impl FakeQldb {
+    /// Creates an empty ledger. It accepts any ledger name.
+    pub fn new() -> FakeQldb {
+        FakeQldb {
+            state: Arc::new(Mutex::new(FakeState::default())),
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+
+    /// Amount of values returned in each page. Defaults to 200, like
+    /// QLDB. Lower values help testing the pagination.
+    pub fn with_page_size(mut self, page_size: usize) -> FakeQldb {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Committed documents of the table, in insertion order. Returns an
+    /// empty Vec when the table doesn't exist.
+    pub fn documents(&self, table: &str) -> Vec<HashMap<String, IonValue>> {
+        self.lock()
+            .ledger
+            .tables
+            .get(table)
+            .map(|table| table.documents.values().map(|document| document.data.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Amount of sessions currently open.
+    pub fn open_sessions(&self) -> usize {
+        self.lock().sessions.len()
+    }
+
+    /// Amount of transactions currently open.
+    pub fn open_transactions(&self) -> usize {
+        self.lock()
+            .sessions
+            .values()
+            .filter(|transaction| transaction.is_some())
+            .count()
+    }
+
+    /// Ends every open session, as QLDB does when they expire. Their
+    /// open transactions are discarded and the next commands sent with
+    /// them fail with `InvalidSession`.
+    pub fn expire_sessions(&self) {
+        self.lock().sessions.clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FakeState> {
+        // A test panicking while holding the lock shouldn't make the
+        // rest fail, the state is never left half updated.
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn handle(&self, request: SendCommandRequest) -> Result<SendCommandResult, TransportError> {
+        let mut state = self.lock();
+        let FakeState { ledger, sessions } = &mut *state;
+        let mut result = SendCommandResult::default();
+
+        if request.start_session.is_some() {
+            let session_token = format!("FakeSession{:011}", ledger.next_id());
+            sessions.insert(session_token.clone(), None);
+
+            result.start_session = Some(StartSessionResult {
+                session_token: Some(session_token),
+            });
+
+            return Ok(result);
+        }
+
+        let session_token = request.session_token.unwrap_or_default();
+
+        let session = sessions.get_mut(&session_token).ok_or_else(|| {
+            TransportError::from(ServiceError::InvalidSession(format!(
+                "Session {} does not exist",
+                session_token
+            )))
+        })?;
+
+        if request.end_session.is_some() {
+            sessions.remove(&session_token);
+            result.end_session = Some(EndSessionResult {});
+        } else if request.start_transaction.is_some() {
+            if session.is_some() {
+                return Err(bad_request("Transaction already open".to_string()));
+            }
+
+            let transaction = FakeTransaction::new(format!("FakeTransaction{:07}", ledger.next_id()), ledger.version);
+
+            result.start_transaction = Some(StartTransactionResult {
+                transaction_id: Some(transaction.id.clone()),
+            });
+
+            *session = Some(transaction);
+        } else if request.abort_transaction.is_some() {
+            *session = None;
+            result.abort_transaction = Some(AbortTransactionResult {});
+        } else if let Some(execute) = request.execute_statement {
+            let transaction = open_transaction(session, &execute.transaction_id)?;
+            let parameters = decode_parameters(execute.parameters.unwrap_or_default())?;
+
+            let mut statement_hash = IonHash::from_ion_value::<Sha256>(&IonValue::String(execute.statement.clone()));
+            for parameter in &parameters {
+                statement_hash.add_ion_value(parameter);
+            }
+            transaction.hasher.dot(statement_hash);
+
+            let (values, consumed_ios) = ledger.execute(transaction, &execute.statement, &parameters)?;
+
+            result.execute_statement = Some(ExecuteStatementResult {
+                first_page: Some(transaction.page(values, self.page_size)),
+                consumed_ios: Some(consumed_ios),
+                timing_information: Some(processing_time()),
+            });
+        } else if let Some(fetch) = request.fetch_page {
+            let transaction = open_transaction(session, &fetch.transaction_id)?;
+
+            let values = transaction
+                .pages
+                .remove(&fetch.next_page_token)
+                .ok_or_else(|| bad_request(format!("Unknown page token {}", fetch.next_page_token)))?;
+
+            result.fetch_page = Some(FetchPageResult {
+                page: Some(transaction.page(values, self.page_size)),
+                consumed_ios: Some(io_usage(0, 0)),
+                timing_information: Some(processing_time()),
+            });
+        } else if let Some(commit) = request.commit_transaction {
+            let transaction = open_transaction(session, &commit.transaction_id)?;
+            let commit_digest = transaction.hasher.get().to_vec();
+
+            if commit_digest != commit.commit_digest {
+                return Err(bad_request("Transaction's commit digest did not match".to_string()));
+            }
+
+            let write_ios = transaction.changes.len();
+
+            if let Some(transaction) = session.take() {
+                ledger.commit(transaction)?;
+            }
+
+            result.commit_transaction = Some(CommitTransactionResult {
+                transaction_id: Some(commit.transaction_id),
+                commit_digest: Some(commit_digest),
+                consumed_ios: Some(io_usage(0, write_ios)),
+                timing_information: Some(processing_time()),
+            });
+        }
+
+        Ok(result)
+    }
+}
+
+#[async_trait::async_trait]
+impl QldbTransport for FakeQldb {
+    async fn send_command(&self, request: SendCommandRequest) -> Result<SendCommandResult, TransportError> {
+        self.handle(request)
+    }
+}
+
+impl Ledger {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn execute(
+        &mut self,
+        transaction: &mut FakeTransaction,
+        statement: &str,
+        parameters: &[IonValue],
+    ) -> Result<(Vec<IonValue>, IoUsage), TransportError> {
+        let statement = partiql::parse(statement).map_err(bad_request)?;
+
+        match statement {
+            Statement::CreateTable { table } => {
+                if transaction.tables.contains_key(&table) || self.tables.contains_key(&table) {
+                    return Err(bad_request(format!("Table {} already exists", table)));
+                }
+
+                transaction.tables.insert(table.clone(), Table::default());
+                transaction.created_tables.insert(table.clone());
+
+                Ok((vec![single_field_struct("tableId", table)], io_usage(0, 1)))
+            }
+            Statement::CreateIndex { table } => {
+                transaction.table(self, &table)?;
+
+                Ok((vec![single_field_struct("tableId", table)], io_usage(0, 1)))
+            }
+            Statement::Insert { table, values } => {
+                let values = values
+                    .iter()
+                    .map(|value| match value.evaluate(parameters).map_err(bad_request)? {
+                        IonValue::Struct(data) => Ok(data),
+                        _ => Err(bad_request("Only structs can be inserted".to_string())),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let keys: Vec<u64> = values.iter().map(|_| self.next_id()).collect();
+                let documents = &mut transaction.table(self, &table)?.documents;
+                let read_ios = 0;
+                let mut result = vec![];
+                let mut changes = vec![];
+
+                for (key, data) in keys.into_iter().zip(values) {
+                    let id = format!("FakeDocument{:010}", key);
+
+                    documents.insert(
+                        key,
+                        StoredDocument {
+                            id: id.clone(),
+                            version: UNCOMMITTED,
+                            data,
+                        },
+                    );
+
+                    changes.push(Change::Put {
+                        table: table.clone(),
+                        key,
+                    });
+
+                    result.push(single_field_struct("documentId", id));
+                }
+
+                transaction.changes.extend(changes);
+
+                let write_ios = result.len();
+
+                Ok((result, io_usage(read_ios, write_ios)))
+            }
+            Statement::Select {
+                table,
+                alias,
+                projection,
+                condition,
+            } => {
+                let keys = transaction.find(self, &table, alias.as_deref(), &condition, parameters)?;
+
+                if condition.is_empty() || projection == Projection::Count {
+                    transaction.scanned_tables.insert(table.clone());
+                }
+
+                let documents = &transaction.table(self, &table)?.documents;
+                let read_ios = documents.len();
+
+                let values = match projection {
+                    Projection::Count => vec![single_field_struct("_1", keys.len() as i64)],
+                    Projection::All => keys
+                        .iter()
+                        .map(|key| IonValue::Struct(documents[key].data.clone()))
+                        .collect(),
+                    // Documents without the field are skipped, as MISSING
+                    Projection::Value(path) => {
+                        let path = strip_alias(&path, &table, alias.as_deref());
+
+                        keys.iter()
+                            .filter_map(|key| get_path(&documents[key].data, path).cloned())
+                            .collect()
+                    }
+                };
+
+                Ok((values, io_usage(read_ios, 0)))
+            }
+            Statement::Update {
+                table,
+                alias,
+                assignments,
+                condition,
+            } => {
+                let keys = transaction.find(self, &table, alias.as_deref(), &condition, parameters)?;
+
+                if condition.is_empty() {
+                    transaction.scanned_tables.insert(table.clone());
+                }
+
+                let assignments = assignments
+                    .iter()
+                    .map(|(path, value)| {
+                        let path = strip_alias(path, &table, alias.as_deref()).to_vec();
+                        Ok((path, value.evaluate(parameters).map_err(bad_request)?))
+                    })
+                    .collect::<Result<Vec<_>, TransportError>>()?;
+
+                let documents = &mut transaction.table(self, &table)?.documents;
+                let read_ios = documents.len();
+                let mut result = vec![];
+                let mut changes = vec![];
+
+                for key in keys {
+                    let document = documents.get_mut(&key).expect("Found documents exist");
+
+                    for (path, value) in &assignments {
+                        set_path(&mut document.data, path, value.clone());
+                    }
+
+                    document.version = UNCOMMITTED;
+
+                    changes.push(Change::Put {
+                        table: table.clone(),
+                        key,
+                    });
+
+                    result.push(single_field_struct("documentId", document.id.clone()));
+                }
+
+                transaction.changes.extend(changes);
+
+                let write_ios = result.len();
+
+                Ok((result, io_usage(read_ios, write_ios)))
+            }
+            Statement::Delete {
+                table,
+                alias,
+                condition,
+            } => {
+                let keys = transaction.find(self, &table, alias.as_deref(), &condition, parameters)?;
+
+                if condition.is_empty() {
+                    transaction.scanned_tables.insert(table.clone());
+                }
+
+                let documents = &mut transaction.table(self, &table)?.documents;
+                let read_ios = documents.len();
+                let mut result = vec![];
+                let mut changes = vec![];
+
+                for key in keys {
+                    if let Some(document) = documents.remove(&key) {
+                        changes.push(Change::Delete {
+                            table: table.clone(),
+                            key,
+                        });
+
+                        result.push(single_field_struct("documentId", document.id));
+                    }
+                }
+
+                transaction.changes.extend(changes);
+
+                let write_ios = result.len();
+
+                Ok((result, io_usage(read_ios, write_ios)))
+            }
+        }
+    }
+
+    fn commit(&mut self, transaction: FakeTransaction) -> Result<(), TransportError> {
+        let start = transaction.start_version;
+
+        let table_conflict = transaction
+            .created_tables
+            .iter()
+            .any(|table| self.tables.contains_key(table))
+            || transaction.scanned_tables.iter().any(|table| {
+                self.tables
+                    .get(table)
+                    .map(|table| table.last_write > start)
+                    .unwrap_or(false)
+            });
+
+        let document_conflict = transaction.read_documents.iter().any(|(table, key)| {
+            self.tables
+                .get(table)
+                .and_then(|table| table.documents.get(key))
+                .map(|document| document.version > start)
+                .unwrap_or(true)
+        });
+
+        if table_conflict || document_conflict {
+            return Err(TransportError::from(ServiceError::OccConflict(format!(
+                "Transaction {} has not been committed due to an OCC conflict",
+                transaction.id
+            ))));
+        }
+
+        self.version += 1;
+        let version = self.version;
+
+        for table in transaction.created_tables {
+            self.tables.insert(table, Table::default());
+        }
+
+        for change in transaction.changes {
+            let (table, key) = match &change {
+                Change::Put { table, key } | Change::Delete { table, key } => (table, *key),
+            };
+
+            let committed = self.tables.entry(table.clone()).or_default();
+            committed.last_write = version;
+
+            match change {
+                Change::Delete { .. } => {
+                    committed.documents.remove(&key);
+                }
+                Change::Put { .. } => {
+                    let document = transaction
+                        .tables
+                        .get(table)
+                        .and_then(|table| table.documents.get(&key));
+
+                    if let Some(document) = document {
+                        committed.documents.insert(
+                            key,
+                            StoredDocument {
+                                version,
+                                ..document.clone()
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FakeTransaction {
+    fn new(id: String, start_version: u64) -> FakeTransaction {
+        FakeTransaction {
+            hasher: IonHash::from_ion_value::<Sha256>(&IonValue::String(id.clone())),
+            id,
+            start_version,
+            tables: HashMap::new(),
+            created_tables: HashSet::new(),
+            scanned_tables: HashSet::new(),
+            read_documents: HashSet::new(),
+            changes: vec![],
+            pages: HashMap::new(),
+            next_page: 0,
+        }
+    }
+
+    /// Returns the transaction's copy of the table, copying it from the
+    /// ledger the first time it is used.
+    fn table(&mut self, ledger: &Ledger, table: &str) -> Result<&mut Table, TransportError> {
+        if !self.tables.contains_key(table) {
+            let committed = ledger
+                .tables
+                .get(table)
+                .ok_or_else(|| bad_request(format!("No such variable named '{}'", table)))?;
+
+            self.tables.insert(table.to_string(), committed.clone());
+        }
+
+        Ok(self.tables.get_mut(table).expect("Table has just been inserted"))
+    }
+
+    /// Keys of the documents matching the condition. The committed ones
+    /// are recorded for checking OCC conflicts on commit.
+    fn find(
+        &mut self,
+        ledger: &Ledger,
+        table: &str,
+        alias: Option<&str>,
+        condition: &[(Path, Expression)],
+        parameters: &[IonValue],
+    ) -> Result<Vec<u64>, TransportError> {
+        let condition = condition
+            .iter()
+            .map(|(path, value)| {
+                Ok((
+                    strip_alias(path, table, alias),
+                    value.evaluate(parameters).map_err(bad_request)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, TransportError>>()?;
+
+        let mut keys = vec![];
+        let mut read = vec![];
+
+        for (key, document) in &self.table(ledger, table)?.documents {
+            let matches = condition
+                .iter()
+                .all(|(path, value)| get_path(&document.data, path) == Some(value));
+
+            if matches {
+                keys.push(*key);
+
+                if document.version != UNCOMMITTED {
+                    read.push((table.to_string(), *key));
+                }
+            }
+        }
+
+        self.read_documents.extend(read);
+
+        Ok(keys)
+    }
+
+    fn page(&mut self, mut values: Vec<IonValue>, page_size: usize) -> Page {
+        let next_page_token = if values.len() > page_size {
+            self.next_page += 1;
+
+            let token = format!("{}-page-{}", self.id, self.next_page);
+            self.pages.insert(token.clone(), values.split_off(page_size));
+
+            Some(token)
+        } else {
+            None
+        };
+
+        Page {
+            values: Some(values.into_iter().map(encode_value).collect()),
+            next_page_token,
+        }
+    }
+}
+
+fn open_transaction<'a>(
+    session: &'a mut Option<FakeTransaction>,
+    transaction_id: &str,
+) -> Result<&'a mut FakeTransaction, TransportError> {
+    match session {
+        Some(transaction) if transaction.id == transaction_id => Ok(transaction),
+        _ => Err(bad_request(format!("Transaction {} is not open", transaction_id))),
+    }
+}
+
+fn strip_alias<'a>(path: &'a [String], table: &str, alias: Option<&str>) -> &'a [String] {
+    match path.split_first() {
+        Some((first, rest)) if !rest.is_empty() && (Some(first.as_str()) == alias || first == table) => rest,
+        _ => path,
+    }
+}
+
+fn get_path<'a>(data: &'a HashMap<String, IonValue>, path: &[String]) -> Option<&'a IonValue> {
+    let (first, rest) = path.split_first()?;
+    let value = data.get(first)?;
+
+    match (rest.is_empty(), value) {
+        (true, value) => Some(value),
+        (false, IonValue::Struct(inner)) => get_path(inner, rest),
+        (false, _) => None,
+    }
+}
+
+fn set_path(data: &mut HashMap<String, IonValue>, path: &[String], value: IonValue) {
+    let (first, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    if rest.is_empty() {
+        data.insert(first.clone(), value);
+        return;
+    }
+
+    let inner = data
+        .entry(first.clone())
+        .or_insert_with(|| IonValue::Struct(HashMap::new()));
+
+    if !matches!(inner, IonValue::Struct(_)) {
+        *inner = IonValue::Struct(HashMap::new());
+    }
+
+    if let IonValue::Struct(inner) = inner {
+        set_path(inner, rest, value);
+    }
+}
+
+fn single_field_struct<V: Into<IonValue>>(field: &str, value: V) -> IonValue {
+    IonValue::Struct(HashMap::from([(field.to_string(), value.into())]))
+}
+
+fn decode_parameters(parameters: Vec<ValueHolder>) -> Result<Vec<IonValue>, TransportError> {
+    let mut values = vec![];
+
+    for parameter in parameters {
+        let bytes = parameter
+            .ion_binary
+            .ok_or_else(|| bad_request("Only binary Ion parameters are supported".to_string()))?;
+
+        let parsed = IonParser::new(&bytes[..])
+            .consume_all()
+            .map_err(|error| bad_request(format!("Invalid Ion parameter: {:?}", error)))?;
+
+        values.extend(parsed);
+    }
+
+    Ok(values)
+}
+
+fn encode_value(value: IonValue) -> ValueHolder {
+    let mut encoder = IonEncoder::new();
+    encoder.add(value);
+
+    ValueHolder {
+        ion_binary: Some(encoder.encode()),
+        ion_text: None,
+    }
+}
+
+fn io_usage(read_ios: usize, write_ios: usize) -> IoUsage {
+    IoUsage {
+        read_ios: Some(read_ios as i64),
+        write_ios: Some(write_ios as i64),
+    }
+}
+
+/// The fake answers instantly, but it reports 1ms for every command so
+/// the timing information can be checked in tests.
+fn processing_time() -> TimingInformation {
+    TimingInformation {
+        processing_time_milliseconds: Some(1),
+    }
+}
+
+fn bad_request(message: String) -> TransportError {
+    TransportError::from(ServiceError::BadRequest(message))
+}
@@ -0,0 +1,389 @@
+use ion_binary_rs::{IonValue, NullIonValue};
+
+/// The subset of PartiQL understood by the fake ledger.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Statement {
+    CreateTable {
+        table: String,
+    },
+    CreateIndex {
+        table: String,
+    },
+    Insert {
+        table: String,
+        values: Vec<Expression>,
+    },
+    Select {
+        table: String,
+        alias: Option<String>,
+        projection: Projection,
+        condition: Vec<(Path, Expression)>,
+    },
+    Update {
+        table: String,
+        alias: Option<String>,
+        assignments: Vec<(Path, Expression)>,
+        condition: Vec<(Path, Expression)>,
+    },
+    Delete {
+        table: String,
+        alias: Option<String>,
+        condition: Vec<(Path, Expression)>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Projection {
+    All,
+    Count,
+    Value(Path),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expression {
+    Parameter(usize),
+    Literal(IonValue),
+}
+
+pub(crate) type Path = Vec<String>;
+
+impl Expression {
+    pub(crate) fn evaluate(&self, parameters: &[IonValue]) -> Result<IonValue, String> {
+        match self {
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Parameter(index) => parameters
+                .get(*index)
+                .cloned()
+                .ok_or_else(|| format!("Missing value for parameter number {}", index + 1)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    QuotedIdentifier(String),
+    String(String),
+    Integer(i64),
+    Parameter,
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 10] = ["<<", ">>", "*", "(", ")", ",", "=", ".", ";", "-"];
+
+fn tokenize(statement: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = statement.chars().collect();
+    let mut tokens = vec![];
+    let mut index = 0;
+
+    while index < chars.len() {
+        let current = chars[index];
+
+        if current.is_whitespace() {
+            index += 1;
+        } else if current == '?' {
+            tokens.push(Token::Parameter);
+            index += 1;
+        } else if current == '\'' || current == '"' {
+            let mut value = String::new();
+            index += 1;
+
+            loop {
+                match chars.get(index) {
+                    None => return Err("Unterminated string literal".to_string()),
+                    Some(&c) if c == current && chars.get(index + 1) == Some(&current) => {
+                        value.push(current);
+                        index += 2;
+                    }
+                    Some(&c) if c == current => {
+                        index += 1;
+                        break;
+                    }
+                    Some(&c) => {
+                        value.push(c);
+                        index += 1;
+                    }
+                }
+            }
+
+            tokens.push(if current == '\'' {
+                Token::String(value)
+            } else {
+                Token::QuotedIdentifier(value)
+            });
+        } else if current.is_ascii_digit() {
+            let start = index;
+            while index < chars.len() && chars[index].is_ascii_digit() {
+                index += 1;
+            }
+            let number: String = chars[start..index].iter().collect();
+            tokens.push(Token::Integer(
+                number.parse().map_err(|_| "Integer too big".to_string())?,
+            ));
+        } else if current.is_alphabetic() || current == '_' {
+            let start = index;
+            while index < chars.len() && (chars[index].is_alphanumeric() || chars[index] == '_') {
+                index += 1;
+            }
+            tokens.push(Token::Word(chars[start..index].iter().collect()));
+        } else {
+            let rest: String = chars[index..chars.len().min(index + 2)].iter().collect();
+
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(*symbol))
+                .ok_or_else(|| format!("Unexpected character '{}'", current))?;
+
+            tokens.push(Token::Symbol(symbol));
+            index += symbol.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+pub(crate) fn parse(statement: &str) -> Result<Statement, String> {
+    let mut parser = Parser {
+        tokens: tokenize(statement)?,
+        position: 0,
+        parameters: 0,
+    };
+
+    let statement = parser.statement()?;
+
+    parser.accept_symbol(";");
+
+    if parser.position < parser.tokens.len() {
+        return Err(format!("Unexpected token {:?}", parser.tokens[parser.position]));
+    }
+
+    Ok(statement)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    parameters: usize,
+}
+
+impl Parser {
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.accept_keyword("CREATE") {
+            if self.accept_keyword("TABLE") {
+                return Ok(Statement::CreateTable {
+                    table: self.identifier()?,
+                });
+            }
+
+            self.expect_keyword("INDEX")?;
+            self.expect_keyword("ON")?;
+            let table = self.identifier()?;
+            self.expect_symbol("(")?;
+            self.path()?;
+            self.expect_symbol(")")?;
+
+            return Ok(Statement::CreateIndex { table });
+        }
+
+        if self.accept_keyword("INSERT") {
+            self.expect_keyword("INTO")?;
+            let table = self.identifier()?;
+
+            let values = if self.accept_keyword("VALUE") {
+                vec![self.expression()?]
+            } else {
+                self.expect_symbol("<<")?;
+                let mut values = vec![self.expression()?];
+                while self.accept_symbol(",") {
+                    values.push(self.expression()?);
+                }
+                self.expect_symbol(">>")?;
+                values
+            };
+
+            return Ok(Statement::Insert { table, values });
+        }
+
+        if self.accept_keyword("SELECT") {
+            let projection = if self.accept_symbol("*") {
+                Projection::All
+            } else if self.accept_keyword("VALUE") {
+                Projection::Value(self.path()?)
+            } else {
+                self.expect_keyword("COUNT")?;
+                self.expect_symbol("(")?;
+                self.expect_symbol("*")?;
+                self.expect_symbol(")")?;
+                Projection::Count
+            };
+
+            self.expect_keyword("FROM")?;
+            let table = self.identifier()?;
+            let alias = self.alias()?;
+            let condition = self.condition()?;
+
+            return Ok(Statement::Select {
+                table,
+                alias,
+                projection,
+                condition,
+            });
+        }
+
+        if self.accept_keyword("UPDATE") {
+            let table = self.identifier()?;
+            let alias = self.alias()?;
+
+            self.expect_keyword("SET")?;
+            let mut assignments = vec![self.assignment()?];
+            while self.accept_symbol(",") {
+                assignments.push(self.assignment()?);
+            }
+
+            let condition = self.condition()?;
+
+            return Ok(Statement::Update {
+                table,
+                alias,
+                assignments,
+                condition,
+            });
+        }
+
+        if self.accept_keyword("DELETE") {
+            self.expect_keyword("FROM")?;
+            let table = self.identifier()?;
+            let alias = self.alias()?;
+            let condition = self.condition()?;
+
+            return Ok(Statement::Delete {
+                table,
+                alias,
+                condition,
+            });
+        }
+
+        Err("Statement not supported by the fake ledger".to_string())
+    }
+
+    fn alias(&mut self) -> Result<Option<String>, String> {
+        if self.accept_keyword("AS") {
+            return Ok(Some(self.identifier()?));
+        }
+
+        match self.tokens.get(self.position) {
+            Some(Token::Word(word)) if !is_keyword(word) => Ok(Some(self.identifier()?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Vec<(Path, Expression)>, String> {
+        let mut condition = vec![];
+
+        if self.accept_keyword("WHERE") {
+            condition.push(self.assignment()?);
+            while self.accept_keyword("AND") {
+                condition.push(self.assignment()?);
+            }
+        }
+
+        Ok(condition)
+    }
+
+    fn assignment(&mut self) -> Result<(Path, Expression), String> {
+        let path = self.path()?;
+        self.expect_symbol("=")?;
+        Ok((path, self.expression()?))
+    }
+
+    fn path(&mut self) -> Result<Path, String> {
+        let mut path = vec![self.identifier()?];
+        while self.accept_symbol(".") {
+            path.push(self.identifier()?);
+        }
+        Ok(path)
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let negative = self.accept_symbol("-");
+
+        let expression = match self.next() {
+            Some(Token::Parameter) if !negative => {
+                self.parameters += 1;
+                Expression::Parameter(self.parameters - 1)
+            }
+            Some(Token::Integer(value)) => {
+                Expression::Literal(IonValue::Integer(if negative { -value } else { value }))
+            }
+            Some(Token::String(value)) if !negative => Expression::Literal(IonValue::String(value)),
+            Some(Token::Word(word)) if !negative && word.eq_ignore_ascii_case("true") => {
+                Expression::Literal(IonValue::Bool(true))
+            }
+            Some(Token::Word(word)) if !negative && word.eq_ignore_ascii_case("false") => {
+                Expression::Literal(IonValue::Bool(false))
+            }
+            Some(Token::Word(word)) if !negative && word.eq_ignore_ascii_case("null") => {
+                Expression::Literal(IonValue::Null(NullIonValue::Null))
+            }
+            token => return Err(format!("Expected a value, found {:?}", token)),
+        };
+
+        Ok(expression)
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            Some(Token::QuotedIdentifier(word)) => Ok(word),
+            token => Err(format!("Expected an identifier, found {:?}", token)),
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.position) {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.accept_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(format!("Expected {}", keyword))
+        }
+    }
+
+    fn accept_symbol(&mut self, symbol: &str) -> bool {
+        match self.tokens.get(self.position) {
+            Some(Token::Symbol(found)) if *found == symbol => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.accept_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(format!("Expected '{}'", symbol))
+        }
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    ["WHERE", "SET", "AND", "AS"]
+        .iter()
+        .any(|keyword| word.eq_ignore_ascii_case(keyword))
+}
@@ -0,0 +1,302 @@
+use crate::types::{QldbError, QldbErrorKind, QldbResult};
+use async_channel::{bounded, Receiver, Sender};
+use async_io::Timer;
+use futures::future::{self, Either};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Controls how many transactions the client runs at the same time
+/// when QLDB throttles it.
+///
+/// The client starts allowing `max_limit` concurrent transactions.
+/// Every time a transaction fails because of `RateExceededException`,
+/// `LimitExceededException` or `CapacityExceededException` the limit is
+/// multiplied by `decrease_factor`, down to `min_limit`, and every
+/// transaction that finishes without being throttled, committed or
+/// rolled back, raises it again a bit (by one each `limit`
+/// transactions). Transactions over the limit wait, so the
+/// pool doesn't open more sessions while QLDB is overloaded.
+///
+/// Failures of transactions started before the last decrease don't
+/// decrease the limit again, as they were caused by the previous limit.
+///
+/// The current limit is returned by `QldbClient::concurrency_limit`.
+///
+/// ```rust
+/// use qldb::ThrottlingPolicy;
+///
+/// let policy = ThrottlingPolicy::new()
+///     .min_limit(2)
+///     .max_limit(50)
+///     .decrease_factor(0.7);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThrottlingPolicy {
+    min_limit: usize,
+    max_limit: Option<usize>,
+    decrease_factor: f64,
+}
+
+impl ThrottlingPolicy {
+    /// Policy with the default values: the limit goes from 1 to the
+    /// maximum of sessions of the pool and it is halved on throttling.
+    pub fn new() -> ThrottlingPolicy {
+        ThrottlingPolicy::default()
+    }
+
+    /// A policy that never reduces the amount of concurrent
+    /// transactions.
+    pub fn disabled() -> ThrottlingPolicy {
+        ThrottlingPolicy::new().decrease_factor(1.0)
+    }
+
+    /// The limit never goes below this value. Values under 1 are
+    /// treated as 1.
+    pub fn min_limit(mut self, min_limit: usize) -> ThrottlingPolicy {
+        self.min_limit = min_limit.max(1);
+        self
+    }
+
+    /// The initial limit and the highest it can recover to. Defaults
+    /// to `max_sessions`.
+    pub fn max_limit(mut self, max_limit: usize) -> ThrottlingPolicy {
+        self.max_limit = Some(max_limit.max(1));
+        self
+    }
+
+    /// What the limit is multiplied by on throttling, between 0 and 1.
+    pub fn decrease_factor(mut self, decrease_factor: f64) -> ThrottlingPolicy {
+        self.decrease_factor = decrease_factor.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn get_min_limit(&self) -> usize {
+        self.min_limit
+    }
+
+    pub fn get_max_limit(&self) -> Option<usize> {
+        self.max_limit
+    }
+
+    pub fn get_decrease_factor(&self) -> f64 {
+        self.decrease_factor
+    }
+}
+
+impl Default for ThrottlingPolicy {
+    fn default() -> Self {
+        ThrottlingPolicy {
+            min_limit: 1,
+            max_limit: None,
+            decrease_factor: 0.5,
+        }
+    }
+}
+
+/// Whether QLDB rejected the request because of the load. The errors of
+/// the pool itself, like `PoolExhausted`, don't count.
+pub(crate) fn is_throttling(error: &QldbError) -> bool {
+    let throttling_kind = matches!(
+        error.kind(),
+        QldbErrorKind::RateExceeded | QldbErrorKind::LimitExceeded | QldbErrorKind::CapacityExceeded
+    );
+
+    throttling_kind && !matches!(error, QldbError::PoolExhausted(_))
+}
+
+struct LimiterState {
+    limit: f64,
+    in_flight: usize,
+    last_decrease: Option<Instant>,
+    waiters: VecDeque<Sender<()>>,
+}
+
+/// AIMD limit of the transactions running at the same time, following
+/// the `ThrottlingPolicy` of the client.
+pub(crate) struct ConcurrencyLimiter {
+    min_limit: f64,
+    max_limit: f64,
+    decrease_factor: f64,
+    state: Mutex<LimiterState>,
+}
+
+impl ConcurrencyLimiter {
+    /// `max_sessions` is the maximum used when the policy doesn't set
+    /// one.
+    pub(crate) fn new(policy: &ThrottlingPolicy, max_sessions: usize) -> ConcurrencyLimiter {
+        let max_limit = policy.max_limit.unwrap_or(max_sessions).max(1) as f64;
+        let min_limit = (policy.min_limit as f64).min(max_limit);
+
+        ConcurrencyLimiter {
+            min_limit,
+            max_limit,
+            decrease_factor: policy.decrease_factor,
+            state: Mutex::new(LimiterState {
+                limit: max_limit,
+                in_flight: 0,
+                last_decrease: None,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.lock().limit as usize
+    }
+
+    /// Waits until the transaction can start. With a timeout it fails
+    /// with `QldbError::SessionAcquireTimeout`, as the time waiting here
+    /// is part of waiting for a session.
+    pub(crate) async fn acquire(self: &Arc<Self>, timeout: Option<Duration>) -> QldbResult<Permit> {
+        let started = Instant::now();
+
+        loop {
+            let waiter = match self.try_acquire() {
+                Ok(permit) => return Ok(permit),
+                Err(receiver) => Waiter {
+                    limiter: self,
+                    receiver,
+                },
+            };
+
+            // Freed slots are handed over to the first waiter, so the
+            // ones arriving later cannot take them first. If it is
+            // cancelled after getting the slot, it passes the slot on
+            // when dropped
+            let receiver = &waiter.receiver;
+
+            let handed_over = match timeout {
+                Some(timeout) => match future::select(receiver.recv(), Timer::at(started + timeout)).await {
+                    Either::Left((handed_over, _)) => handed_over,
+                    Either::Right(_) => return Err(QldbError::SessionAcquireTimeout(timeout)),
+                },
+                None => receiver.recv().await,
+            };
+
+            if handed_over.is_ok() {
+                return Ok(self.permit());
+            }
+        }
+    }
+
+    fn try_acquire(self: &Arc<Self>) -> Result<Permit, Receiver<()>> {
+        let mut state = self.lock();
+
+        if state.in_flight < state.limit as usize {
+            state.in_flight += 1;
+            return Ok(self.permit());
+        }
+
+        let (sender, receiver) = bounded(1);
+        state.waiters.push_back(sender);
+
+        Err(receiver)
+    }
+
+    /// For a slot already counted in `in_flight`.
+    fn permit(self: &Arc<Self>) -> Permit {
+        Permit {
+            limiter: self.clone(),
+            acquired_at: Instant::now(),
+        }
+    }
+
+    /// A transaction finished without being throttled.
+    pub(crate) fn record_success(&self) {
+        let mut state = self.lock();
+
+        let previous = state.limit as usize;
+        state.limit = (state.limit + 1.0 / state.limit).min(self.max_limit);
+
+        // It grows by one slot at most
+        if state.limit as usize > previous && state.in_flight < state.limit as usize {
+            state.in_flight += 1;
+            hand_over(&mut state);
+        }
+    }
+
+    /// Reduces the limit if the error is a throttling one, unless it
+    /// was already reduced after `acquired_at`.
+    pub(crate) fn record_error(&self, error: &QldbError, acquired_at: Instant) {
+        if !is_throttling(error) {
+            return;
+        }
+
+        let mut state = self.lock();
+
+        if state.last_decrease.is_some_and(|last| acquired_at < last) {
+            return;
+        }
+
+        state.limit = (state.limit * self.decrease_factor).max(self.min_limit);
+        state.last_decrease = Some(Instant::now());
+    }
+
+    fn release(&self) {
+        let mut state = self.lock();
+
+        // The slot is gone when the limit was reduced under it
+        if state.in_flight > state.limit as usize {
+            state.in_flight -= 1;
+        } else {
+            hand_over(&mut state);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LimiterState> {
+        // The state is always left consistent, even after a panic
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Gives a slot counted in `in_flight` to the first waiter still
+/// waiting, or frees it when there is none.
+fn hand_over(state: &mut LimiterState) {
+    while let Some(waiter) = state.waiters.pop_front() {
+        // It fails when the waiter is gone
+        if waiter.try_send(()).is_ok() {
+            return;
+        }
+    }
+
+    state.in_flight = state.in_flight.saturating_sub(1);
+}
+
+/// A transaction waiting for a slot.
+struct Waiter<'a> {
+    limiter: &'a ConcurrencyLimiter,
+    receiver: Receiver<()>,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        // Handed a slot but cancelled before taking it, so it goes to
+        // the next waiter instead
+        if self.receiver.try_recv().is_ok() {
+            self.limiter.release();
+        }
+    }
+}
+
+/// A slot of the concurrency limit, freed when dropped.
+pub(crate) struct Permit {
+    limiter: Arc<ConcurrencyLimiter>,
+    acquired_at: Instant,
+}
+
+impl Permit {
+    pub(crate) fn limiter(&self) -> &Arc<ConcurrencyLimiter> {
+        &self.limiter
+    }
+
+    pub(crate) fn acquired_at(&self) -> Instant {
+        self.acquired_at
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
@@ -1,16 +1,27 @@
+use crate::bulk_load::{
+    check_table_name, check_transaction_limits, insert_statement, Batches, BulkLimits, EncodedDocument,
+};
+use crate::query_builder::{valueholders_to_ionvalues, Param, Prefetch};
+use crate::session_pool::BackgroundTask;
 use crate::session_pool::{Session, SessionPool};
-use crate::types::{QldbError, QldbResult};
-use crate::QueryBuilder;
+use crate::throttling::{is_throttling, ConcurrencyLimiter, Permit};
+use crate::transport::{
+    AbortTransactionRequest, CommitTransactionRequest, FetchPageRequest, QldbTransport, SendCommandRequest,
+    SendCommandResult, StartTransactionRequest,
+};
+use crate::types::{QldbError, QldbResult, TimeoutPhase};
+use crate::{QueryBuilder, QueryStats};
+use async_io::Timer;
+use futures::future::{self, Either};
 use futures::lock::Mutex;
 use futures::lock::MutexGuard;
-use ion_binary_rs::{IonEncoder, IonHash, IonValue};
-use rusoto_qldb_session::{
-    AbortTransactionRequest, CommitTransactionRequest, QldbSession, QldbSessionClient, SendCommandRequest,
-    StartTransactionRequest,
-};
+use ion_binary_rs::{IonHash, IonValue};
 use sha2::Sha256;
 use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 enum TransactionStatus {
@@ -21,25 +32,148 @@ enum TransactionStatus {
 
 /// Every query in QLDB is within a transaction. Ideally you will interact
 /// with this object via the method QLDBClient::transaction_within.
+///
+/// Dropping every clone of an open transaction rolls it back in the
+/// background, so cancelling a future using it doesn't leave the
+/// transaction open in QLDB nor loses the session.
 #[derive(Clone)]
 pub struct Transaction {
-    client: Arc<QldbSessionClient>,
+    client: Arc<dyn QldbTransport>,
     session_pool: Arc<dyn SessionPool>,
     pub(crate) transaction_id: Arc<String>,
     pub(crate) session: Arc<Session>,
     completed: Arc<Mutex<TransactionStatus>>,
     hasher: Arc<Mutex<IonHash>>,
+    stats: Arc<Mutex<QueryStats>>,
     auto_rollback: bool,
+    bulk_limits: BulkLimits,
+    // Of the last command sent, for `QldbError::Timeout`
+    phase: Arc<std::sync::Mutex<TimeoutPhase>>,
+    limiter: Arc<ConcurrencyLimiter>,
+    acquired_at: Instant,
+    // Set when QLDB throttles a command, so the transaction doesn't
+    // raise the concurrency limit when it finishes
+    throttled: Arc<AtomicBool>,
+    drop_guard: Arc<DropGuard>,
+    // Pages of a cursor being loaded in the background
+    prefetch: Arc<std::sync::Mutex<Option<Prefetch>>>,
+}
+
+/// Shared by all the clones of a transaction. When the last one is
+/// dropped while the transaction is still open, for example because the
+/// future using it was cancelled, the transaction is aborted in the
+/// executor of the pool and the session is given back. If the abort
+/// fails the session is discarded.
+///
+/// It also holds the slot of the transaction in the concurrency limit
+/// of the client until the transaction is completed.
+struct DropGuard {
+    client: Arc<dyn QldbTransport>,
+    session_pool: Arc<dyn SessionPool>,
+    session: Session,
+    completed: Arc<Mutex<TransactionStatus>>,
+    permit: std::sync::Mutex<Option<Permit>>,
+}
+
+impl DropGuard {
+    /// The session was already given back.
+    fn disarm(&self) {
+        if let Some(mut status) = self.completed.try_lock() {
+            *status = TransactionStatus::Rollback;
+        }
+    }
+
+    fn release_permit(&self) {
+        if let Ok(mut permit) = self.permit.lock() {
+            permit.take();
+        }
+    }
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        // Nobody else can hold the lock once every clone is dropped
+        let is_open = self
+            .completed
+            .try_lock()
+            .is_some_and(|status| matches!(*status, TransactionStatus::Open));
+
+        if !is_open {
+            return;
+        }
+
+        let session = self.session.clone();
+
+        // The transaction is already gone with the session
+        if session.is_poisoned() {
+            self.session_pool.give_back(session);
+            return;
+        }
+
+        abort_in_background(&self.client, &self.session_pool, session, None);
+    }
+}
+
+/// Aborts the transaction of the session in the executor of the pool
+/// and gives the session back, or discards it if the abort fails. The
+/// prefetch of a cursor, if any, is stopped first.
+fn abort_in_background(
+    client: &Arc<dyn QldbTransport>,
+    session_pool: &Arc<dyn SessionPool>,
+    session: Session,
+    prefetch: Option<Prefetch>,
+) {
+    let client = client.clone();
+    let pool = session_pool.clone();
+
+    session_pool.spawn(Box::pin(async move {
+        if let Some(prefetch) = prefetch {
+            prefetch.stop().await;
+        }
+
+        let rollback = create_rollback_command(session.get_session_id());
+
+        if send_command(&client, &session, rollback).await.is_err() {
+            session.poison();
+        }
+
+        pool.give_back(session);
+    }));
 }
 
 impl Transaction {
     pub(crate) async fn new(
-        client: Arc<QldbSessionClient>,
+        client: Arc<dyn QldbTransport>,
         session_pool: Arc<dyn SessionPool>,
         session: Session,
         auto_rollback: bool,
+        permit: Permit,
+        bulk_limits: BulkLimits,
     ) -> QldbResult<Transaction> {
-        let transaction_id = Transaction::get_transaction_id(&client, session.get_session_id()).await?;
+        let completed = Arc::new(Mutex::new(TransactionStatus::Open));
+        let limiter = permit.limiter().clone();
+        let acquired_at = permit.acquired_at();
+
+        // Aborts the transaction if this future is dropped while starting it
+        let drop_guard = Arc::new(DropGuard {
+            client: client.clone(),
+            session_pool: session_pool.clone(),
+            session: session.clone(),
+            completed: completed.clone(),
+            permit: std::sync::Mutex::new(Some(permit)),
+        });
+
+        let transaction_id = match Transaction::get_transaction_id(&client, &session).await {
+            Ok(transaction_id) => transaction_id,
+            Err(error) => {
+                limiter.record_error(&error, acquired_at);
+
+                // The session is still usable unless QLDB said otherwise
+                drop_guard.disarm();
+                session_pool.give_back(session);
+                return Err(error);
+            }
+        };
 
         let hasher = IonHash::from_ion_value::<Sha256>(&IonValue::String(transaction_id.clone()));
 
@@ -48,45 +182,147 @@ impl Transaction {
             session_pool,
             transaction_id: Arc::new(transaction_id),
             session: Arc::new(session),
-            completed: Arc::new(Mutex::new(TransactionStatus::Open)),
+            completed,
             hasher: Arc::new(Mutex::new(hasher)),
+            stats: Arc::new(Mutex::new(QueryStats::default())),
             auto_rollback,
+            bulk_limits,
+            phase: Arc::new(std::sync::Mutex::new(TimeoutPhase::Acquire)),
+            limiter,
+            acquired_at,
+            throttled: Arc::new(AtomicBool::new(false)),
+            drop_guard,
+            prefetch: Arc::new(std::sync::Mutex::new(None)),
         })
     }
 
     /// Sends a query to QLDB. It will return an Array of IonValues
     /// already decoded. Parameters need to be provided using IonValue.
     pub fn query(&self, statement: &str) -> QueryBuilder {
-        QueryBuilder::new(self.client.clone(), self.clone(), statement, self.auto_rollback)
+        QueryBuilder::new(self.clone(), statement, self.auto_rollback)
+    }
+
+    /// Inserts the documents in `table` with as few `INSERT INTO table
+    /// << ?, ... >>` statements as the [BulkLimits](crate::BulkLimits)
+    /// of the client allow, and returns the ids of the new documents in
+    /// the same order.
+    ///
+    /// All of them go in this transaction, so it fails with
+    /// `QldbError::BulkLimitsExceeded`, before sending anything, when
+    /// they go over the limits of one transaction, and with
+    /// `QldbError::InvalidTableName` when `table` isn't a valid table
+    /// name. `QldbClient::bulk_load` splits bigger inputs in several
+    /// transactions.
+    ///
+    /// ```rust,no_run
+    /// use qldb::{QldbResult, Transaction};
+    /// use std::collections::HashMap;
+    ///
+    /// # async fn test(tx: Transaction) -> QldbResult<()> {
+    /// let cars = (0..30).map(|number| {
+    ///     let mut car = HashMap::new();
+    ///     car.insert("plate", format!("{:04}-ABC", number));
+    ///     car
+    /// });
+    ///
+    /// let document_ids = tx.insert_many("Cars", cars).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn insert_many<D: Into<IonValue>>(
+        &self,
+        table: &str,
+        documents: impl IntoIterator<Item = D>,
+    ) -> QldbResult<Vec<String>> {
+        let documents = documents
+            .into_iter()
+            .map(|document| EncodedDocument::new(document.into()))
+            .collect();
+
+        self.insert_encoded(table, documents).await
+    }
+
+    /// `insert_many` for documents already encoded.
+    pub(crate) async fn insert_encoded(&self, table: &str, documents: Vec<EncodedDocument>) -> QldbResult<Vec<String>> {
+        check_table_name(table)?;
+        check_transaction_limits(&documents, &self.bulk_limits)?;
+
+        let batches = Batches::new(
+            documents.into_iter(),
+            self.bulk_limits.get_documents_per_statement(),
+            self.bulk_limits.get_bytes_per_transaction(),
+        );
+
+        let mut document_ids = vec![];
+
+        for batch in batches {
+            let mut query = self.query(&insert_statement(table, batch.len())?);
+
+            for document in batch {
+                query = query.encoded_param(document);
+            }
+
+            for inserted in query.execute().await? {
+                document_ids.push(inserted.get_value::<String>("documentId")?);
+            }
+        }
+
+        Ok(document_ids)
     }
 
+    /// Commits the transaction. QLDB returns the digest of all the
+    /// statements executed in the transaction, which is compared with
+    /// the one calculated locally. If they differ it returns
+    /// `QldbError::CommitDigestMismatch`. Keep in mind that, in that
+    /// case, QLDB has already committed the transaction.
+    ///
+    /// The IOs and processing time used by the whole transaction,
+    /// commit included, are returned by `stats` afterwards.
     pub async fn commit(&self) -> QldbResult<()> {
         use TransactionStatus::*;
 
+        self.stop_prefetch().await;
+
         let is_completed = self.completed.lock().await;
 
         match *is_completed {
-            Commit => return Ok(()),
-            Rollback => return Err(QldbError::TransactionAlreadyRollback),
+            Commit => Ok(()),
+            Rollback => Err(QldbError::TransactionAlreadyRollback),
             Open => {
                 let commit_digest = self.hasher.lock().await.get().to_owned();
 
-                self.client
+                let response = match self
                     .send_command(create_commit_command(
                         self.session.get_session_id(),
                         &self.transaction_id,
                         &commit_digest,
                     ))
-                    .await?;
+                    .await
+                {
+                    Ok(response) => response,
+                    Err(error) => {
+                        // QLDB won't accept anything else in this session
+                        if self.session.is_poisoned() {
+                            self.complete(is_completed, Rollback);
+                        }
+
+                        return Err(error);
+                    }
+                };
+
+                self.complete(is_completed, Commit);
+
+                let commit_result = response.commit_transaction.unwrap_or_default();
+
+                self.add_stats(QueryStats::from_result(
+                    commit_result.consumed_ios,
+                    commit_result.timing_information,
+                ))
+                .await;
+
+                check_commit_digest(commit_digest, commit_result.commit_digest.unwrap_or_default())
             }
         }
-
-        self.complete(is_completed, Commit);
-
-        // TODO: Check the returned CommitDigest with the
-        // current hash and fail if they are not equal.
-
-        Ok(())
     }
 
     pub(crate) async fn silent_commit(&self) -> QldbResult<()> {
@@ -107,15 +343,27 @@ impl Transaction {
     pub async fn rollback(&self) -> QldbResult<()> {
         use TransactionStatus::*;
 
+        self.stop_prefetch().await;
+
         let is_completed = self.completed.lock().await;
 
         match *is_completed {
             Rollback => return Ok(()),
             Commit => return Err(QldbError::TransactionAlreadyCommitted),
+            // The transaction is already gone with the session
+            Open if self.session.is_poisoned() => {}
             Open => {
-                self.client
+                let result = self
                     .send_command(create_rollback_command(self.session.get_session_id()))
-                    .await?;
+                    .await;
+
+                if let Err(error) = result {
+                    if self.session.is_poisoned() {
+                        self.complete(is_completed, Rollback);
+                    }
+
+                    return Err(error);
+                }
             }
         }
 
@@ -140,6 +388,146 @@ impl Transaction {
         }
     }
 
+    /// IOs and processing time used by the statements executed so far
+    /// in this transaction and, once committed, by the commit.
+    pub async fn stats(&self) -> QueryStats {
+        *self.stats.lock().await
+    }
+
+    pub(crate) async fn fetch_page(&self, page_token: &str) -> QldbResult<(Vec<IonValue>, Option<String>, QueryStats)> {
+        self.stop_prefetch().await;
+        self.load_page(page_token).await
+    }
+
+    /// Same as `fetch_page`, for the prefetch task itself.
+    pub(crate) async fn load_page(&self, page_token: &str) -> QldbResult<(Vec<IonValue>, Option<String>, QueryStats)> {
+        let result = self
+            .send_command(create_next_page_command(
+                self.session.get_session_id(),
+                &self.transaction_id,
+                page_token,
+            ))
+            .await?;
+
+        let fetch_page = result.fetch_page.unwrap_or_default();
+
+        let stats = QueryStats::from_result(fetch_page.consumed_ios, fetch_page.timing_information);
+        self.add_stats(stats).await;
+
+        let (values, next_page_token) = fetch_page
+            .page
+            .map(|page| {
+                // Default of Vec is empty Vec
+                let values = page.values.unwrap_or_default();
+
+                (values, page.next_page_token)
+            })
+            .unwrap_or((vec![], None));
+
+        let values = valueholders_to_ionvalues(values)?;
+
+        Ok((values, next_page_token, stats))
+    }
+
+    /// Sends a command of this transaction to QLDB. When QLDB says
+    /// that the session isn't valid anymore the session is poisoned,
+    /// so the pool discards it instead of giving it to another
+    /// transaction.
+    pub(crate) async fn send_command(&self, request: SendCommandRequest) -> QldbResult<SendCommandResult> {
+        if let Ok(mut phase) = self.phase.lock() {
+            *phase = command_phase(&request);
+        }
+
+        let result = send_command(&self.client, &self.session, request).await;
+
+        if let Err(error) = &result {
+            if is_throttling(error) {
+                self.throttled.store(true, Relaxed);
+            }
+
+            self.limiter.record_error(error, self.acquired_at);
+        }
+
+        result
+    }
+
+    /// Runs `future` until `started + timeout`. When the deadline
+    /// expires the transaction is rolled back in the background and it
+    /// returns `QldbError::Timeout` with the phase of the last command
+    /// sent.
+    pub(crate) async fn within_deadline<T>(
+        &self,
+        started: Instant,
+        timeout: Duration,
+        future: impl Future<Output = QldbResult<T>>,
+    ) -> QldbResult<T> {
+        let result = future::select(Box::pin(future), Timer::at(started + timeout)).await;
+
+        let pending = match result {
+            Either::Left((result, _)) => return result,
+            Either::Right((_, pending)) => pending,
+        };
+
+        // It may be holding the lock of the transaction status
+        drop(pending);
+
+        let phase = self.phase.lock().map(|phase| *phase).unwrap_or(TimeoutPhase::Statement);
+
+        self.cancel().await;
+
+        Err(QldbError::Timeout {
+            elapsed: started.elapsed(),
+            phase,
+        })
+    }
+
+    /// Rolls back the transaction without waiting for QLDB.
+    async fn cancel(&self) {
+        let mut is_completed = self.completed.lock().await;
+
+        if let TransactionStatus::Open = *is_completed {
+            *is_completed = TransactionStatus::Rollback;
+
+            if self.session.is_poisoned() {
+                self.session_pool.give_back((*self.session).clone());
+            } else {
+                let prefetch = self.take_prefetch();
+                abort_in_background(&self.client, &self.session_pool, (*self.session).clone(), prefetch);
+            }
+
+            self.drop_guard.release_permit();
+        }
+    }
+
+    /// Keeps the background task loading the pages of a cursor, so the
+    /// next command of the transaction waits for it.
+    pub(crate) fn set_prefetch(&self, prefetch: Prefetch) {
+        if let Ok(mut current) = self.prefetch.lock() {
+            *current = Some(prefetch);
+        }
+    }
+
+    /// Stops the prefetch of a cursor, if any, and waits for the page it
+    /// may be loading, so QLDB doesn't receive a command of this
+    /// transaction while another one is still running.
+    pub(crate) async fn stop_prefetch(&self) {
+        if let Some(prefetch) = self.take_prefetch() {
+            prefetch.stop().await;
+        }
+    }
+
+    fn take_prefetch(&self) -> Option<Prefetch> {
+        self.prefetch.lock().ok().and_then(|mut prefetch| prefetch.take())
+    }
+
+    pub(crate) fn spawn(&self, task: BackgroundTask) {
+        self.session_pool.spawn(task);
+    }
+
+    pub(crate) async fn add_stats(&self, stats: QueryStats) {
+        *self.stats.lock().await += stats;
+    }
+
     pub(crate) async fn is_completed(&self) -> bool {
         use TransactionStatus::*;
 
@@ -154,23 +542,32 @@ impl Transaction {
     fn complete(&self, mut is_completed: MutexGuard<'_, TransactionStatus>, status: TransactionStatus) {
         *is_completed = status;
         self.session_pool.give_back((*self.session).clone());
+        self.drop_guard.release_permit();
+
+        // Rolled back transactions count too, read-only traffic usually
+        // ends with a rollback
+        if !self.throttled.load(Relaxed) {
+            self.limiter.record_success();
+        }
     }
 
-    pub(crate) async fn hash_query(&self, statement: &str, params: &[IonValue]) {
+    pub(crate) async fn hash_query(&self, statement: &str, params: &[Param]) {
         let mut hasher = IonHash::from_ion_value::<Sha256>(&IonValue::String(statement.to_string()));
 
         for param in params {
-            let mut encoder = IonEncoder::new();
-            encoder.add(param.clone());
-
-            hasher.add_ion_value(param);
+            hasher.add_ion_value(&param.value);
         }
 
         self.hasher.lock().await.dot(hasher);
     }
 
-    async fn get_transaction_id(client: &Arc<QldbSessionClient>, session: &str) -> QldbResult<String> {
-        let response = client.send_command(create_start_transaction_command(session)).await?;
+    async fn get_transaction_id(client: &Arc<dyn QldbTransport>, session: &Session) -> QldbResult<String> {
+        let response = send_command(
+            client,
+            session,
+            create_start_transaction_command(session.get_session_id()),
+        )
+        .await?;
 
         let token = match response.start_transaction {
             Some(session) => match session.transaction_id {
@@ -194,12 +591,59 @@ impl Debug for Transaction {
     }
 }
 
+/// QLDB returns the digest it calculated for the statements it received.
+/// If it differs from ours, what QLDB executed isn't what we sent.
+fn check_commit_digest(expected: Vec<u8>, actual: Vec<u8>) -> QldbResult<()> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(QldbError::CommitDigestMismatch { expected, actual })
+    }
+}
+
 fn create_commit_command(session: &str, transaction_id: &str, commit_digest: &[u8]) -> SendCommandRequest {
     SendCommandRequest {
         session_token: Some(session.to_string()),
         commit_transaction: Some(CommitTransactionRequest {
             transaction_id: transaction_id.to_string(),
-            commit_digest: commit_digest.to_owned().into(),
+            commit_digest: commit_digest.to_owned(),
+        }),
+        ..Default::default()
+    }
+}
+
+async fn send_command(
+    client: &Arc<dyn QldbTransport>,
+    session: &Session,
+    request: SendCommandRequest,
+) -> QldbResult<SendCommandResult> {
+    client.send_command(request).await.map_err(|error| {
+        if error.invalidates_session() {
+            session.poison();
+        }
+
+        QldbError::SendCommandError(error)
+    })
+}
+
+fn command_phase(request: &SendCommandRequest) -> TimeoutPhase {
+    if request.fetch_page.is_some() {
+        TimeoutPhase::FetchPage
+    } else if request.commit_transaction.is_some() {
+        TimeoutPhase::Commit
+    } else if request.start_transaction.is_some() {
+        TimeoutPhase::Acquire
+    } else {
+        TimeoutPhase::Statement
+    }
+}
+
+fn create_next_page_command(session: &str, transaction_id: &str, next_page_token: &str) -> SendCommandRequest {
+    SendCommandRequest {
+        session_token: Some(session.to_string()),
+        fetch_page: Some(FetchPageRequest {
+            transaction_id: transaction_id.to_string(),
+            next_page_token: next_page_token.to_string(),
         }),
         ..Default::default()
     }
@@ -220,3 +664,31 @@ fn create_start_transaction_command(session: &str) -> SendCommandRequest {
         ..Default::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_digest_matches() {
+        assert!(check_commit_digest(vec![1, 2, 3], vec![1, 2, 3]).is_ok());
+    }
+
+    #[test]
+    fn commit_digest_mismatch() {
+        let error = check_commit_digest(vec![1, 2, 3], vec![1, 2, 4]).unwrap_err();
+
+        match error {
+            QldbError::CommitDigestMismatch { expected, actual } => {
+                assert_eq!(expected, vec![1, 2, 3]);
+                assert_eq!(actual, vec![1, 2, 4]);
+            }
+            error => panic!("Unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn empty_commit_digest_is_a_mismatch() {
+        assert!(check_commit_digest(vec![1, 2, 3], vec![]).is_err());
+    }
+}
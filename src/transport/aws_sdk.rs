@@ -0,0 +1,200 @@
+use crate::transport::{
+    AbortTransactionResult, CommitTransactionResult, EndSessionResult, ExecuteStatementResult, FetchPageResult,
+    IoUsage, Page, QldbTransport, SendCommandRequest, SendCommandResult, ServiceError, StartSessionResult,
+    StartTransactionResult, TimingInformation, TransportError, ValueHolder,
+};
+use aws_sdk_qldbsession::error::{BuildError, DisplayErrorContext, SdkError};
+use aws_sdk_qldbsession::operation::send_command::{SendCommandError, SendCommandOutput};
+use aws_sdk_qldbsession::operation::RequestId;
+use aws_sdk_qldbsession::primitives::Blob;
+use aws_sdk_qldbsession::{types, Client};
+
+/// [QldbTransport] backed by the official
+/// [aws-sdk-qldbsession](https://crates.io/crates/aws-sdk-qldbsession)
+/// client.
+///
+/// The client is created by you, usually from
+/// [aws-config](https://crates.io/crates/aws-config), so the region,
+/// credentials, retries and HTTP client are the ones configured there.
+#[derive(Clone, Debug)]
+pub struct AwsSdkTransport {
+    client: Client,
+}
+
+impl AwsSdkTransport {
+    pub fn new(client: Client) -> AwsSdkTransport {
+        AwsSdkTransport { client }
+    }
+}
+
+#[async_trait::async_trait]
+impl QldbTransport for AwsSdkTransport {
+    async fn send_command(&self, request: SendCommandRequest) -> Result<SendCommandResult, TransportError> {
+        let mut command = self.client.send_command().set_session_token(request.session_token);
+
+        if let Some(start) = request.start_session {
+            command = command.start_session(
+                types::StartSessionRequest::builder()
+                    .ledger_name(start.ledger_name)
+                    .build()
+                    .map_err(from_build_error)?,
+            );
+        }
+
+        if request.start_transaction.is_some() {
+            command = command.start_transaction(types::StartTransactionRequest::builder().build());
+        }
+
+        if request.end_session.is_some() {
+            command = command.end_session(types::EndSessionRequest::builder().build());
+        }
+
+        if let Some(commit) = request.commit_transaction {
+            command = command.commit_transaction(
+                types::CommitTransactionRequest::builder()
+                    .transaction_id(commit.transaction_id)
+                    .commit_digest(Blob::new(commit.commit_digest))
+                    .build()
+                    .map_err(from_build_error)?,
+            );
+        }
+
+        if request.abort_transaction.is_some() {
+            command = command.abort_transaction(types::AbortTransactionRequest::builder().build());
+        }
+
+        if let Some(execute) = request.execute_statement {
+            let parameters = execute
+                .parameters
+                .unwrap_or_default()
+                .into_iter()
+                .map(to_sdk_value_holder)
+                .collect();
+
+            command = command.execute_statement(
+                types::ExecuteStatementRequest::builder()
+                    .transaction_id(execute.transaction_id)
+                    .statement(execute.statement)
+                    .set_parameters(Some(parameters))
+                    .build()
+                    .map_err(from_build_error)?,
+            );
+        }
+
+        if let Some(fetch) = request.fetch_page {
+            command = command.fetch_page(
+                types::FetchPageRequest::builder()
+                    .transaction_id(fetch.transaction_id)
+                    .next_page_token(fetch.next_page_token)
+                    .build()
+                    .map_err(from_build_error)?,
+            );
+        }
+
+        let output = command.send().await.map_err(from_sdk_error)?;
+
+        Ok(from_sdk_output(output))
+    }
+}
+
+fn to_sdk_value_holder(value: ValueHolder) -> types::ValueHolder {
+    types::ValueHolder::builder()
+        .set_ion_binary(value.ion_binary.map(Blob::new))
+        .set_ion_text(value.ion_text)
+        .build()
+}
+
+fn from_sdk_output(output: SendCommandOutput) -> SendCommandResult {
+    SendCommandResult {
+        start_session: output.start_session().map(|start| StartSessionResult {
+            session_token: start.session_token().map(str::to_string),
+        }),
+        start_transaction: output.start_transaction().map(|start| StartTransactionResult {
+            transaction_id: start.transaction_id().map(str::to_string),
+        }),
+        end_session: output.end_session().map(|_| EndSessionResult {}),
+        commit_transaction: output.commit_transaction().map(|commit| CommitTransactionResult {
+            transaction_id: commit.transaction_id().map(str::to_string),
+            commit_digest: commit.commit_digest().map(|digest| digest.as_ref().to_vec()),
+            consumed_ios: commit.consumed_ios().map(from_sdk_io_usage),
+            timing_information: commit.timing_information().map(from_sdk_timing_information),
+        }),
+        abort_transaction: output.abort_transaction().map(|_| AbortTransactionResult {}),
+        execute_statement: output.execute_statement().map(|execute| ExecuteStatementResult {
+            first_page: execute.first_page().map(from_sdk_page),
+            consumed_ios: execute.consumed_ios().map(from_sdk_io_usage),
+            timing_information: execute.timing_information().map(from_sdk_timing_information),
+        }),
+        fetch_page: output.fetch_page().map(|fetch| FetchPageResult {
+            page: fetch.page().map(from_sdk_page),
+            consumed_ios: fetch.consumed_ios().map(from_sdk_io_usage),
+            timing_information: fetch.timing_information().map(from_sdk_timing_information),
+        }),
+    }
+}
+
+fn from_sdk_page(page: &types::Page) -> Page {
+    Page {
+        values: Some(
+            page.values()
+                .iter()
+                .map(|value| ValueHolder {
+                    ion_binary: value.ion_binary().map(|bytes| bytes.as_ref().to_vec()),
+                    ion_text: value.ion_text().map(str::to_string),
+                })
+                .collect(),
+        ),
+        next_page_token: page.next_page_token().map(str::to_string),
+    }
+}
+
+fn from_sdk_io_usage(usage: &types::IoUsage) -> IoUsage {
+    IoUsage {
+        read_ios: Some(usage.read_ios()),
+        write_ios: Some(usage.write_ios()),
+    }
+}
+
+fn from_sdk_timing_information(timing: &types::TimingInformation) -> TimingInformation {
+    TimingInformation {
+        processing_time_milliseconds: Some(timing.processing_time_milliseconds()),
+    }
+}
+
+fn from_build_error(error: BuildError) -> TransportError {
+    TransportError::Validation(error.to_string())
+}
+
+fn from_sdk_error(error: SdkError<SendCommandError>) -> TransportError {
+    let description = DisplayErrorContext(&error).to_string();
+
+    match error {
+        SdkError::ServiceError(context) => {
+            let status = context.raw().status().as_u16();
+            let error = context.into_err();
+            let message = error.meta().message().unwrap_or_default().to_string();
+            let request_id = error.request_id().map(str::to_string);
+
+            let error = match error {
+                SendCommandError::BadRequestException(_) => ServiceError::BadRequest(message),
+                SendCommandError::CapacityExceededException(_) => ServiceError::CapacityExceeded(message),
+                SendCommandError::InvalidSessionException(_) => ServiceError::InvalidSession(message),
+                SendCommandError::LimitExceededException(_) => ServiceError::LimitExceeded(message),
+                SendCommandError::OccConflictException(_) => ServiceError::OccConflict(message),
+                SendCommandError::RateExceededException(_) => ServiceError::RateExceeded(message),
+                _ => {
+                    return TransportError::Unknown {
+                        status,
+                        body: description,
+                        request_id,
+                    }
+                }
+            };
+
+            TransportError::Service { error, request_id }
+        }
+        SdkError::ConstructionFailure(_) => TransportError::Validation(description),
+        SdkError::ResponseError(_) => TransportError::Parse(description),
+        _ => TransportError::Dispatch(description),
+    }
+}
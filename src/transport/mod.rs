@@ -0,0 +1,242 @@
+//! Abstraction over the SendCommand API of QLDB.
+//!
+//! The whole driver talks to QLDB through the [QldbTransport] trait, so
+//! it doesn't depend on any specific AWS SDK. The crate ships two
+//! implementations:
+//!
+//!  - [RusotoTransport], behind the `rusoto` feature (enabled by default).
+//!  - [AwsSdkTransport], behind the `aws-sdk` feature, which uses the
+//!    official [aws-sdk-qldbsession](https://crates.io/crates/aws-sdk-qldbsession).
+//!
+//! Any other implementation can be given to
+//! [QldbClientBuilder::transport](crate::QldbClientBuilder::transport),
+//! for example a fake one for tests.
+
+#[cfg(feature = "aws-sdk")]
+mod aws_sdk;
+#[cfg(feature = "rusoto")]
+mod rusoto;
+
+#[cfg(feature = "aws-sdk")]
+pub use aws_sdk::AwsSdkTransport;
+#[cfg(feature = "rusoto")]
+pub use rusoto::RusotoTransport;
+
+use thiserror::Error;
+
+/// Sends commands to QLDB. Every command is one call to the
+/// [SendCommand](https://docs.aws.amazon.com/qldb/latest/developerguide/API_QLDB-Session_SendCommand.html)
+/// API.
+#[async_trait::async_trait]
+pub trait QldbTransport: Send + Sync {
+    async fn send_command(&self, request: SendCommandRequest) -> Result<SendCommandResult, TransportError>;
+}
+
+/// Error returned by a [QldbTransport].
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error("QLDB returned an error: {error}")]
+    Service {
+        error: ServiceError,
+        // Given by AWS, when the transport knows it
+        request_id: Option<String>,
+    },
+    #[error("Error sending the command to QLDB: {0}")]
+    Dispatch(String),
+    #[error("Error getting the AWS credentials: {0}")]
+    Credentials(String),
+    #[error("The command is not valid: {0}")]
+    Validation(String),
+    #[error("The QLDB response cannot be parsed: {0}")]
+    Parse(String),
+    #[error("QLDB returned an unexpected response with status {status}: {body}")]
+    Unknown {
+        status: u16,
+        body: String,
+        request_id: Option<String>,
+    },
+}
+
+impl TransportError {
+    /// Id given by AWS to the request, useful when contacting AWS
+    /// support. Not every transport provides it.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            TransportError::Service { request_id, .. } | TransportError::Unknown { request_id, .. } => {
+                request_id.as_deref()
+            }
+            _ => None,
+        }
+    }
+
+    /// QLDB doesn't accept more commands in the session, for example
+    /// because it expired or because its transaction was aborted.
+    pub(crate) fn invalidates_session(&self) -> bool {
+        matches!(
+            self,
+            TransportError::Service {
+                error: ServiceError::InvalidSession(_),
+                ..
+            }
+        )
+    }
+}
+
+impl From<ServiceError> for TransportError {
+    fn from(error: ServiceError) -> TransportError {
+        TransportError::Service {
+            error,
+            request_id: None,
+        }
+    }
+}
+
+/// Errors defined by the QLDB Session API.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ServiceError {
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("Capacity exceeded: {0}")]
+    CapacityExceeded(String),
+    #[error("Invalid session: {0}")]
+    InvalidSession(String),
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+    #[error("OCC conflict: {0}")]
+    OccConflict(String),
+    #[error("Rate exceeded: {0}")]
+    RateExceeded(String),
+}
+
+impl ServiceError {
+    /// Message returned by QLDB.
+    pub fn message(&self) -> &str {
+        match self {
+            ServiceError::BadRequest(message)
+            | ServiceError::CapacityExceeded(message)
+            | ServiceError::InvalidSession(message)
+            | ServiceError::LimitExceeded(message)
+            | ServiceError::OccConflict(message)
+            | ServiceError::RateExceeded(message) => message,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SendCommandRequest {
+    pub session_token: Option<String>,
+    pub start_session: Option<StartSessionRequest>,
+    pub start_transaction: Option<StartTransactionRequest>,
+    pub end_session: Option<EndSessionRequest>,
+    pub commit_transaction: Option<CommitTransactionRequest>,
+    pub abort_transaction: Option<AbortTransactionRequest>,
+    pub execute_statement: Option<ExecuteStatementRequest>,
+    pub fetch_page: Option<FetchPageRequest>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SendCommandResult {
+    pub start_session: Option<StartSessionResult>,
+    pub start_transaction: Option<StartTransactionResult>,
+    pub end_session: Option<EndSessionResult>,
+    pub commit_transaction: Option<CommitTransactionResult>,
+    pub abort_transaction: Option<AbortTransactionResult>,
+    pub execute_statement: Option<ExecuteStatementResult>,
+    pub fetch_page: Option<FetchPageResult>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StartSessionRequest {
+    pub ledger_name: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StartTransactionRequest {}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EndSessionRequest {}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommitTransactionRequest {
+    pub transaction_id: String,
+    pub commit_digest: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AbortTransactionRequest {}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExecuteStatementRequest {
+    pub transaction_id: String,
+    pub statement: String,
+    pub parameters: Option<Vec<ValueHolder>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FetchPageRequest {
+    pub transaction_id: String,
+    pub next_page_token: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StartSessionResult {
+    pub session_token: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StartTransactionResult {
+    pub transaction_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EndSessionResult {}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommitTransactionResult {
+    pub transaction_id: Option<String>,
+    pub commit_digest: Option<Vec<u8>>,
+    pub consumed_ios: Option<IoUsage>,
+    pub timing_information: Option<TimingInformation>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AbortTransactionResult {}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExecuteStatementResult {
+    pub first_page: Option<Page>,
+    pub consumed_ios: Option<IoUsage>,
+    pub timing_information: Option<TimingInformation>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FetchPageResult {
+    pub page: Option<Page>,
+    pub consumed_ios: Option<IoUsage>,
+    pub timing_information: Option<TimingInformation>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Page {
+    pub values: Option<Vec<ValueHolder>>,
+    pub next_page_token: Option<String>,
+}
+
+/// A value in Ion format. The driver always sends and reads
+/// `ion_binary`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValueHolder {
+    pub ion_binary: Option<Vec<u8>>,
+    pub ion_text: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IoUsage {
+    pub read_ios: Option<i64>,
+    pub write_ios: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TimingInformation {
+    pub processing_time_milliseconds: Option<i64>,
+}
@@ -0,0 +1,233 @@
+use crate::transport::{AbortTransactionResult, EndSessionResult};
+use crate::transport::{
+    CommitTransactionResult, ExecuteStatementResult, FetchPageResult, IoUsage, Page, QldbTransport, SendCommandRequest,
+    SendCommandResult, ServiceError, StartSessionResult, StartTransactionResult, TimingInformation, TransportError,
+    ValueHolder,
+};
+use async_compat::CompatExt;
+use rusoto_core::request::BufferedHttpResponse;
+use rusoto_core::signature::SignedRequest;
+use rusoto_core::{proto, Client, Region, RusotoError};
+use rusoto_qldb_session::{QldbSession, QldbSessionClient, SendCommandError};
+
+/// [QldbTransport] backed by rusoto.
+///
+/// Rusoto needs a tokio reactor, so every command is run inside
+/// [async-compat](https://crates.io/crates/async-compat) in order to
+/// work from any executor.
+#[derive(Clone)]
+pub struct RusotoTransport {
+    client: RusotoClient,
+}
+
+#[derive(Clone)]
+enum RusotoClient {
+    Session(QldbSessionClient),
+    Core { client: Client, region: Region },
+}
+
+impl RusotoTransport {
+    /// Sends the commands with a `QldbSessionClient`. It doesn't keep
+    /// the request id of the errors returned by QLDB, use `with_client`
+    /// if you need it.
+    pub fn new(client: QldbSessionClient) -> RusotoTransport {
+        RusotoTransport {
+            client: RusotoClient::Session(client),
+        }
+    }
+
+    /// Sends the commands with a rusoto `Client`, which signs them with
+    /// its credentials, to the QLDB endpoint of `region`. The errors
+    /// returned by QLDB keep the id of the request.
+    pub fn with_client(client: Client, region: Region) -> RusotoTransport {
+        RusotoTransport {
+            client: RusotoClient::Core { client, region },
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl QldbTransport for RusotoTransport {
+    async fn send_command(&self, request: SendCommandRequest) -> Result<SendCommandResult, TransportError> {
+        let request = to_rusoto_request(request);
+
+        let result = match &self.client {
+            RusotoClient::Session(client) => client.send_command(request).compat().await.map_err(from_rusoto_error)?,
+            RusotoClient::Core { client, region } => send_command(client, region, request).compat().await?,
+        };
+
+        Ok(from_rusoto_result(result))
+    }
+}
+
+/// Same as `QldbSessionClient::send_command`, but reading the request id
+/// of the response before rusoto parses the error.
+async fn send_command(
+    client: &Client,
+    region: &Region,
+    input: rusoto_qldb_session::SendCommandRequest,
+) -> Result<rusoto_qldb_session::SendCommandResult, TransportError> {
+    let mut request = SignedRequest::new("POST", "qldb", region, "/");
+    request.set_endpoint_prefix("session.qldb".to_string());
+    request.set_content_type("application/x-amz-json-1.0".to_owned());
+    request.add_header("x-amz-target", "QLDBSession.SendCommand");
+
+    let payload = serde_json::to_string(&input).map_err(|error| TransportError::Validation(error.to_string()))?;
+    request.set_payload(Some(payload));
+
+    let mut response = client
+        .sign_and_dispatch(request)
+        .await
+        .map_err(|error| from_rusoto_error(error.into()))?;
+
+    let response = response
+        .buffer()
+        .await
+        .map_err(|error| TransportError::Dispatch(error.to_string()))?;
+
+    if !response.status.is_success() {
+        let request_id = request_id(&response);
+
+        return Err(match from_rusoto_error(SendCommandError::from_response(response)) {
+            TransportError::Service { error, .. } => TransportError::Service { error, request_id },
+            error => error,
+        });
+    }
+
+    proto::json::ResponsePayload::new(&response)
+        .deserialize::<_, SendCommandError>()
+        .map_err(from_rusoto_error)
+}
+
+fn request_id(response: &BufferedHttpResponse) -> Option<String> {
+    response
+        .headers
+        .get("x-amzn-requestid")
+        .map(|request_id| request_id.to_string())
+}
+
+fn to_rusoto_request(request: SendCommandRequest) -> rusoto_qldb_session::SendCommandRequest {
+    rusoto_qldb_session::SendCommandRequest {
+        session_token: request.session_token,
+        start_session: request
+            .start_session
+            .map(|start| rusoto_qldb_session::StartSessionRequest {
+                ledger_name: start.ledger_name,
+            }),
+        start_transaction: request
+            .start_transaction
+            .map(|_| rusoto_qldb_session::StartTransactionRequest {}),
+        end_session: request.end_session.map(|_| rusoto_qldb_session::EndSessionRequest {}),
+        commit_transaction: request
+            .commit_transaction
+            .map(|commit| rusoto_qldb_session::CommitTransactionRequest {
+                transaction_id: commit.transaction_id,
+                commit_digest: commit.commit_digest.into(),
+            }),
+        abort_transaction: request
+            .abort_transaction
+            .map(|_| rusoto_qldb_session::AbortTransactionRequest {}),
+        execute_statement: request
+            .execute_statement
+            .map(|execute| rusoto_qldb_session::ExecuteStatementRequest {
+                transaction_id: execute.transaction_id,
+                statement: execute.statement,
+                parameters: execute
+                    .parameters
+                    .map(|parameters| parameters.into_iter().map(to_rusoto_value_holder).collect()),
+            }),
+        fetch_page: request.fetch_page.map(|fetch| rusoto_qldb_session::FetchPageRequest {
+            transaction_id: fetch.transaction_id,
+            next_page_token: fetch.next_page_token,
+        }),
+    }
+}
+
+fn to_rusoto_value_holder(value: ValueHolder) -> rusoto_qldb_session::ValueHolder {
+    rusoto_qldb_session::ValueHolder {
+        ion_binary: value.ion_binary.map(Into::into),
+        ion_text: value.ion_text,
+    }
+}
+
+fn from_rusoto_result(result: rusoto_qldb_session::SendCommandResult) -> SendCommandResult {
+    SendCommandResult {
+        start_session: result.start_session.map(|start| StartSessionResult {
+            session_token: start.session_token,
+        }),
+        start_transaction: result.start_transaction.map(|start| StartTransactionResult {
+            transaction_id: start.transaction_id,
+        }),
+        end_session: result.end_session.map(|_| EndSessionResult {}),
+        commit_transaction: result.commit_transaction.map(|commit| CommitTransactionResult {
+            transaction_id: commit.transaction_id,
+            commit_digest: commit.commit_digest.map(|digest| digest.to_vec()),
+            consumed_ios: commit.consumed_i_os.map(from_rusoto_io_usage),
+            timing_information: commit.timing_information.map(from_rusoto_timing_information),
+        }),
+        abort_transaction: result.abort_transaction.map(|_| AbortTransactionResult {}),
+        execute_statement: result.execute_statement.map(|execute| ExecuteStatementResult {
+            first_page: execute.first_page.map(from_rusoto_page),
+            consumed_ios: execute.consumed_i_os.map(from_rusoto_io_usage),
+            timing_information: execute.timing_information.map(from_rusoto_timing_information),
+        }),
+        fetch_page: result.fetch_page.map(|fetch| FetchPageResult {
+            page: fetch.page.map(from_rusoto_page),
+            consumed_ios: fetch.consumed_i_os.map(from_rusoto_io_usage),
+            timing_information: fetch.timing_information.map(from_rusoto_timing_information),
+        }),
+    }
+}
+
+fn from_rusoto_page(page: rusoto_qldb_session::Page) -> Page {
+    Page {
+        values: page.values.map(|values| {
+            values
+                .into_iter()
+                .map(|value| ValueHolder {
+                    ion_binary: value.ion_binary.map(|bytes| bytes.to_vec()),
+                    ion_text: value.ion_text,
+                })
+                .collect()
+        }),
+        next_page_token: page.next_page_token,
+    }
+}
+
+fn from_rusoto_io_usage(usage: rusoto_qldb_session::IOUsage) -> IoUsage {
+    IoUsage {
+        read_ios: usage.read_i_os,
+        write_ios: usage.write_i_os,
+    }
+}
+
+fn from_rusoto_timing_information(timing: rusoto_qldb_session::TimingInformation) -> TimingInformation {
+    TimingInformation {
+        processing_time_milliseconds: timing.processing_time_milliseconds,
+    }
+}
+
+fn from_rusoto_error(error: RusotoError<SendCommandError>) -> TransportError {
+    match error {
+        // Rusoto doesn't keep the request id of service errors, the
+        // callers that have the response add it
+        RusotoError::Service(error) => TransportError::from(match error {
+            SendCommandError::BadRequest(message) => ServiceError::BadRequest(message),
+            SendCommandError::CapacityExceeded(message) => ServiceError::CapacityExceeded(message),
+            SendCommandError::InvalidSession(message) => ServiceError::InvalidSession(message),
+            SendCommandError::LimitExceeded(message) => ServiceError::LimitExceeded(message),
+            SendCommandError::OccConflict(message) => ServiceError::OccConflict(message),
+            SendCommandError::RateExceeded(message) => ServiceError::RateExceeded(message),
+        }),
+        RusotoError::HttpDispatch(error) => TransportError::Dispatch(error.to_string()),
+        RusotoError::Credentials(error) => TransportError::Credentials(error.to_string()),
+        RusotoError::Validation(message) => TransportError::Validation(message),
+        RusotoError::ParseError(message) => TransportError::Parse(message),
+        RusotoError::Unknown(response) => TransportError::Unknown {
+            status: response.status.as_u16(),
+            body: response.body_as_str().to_string(),
+            request_id: request_id(&response),
+        },
+        RusotoError::Blocking => TransportError::Dispatch("Blocking error".to_string()),
+    }
+}
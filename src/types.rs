@@ -1,19 +1,23 @@
+use crate::transport::{ServiceError, TransportError};
 use eyre::Report;
 use ion_binary_rs::IonParserError;
-use rusoto_core::{request::TlsError, RusotoError};
-use rusoto_qldb_session::SendCommandError;
+#[cfg(feature = "rusoto")]
+use rusoto_core::request::TlsError;
+use std::fmt;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum QldbError {
-    #[error("The QLDB command returned an error")]
-    SendCommandError(#[from] RusotoError<SendCommandError>),
+    #[error("The QLDB command returned an error: {0}")]
+    SendCommandError(#[from] TransportError),
     #[error("We requested a session but QLDB returned nothing")]
     QldbReturnedEmptySession,
     #[error("We requested a transaction id but QLDB returned nothing")]
     QldbReturnedEmptyTransaction,
-    #[error("We requested a transaction id but QLDB returned nothing")]
+    #[error("Cannot parse the Ion values returned by QLDB: {0:?}")]
     IonParserError(#[from] IonParserError),
+    #[cfg(feature = "rusoto")]
     #[error("Error when creating the HttpClient")]
     TlsError(#[from] TlsError),
     #[error("Transaction has been already commit or rollback")]
@@ -30,10 +34,244 @@ pub enum QldbError {
     TransactionAlreadyRollback,
     #[error("The query was already executed. Trying to get a Cursor or executing it again will fail.")]
     QueryAlreadyExecuted,
-    #[error("Error extranting the QLDB returned Ion values to the requested type.")]
+    #[error("Error extracting the QLDB returned Ion values to the requested type: {0}")]
     QldbExtractError(#[from] QldbExtractError),
     #[error("Cannot get session from session pool. This means that the session pool was closed by calling the `.close()` method.")]
     SessionPoolClosed(Report),
+    #[error("QLDB couldn't start a new session for the session pool: {0}")]
+    SessionStartError(Report),
+    #[error("No session became available in the session pool after waiting {0:?}")]
+    SessionAcquireTimeout(Duration),
+    #[error("There are already {0} transactions waiting for a session in the session pool")]
+    PoolExhausted(usize),
+    #[error("No transport was provided to the QldbClientBuilder and the `rusoto` feature is disabled")]
+    MissingTransport,
+    #[error("The commit digest returned by QLDB doesn't match the one calculated by the driver. Expected {expected:02x?}, got {actual:02x?}")]
+    CommitDigestMismatch { expected: Vec<u8>, actual: Vec<u8> },
+    #[error("The transaction failed after {attempts} attempts. Last error: {source}")]
+    TransactionRetriesExhausted {
+        attempts: u32,
+        #[source]
+        source: Box<QldbError>,
+    },
+    #[error("Cannot serialize the query parameter, field '{path}': {message}")]
+    ParamSerializationError { path: String, message: String },
+    #[error("The deadline expired after {elapsed:?}, during the {phase}")]
+    Timeout { elapsed: Duration, phase: TimeoutPhase },
+    #[error("The statement uses the parameter :{0} but no value was given for it")]
+    MissingNamedParam(String),
+    #[error("The parameter :{0} was given but the statement doesn't use it")]
+    UnusedNamedParam(String),
+    #[error("The parameter :{0} was given more than once")]
+    DuplicatedNamedParam(String),
+    #[error("Positional (?) and named (:name) parameters cannot be mixed in the same statement")]
+    MixedParams,
+    #[error("The statement returned {got} documents instead of {expected}")]
+    UnexpectedRowCount { expected: usize, got: usize },
+    #[error("The bulk load stopped after loading {loaded_documents} documents. Error: {source}")]
+    BulkLoadInterrupted {
+        loaded_documents: usize,
+        #[source]
+        source: Box<QldbError>,
+    },
+    #[error("'{0}' is not a valid table name. It can only have letters, digits and underscores and cannot start with a digit")]
+    InvalidTableName(String),
+    #[error("The {documents} documents ({bytes} bytes) go over the BulkLimits of one transaction. QldbClient::bulk_load splits them in several transactions")]
+    BulkLimitsExceeded { documents: usize, bytes: usize },
+}
+
+/// What the transaction was doing when its deadline expired. See
+/// `QldbError::Timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    /// Waiting for a session or starting the transaction.
+    Acquire,
+    /// Executing a statement.
+    Statement,
+    /// Loading the next page of a statement.
+    FetchPage,
+    /// Committing the transaction. It may have been committed anyway.
+    Commit,
+}
+
+impl fmt::Display for TimeoutPhase {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self {
+            TimeoutPhase::Acquire => "session acquisition",
+            TimeoutPhase::Statement => "statement execution",
+            TimeoutPhase::FetchPage => "page fetch",
+            TimeoutPhase::Commit => "commit",
+        };
+
+        formatter.write_str(phase)
+    }
+}
+
+/// Classification of a `QldbError`, given by `QldbError::kind`. It
+/// doesn't depend on the transport used, so it is what callers should
+/// match on.
+///
+/// ```rust,no_run
+/// use qldb::{QldbClient, QldbErrorKind, QldbResult};
+///
+/// # async fn test(client: QldbClient) -> QldbResult<()> {
+/// let result = client
+///     .transaction_within(|tx| async move {
+///         tx.query("UPDATE Cars SET sold = true").execute().await?;
+///         Ok(())
+///     })
+///     .await;
+///
+/// if let Err(error) = result {
+///     match error.kind() {
+///         QldbErrorKind::OccConflict => println!("Someone else changed the cars"),
+///         _ => println!("Request {:?} failed: {}", error.request_id(), error),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum QldbErrorKind {
+    /// Another transaction changed the same data.
+    OccConflict,
+    /// The session expired or QLDB closed it.
+    InvalidSession,
+    /// Too many requests to QLDB.
+    RateExceeded,
+    /// A QLDB limit was reached, like the amount of sessions.
+    LimitExceeded,
+    /// QLDB, or the session pool of the driver, cannot take more work
+    /// right now.
+    CapacityExceeded,
+    /// QLDB rejected the command, for example because of a syntax error
+    /// in the statement.
+    BadRequest,
+    /// The command couldn't be sent or its response couldn't be read.
+    Transport,
+    /// The AWS credentials couldn't be loaded.
+    Credentials,
+    /// QLDB returned something unexpected, like a wrong commit digest.
+    Integrity,
+    /// A deadline of the driver expired.
+    Timeout,
+    /// The driver was used in a wrong way, like committing a transaction
+    /// already rolled back.
+    Usage,
+}
+
+impl QldbError {
+    /// Classification of the error.
+    pub fn kind(&self) -> QldbErrorKind {
+        match self {
+            QldbError::SendCommandError(error) => transport_error_kind(error),
+            QldbError::SessionStartError(report) => match report.downcast_ref::<TransportError>() {
+                Some(error) => transport_error_kind(error),
+                None => QldbErrorKind::Integrity,
+            },
+            QldbError::TransactionRetriesExhausted { source, .. } | QldbError::BulkLoadInterrupted { source, .. } => {
+                source.kind()
+            }
+            QldbError::QldbReturnedEmptySession
+            | QldbError::QldbReturnedEmptyTransaction
+            | QldbError::IonParserError(_)
+            | QldbError::InternalChannelSendError
+            | QldbError::CommitDigestMismatch { .. } => QldbErrorKind::Integrity,
+            #[cfg(feature = "rusoto")]
+            QldbError::TlsError(_) => QldbErrorKind::Transport,
+            QldbError::SessionAcquireTimeout(_) | QldbError::Timeout { .. } => QldbErrorKind::Timeout,
+            QldbError::PoolExhausted(_) => QldbErrorKind::CapacityExceeded,
+            QldbError::TransactionCompleted
+            | QldbError::NonValidCountStatementResult
+            | QldbError::TransactionAlreadyCommitted
+            | QldbError::TransactionAlreadyRollback
+            | QldbError::QueryAlreadyExecuted
+            | QldbError::QldbExtractError(_)
+            | QldbError::SessionPoolClosed(_)
+            | QldbError::MissingTransport
+            | QldbError::ParamSerializationError { .. }
+            | QldbError::MissingNamedParam(_)
+            | QldbError::UnusedNamedParam(_)
+            | QldbError::DuplicatedNamedParam(_)
+            | QldbError::MixedParams
+            | QldbError::UnexpectedRowCount { .. }
+            | QldbError::InvalidTableName(_)
+            | QldbError::BulkLimitsExceeded { .. } => QldbErrorKind::Usage,
+        }
+    }
+
+    /// Errors that are worth retrying with a new transaction: OCC
+    /// conflicts, expired sessions and transient transport failures.
+    /// They are the ones retried by `QldbClient::transaction_within_retry`.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            QldbError::SendCommandError(error) => transport_error_is_retryable(error),
+            QldbError::SessionStartError(report) => match report.downcast_ref::<TransportError>() {
+                Some(error) => transport_error_is_retryable(error),
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Id given by AWS to the failed request, when the transport
+    /// provides it.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            QldbError::SendCommandError(error) => error.request_id(),
+            QldbError::SessionStartError(report) => report.downcast_ref::<TransportError>()?.request_id(),
+            QldbError::TransactionRetriesExhausted { source, .. } | QldbError::BulkLoadInterrupted { source, .. } => {
+                source.request_id()
+            }
+            _ => None,
+        }
+    }
+
+    /// Message of the error returned by QLDB, if QLDB returned one.
+    pub fn service_message(&self) -> Option<&str> {
+        match self {
+            QldbError::SendCommandError(TransportError::Service { error, .. }) => Some(error.message()),
+            QldbError::SessionStartError(report) => match report.downcast_ref::<TransportError>()? {
+                TransportError::Service { error, .. } => Some(error.message()),
+                _ => None,
+            },
+            QldbError::TransactionRetriesExhausted { source, .. } | QldbError::BulkLoadInterrupted { source, .. } => {
+                source.service_message()
+            }
+            _ => None,
+        }
+    }
+}
+
+fn transport_error_kind(error: &TransportError) -> QldbErrorKind {
+    match error {
+        TransportError::Service { error, .. } => match error {
+            ServiceError::BadRequest(_) => QldbErrorKind::BadRequest,
+            ServiceError::CapacityExceeded(_) => QldbErrorKind::CapacityExceeded,
+            ServiceError::InvalidSession(_) => QldbErrorKind::InvalidSession,
+            ServiceError::LimitExceeded(_) => QldbErrorKind::LimitExceeded,
+            ServiceError::OccConflict(_) => QldbErrorKind::OccConflict,
+            ServiceError::RateExceeded(_) => QldbErrorKind::RateExceeded,
+        },
+        TransportError::Credentials(_) => QldbErrorKind::Credentials,
+        TransportError::Validation(_) => QldbErrorKind::Usage,
+        TransportError::Dispatch(_) | TransportError::Parse(_) | TransportError::Unknown { .. } => {
+            QldbErrorKind::Transport
+        }
+    }
+}
+
+fn transport_error_is_retryable(error: &TransportError) -> bool {
+    match error {
+        TransportError::Service { error, .. } => matches!(
+            error,
+            ServiceError::OccConflict(_) | ServiceError::InvalidSession(_) | ServiceError::CapacityExceeded(_)
+        ),
+        TransportError::Dispatch(_) => true,
+        TransportError::Unknown { status, .. } => (500..600).contains(status),
+        _ => false,
+    }
 }
 
 pub type QldbResult<T> = Result<T, QldbError>;
@@ -46,6 +284,8 @@ pub enum QldbExtractError {
     MissingProperty(String),
     #[error("Not a document. QLDB Documents must be an Ion::Struct, this is a: {0:?}")]
     NotADocument(ion_binary_rs::IonValue),
+    #[error("Cannot deserialize the field '{path}' of the QLDB Document: {message}")]
+    Deserialize { path: String, message: String },
 }
 
 pub type QldbExtractResult<T> = Result<T, QldbExtractError>;
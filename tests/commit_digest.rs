@@ -0,0 +1,121 @@
+use ion_binary_rs::{IonHash, IonParser, IonValue};
+use qldb::transport::{
+    CommitTransactionResult, ExecuteStatementResult, Page, SendCommandRequest, SendCommandResult, StartSessionResult,
+    StartTransactionResult, TransportError,
+};
+use qldb::{QldbClient, QldbError, QldbTransport};
+use sha2::Sha256;
+use std::sync::{Arc, Mutex};
+
+// Replies to the commands like QLDB would, calculating the commit
+// digest on its own from the statements it receives.
+struct DigestTransport {
+    corrupt_digest: bool,
+    hasher: Mutex<Option<IonHash>>,
+}
+
+impl DigestTransport {
+    fn new(corrupt_digest: bool) -> DigestTransport {
+        DigestTransport {
+            corrupt_digest,
+            hasher: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl QldbTransport for DigestTransport {
+    async fn send_command(&self, request: SendCommandRequest) -> Result<SendCommandResult, TransportError> {
+        let mut result = SendCommandResult::default();
+
+        if request.start_session.is_some() {
+            result.start_session = Some(StartSessionResult {
+                session_token: Some("session".to_string()),
+            });
+        }
+
+        if request.start_transaction.is_some() {
+            let transaction_id = "transaction".to_string();
+
+            *self.hasher.lock().unwrap() = Some(IonHash::from_ion_value::<Sha256>(&IonValue::String(
+                transaction_id.clone(),
+            )));
+
+            result.start_transaction = Some(StartTransactionResult {
+                transaction_id: Some(transaction_id),
+            });
+        }
+
+        if let Some(execute) = request.execute_statement {
+            let mut statement_hash = IonHash::from_ion_value::<Sha256>(&IonValue::String(execute.statement));
+
+            for parameter in execute.parameters.unwrap_or_default() {
+                let bytes = parameter.ion_binary.unwrap();
+                for value in IonParser::new(&bytes[..]).consume_all().unwrap() {
+                    statement_hash.add_ion_value(&value);
+                }
+            }
+
+            self.hasher.lock().unwrap().as_mut().unwrap().dot(statement_hash);
+
+            result.execute_statement = Some(ExecuteStatementResult {
+                first_page: Some(Page::default()),
+                ..Default::default()
+            });
+        }
+
+        if request.commit_transaction.is_some() {
+            let mut digest = self.hasher.lock().unwrap().take().unwrap().get().to_vec();
+
+            if self.corrupt_digest {
+                digest[0] = digest[0].wrapping_add(1);
+            }
+
+            result.commit_transaction = Some(CommitTransactionResult {
+                commit_digest: Some(digest),
+                ..Default::default()
+            });
+        }
+
+        Ok(result)
+    }
+}
+
+async fn insert_one(client: &QldbClient) -> Result<(), QldbError> {
+    client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Test VALUE ?")
+                .param(IonValue::String("value".to_string()))
+                .execute()
+                .await?;
+
+            Ok(())
+        })
+        .await
+}
+
+#[async_std::test]
+async fn commit_digest_matches() {
+    let client = QldbClient::builder("ledger")
+        .transport(Arc::new(DigestTransport::new(false)))
+        .build()
+        .unwrap();
+
+    insert_one(&client).await.unwrap();
+}
+
+#[async_std::test]
+async fn commit_digest_mismatch() {
+    let client = QldbClient::builder("ledger")
+        .transport(Arc::new(DigestTransport::new(true)))
+        .build()
+        .unwrap();
+
+    match insert_one(&client).await {
+        Err(QldbError::CommitDigestMismatch { expected, actual }) => {
+            assert_eq!(expected.len(), actual.len());
+            assert_ne!(expected, actual);
+        }
+        other => panic!("Expected a digest mismatch, got {:?}", other),
+    }
+}
@@ -1,6 +1,12 @@
-use ion_binary_rs::IonValue;
+#![allow(clippy::bool_assert_comparison, clippy::unnecessary_fold)]
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, TimeZone, Utc};
+use ion_binary_rs::{IonValue, NullIonValue};
 use qldb::{Document, DocumentCollection, QldbExtractError};
+use serde::Deserialize;
 use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
 
 fn get_qldb_struct() -> IonValue {
     IonValue::Struct(hashmap!(
@@ -64,6 +70,133 @@ fn check_document_collection() {
     assert_eq!(value, 2019 * 3);
 }
 
+#[derive(Debug, Deserialize, PartialEq)]
+struct Car {
+    #[serde(rename = "Model")]
+    model: String,
+    #[serde(rename = "Year")]
+    year: u16,
+    price: BigDecimal,
+    registered_on: DateTime<Utc>,
+    owner: Owner,
+    previous_owners: Vec<Owner>,
+    plate: Option<String>,
+    nickname: Option<String>,
+    photo: Vec<u8>,
+    fuel: Fuel,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Owner {
+    name: String,
+    zip_code: u32,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+enum Fuel {
+    Diesel,
+    Electric,
+}
+
+fn get_owner(name: &str, zip_code: IonValue) -> IonValue {
+    IonValue::Struct(hashmap!(
+        "name".to_string() => IonValue::String(name.to_string()),
+        "zip_code".to_string() => zip_code
+    ))
+}
+
+fn get_car_struct(previous_owner_zip_code: IonValue) -> IonValue {
+    let registered_on = Utc.with_ymd_and_hms(2019, 5, 17, 10, 30, 0).unwrap();
+
+    IonValue::Struct(hashmap!(
+        "Model".to_string() => IonValue::String("CLK 350".to_string()),
+        "Year".to_string() => IonValue::Integer(2019),
+        "price".to_string() => IonValue::Decimal(BigDecimal::from_str("35999.99").unwrap()),
+        "registered_on".to_string() => IonValue::DateTime(registered_on.into()),
+        "owner".to_string() => get_owner("Ana", IonValue::Integer(28001)),
+        "previous_owners".to_string() => IonValue::List(vec![
+            get_owner("Bob", IonValue::Integer(8001)),
+            get_owner("Carla", previous_owner_zip_code)
+        ]),
+        "plate".to_string() => IonValue::Null(NullIonValue::Null),
+        "photo".to_string() => IonValue::Blob(vec![1, 2, 3]),
+        "fuel".to_string() => IonValue::Symbol("Electric".to_string())
+    ))
+}
+
+#[test]
+fn deserialize_document() {
+    let document = Document::try_from(get_car_struct(IonValue::Integer(46001))).unwrap();
+
+    let car: Car = document.deserialize().unwrap();
+
+    assert_eq!(
+        car,
+        Car {
+            model: "CLK 350".to_string(),
+            year: 2019,
+            price: BigDecimal::from_str("35999.99").unwrap(),
+            registered_on: Utc.with_ymd_and_hms(2019, 5, 17, 10, 30, 0).unwrap(),
+            owner: Owner {
+                name: "Ana".to_string(),
+                zip_code: 28001,
+            },
+            previous_owners: vec![
+                Owner {
+                    name: "Bob".to_string(),
+                    zip_code: 8001,
+                },
+                Owner {
+                    name: "Carla".to_string(),
+                    zip_code: 46001,
+                },
+            ],
+            plate: None,
+            nickname: None,
+            photo: vec![1, 2, 3],
+            fuel: Fuel::Electric,
+        }
+    );
+}
+
+#[test]
+fn deserialize_document_error_path() {
+    let document = Document::try_from(get_car_struct(IonValue::String("46001".to_string()))).unwrap();
+
+    match document.deserialize::<Car>().unwrap_err() {
+        QldbExtractError::Deserialize { path, message } => {
+            assert_eq!(path, "previous_owners[1].zip_code");
+            assert!(message.contains("invalid type"), "{}", message);
+        }
+        error => panic!("Unexpected error {:?}", error),
+    }
+}
+
+#[test]
+fn deserialize_document_collection() {
+    let documents: DocumentCollection = vec![
+        get_car_struct(IonValue::Integer(46001)),
+        get_car_struct(IonValue::Integer(46002)),
+        get_car_struct(IonValue::Integer(-1)),
+    ]
+    .try_into()
+    .unwrap();
+
+    match documents.deserialize_all::<Car>().unwrap_err() {
+        QldbExtractError::Deserialize { path, .. } => assert_eq!(path, "[2].previous_owners[1].zip_code"),
+        error => panic!("Unexpected error {:?}", error),
+    }
+
+    let owners: Vec<Owner> = DocumentCollection::new(vec![
+        get_owner("Ana", IonValue::Integer(28001)).try_into().unwrap(),
+        get_owner("Bob", IonValue::Integer(8001)).try_into().unwrap(),
+    ])
+    .deserialize_all()
+    .unwrap();
+
+    assert_eq!(owners[1].name, "Bob");
+}
+
 #[macro_export]
 macro_rules! hashmap(
     { $($key:expr => $value:expr),+ } => {
@@ -0,0 +1,433 @@
+mod utils;
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, TimeZone, Utc};
+use eyre::Result;
+use ion_binary_rs::{IonValue, NullIonValue};
+use qldb::testing::FakeQldb;
+use qldb::{QldbError, QldbErrorKind};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use utils::fake::{car, create_client, create_table};
+
+#[async_std::test]
+async fn fake_insert_select_update_delete() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    client
+        .transaction_within(|tx| async move {
+            let inserted = tx
+                .query("INSERT INTO Cars << ?, ? >>")
+                .param(car("1111", "Ana"))
+                .param(car("2222", "Bob"))
+                .execute()
+                .await?;
+
+            assert_eq!(inserted.len(), 2);
+            assert!(inserted[0].get("documentId").is_some());
+
+            tx.query("INSERT INTO Cars VALUE ?")
+                .param(car("3333", "Bob"))
+                .execute()
+                .await?;
+
+            Ok(())
+        })
+        .await?;
+
+    let bob_cars = client
+        .transaction_within(|tx| async move {
+            let cars = tx
+                .query("SELECT * FROM Cars AS c WHERE c.owner = ?")
+                .param("Bob")
+                .execute()
+                .await?;
+
+            Ok(cars)
+        })
+        .await?;
+
+    assert_eq!(bob_cars.len(), 2);
+    assert_eq!(bob_cars[0].get_value::<String>("plate")?, "2222");
+    assert_eq!(bob_cars[1].get_value::<String>("plate")?, "3333");
+
+    client
+        .transaction_within(|tx| async move {
+            let updated = tx
+                .query("UPDATE Cars SET owner = ?, details.color = 'red' WHERE plate = ?")
+                .param("Carla")
+                .param("1111")
+                .execute()
+                .await?;
+
+            assert_eq!(updated.len(), 1);
+
+            let deleted = tx.query("DELETE FROM Cars WHERE owner = 'Bob'").execute().await?;
+
+            assert_eq!(deleted.len(), 2);
+
+            Ok(())
+        })
+        .await?;
+
+    let count = client
+        .transaction_within(|tx| async move { tx.query("SELECT COUNT(*) FROM Cars").count().await })
+        .await?;
+
+    assert_eq!(count, 1);
+
+    let documents = fake.documents("Cars");
+
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0]["owner"], IonValue::String("Carla".to_string()));
+    assert_eq!(
+        documents[0]["details"],
+        IonValue::Struct(HashMap::from([(
+            "color".to_string(),
+            IonValue::String("red".to_string())
+        )]))
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_rollback_discards_changes() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    let transaction = client.transaction().await?;
+
+    transaction
+        .query("INSERT INTO Cars VALUE ?")
+        .param(car("1111", "Ana"))
+        .execute()
+        .await?;
+
+    transaction.rollback().await?;
+
+    assert!(fake.documents("Cars").is_empty());
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_occ_conflict() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Cars VALUE ?")
+                .param(car("1111", "Ana"))
+                .execute()
+                .await?;
+            Ok(())
+        })
+        .await?;
+
+    let first = client.transaction().await?;
+    let second = client.transaction().await?;
+
+    first
+        .query("UPDATE Cars SET owner = 'Bob' WHERE plate = '1111'")
+        .execute()
+        .await?;
+
+    second
+        .query("UPDATE Cars SET owner = 'Carla' WHERE plate = '1111'")
+        .execute()
+        .await?;
+
+    first.commit().await?;
+
+    let error = second.commit().await.unwrap_err();
+    assert_eq!(error.kind(), QldbErrorKind::OccConflict);
+    assert!(error.is_retryable());
+
+    assert_eq!(fake.documents("Cars")[0]["owner"], IonValue::String("Bob".to_string()));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_no_conflict_between_different_documents() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Cars << ?, ? >>")
+                .param(car("1111", "Ana"))
+                .param(car("2222", "Bob"))
+                .execute()
+                .await?;
+            Ok(())
+        })
+        .await?;
+
+    let first = client.transaction().await?;
+    let second = client.transaction().await?;
+
+    first
+        .query("UPDATE Cars SET owner = 'Carla' WHERE plate = '1111'")
+        .execute()
+        .await?;
+
+    second
+        .query("UPDATE Cars SET owner = 'Dani' WHERE plate = '2222'")
+        .execute()
+        .await?;
+
+    first.commit().await?;
+    second.commit().await?;
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_reports_consumed_ios_and_timing() -> Result<()> {
+    let fake = FakeQldb::new().with_page_size(2);
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    let transaction = client.transaction().await?;
+
+    let inserted = transaction
+        .query("INSERT INTO Cars << ?, ?, ? >>")
+        .param(car("1111", "Ana"))
+        .param(car("2222", "Bob"))
+        .param(car("3333", "Carla"))
+        .execute()
+        .await?;
+
+    assert_eq!(inserted.stats().write_ios(), 3);
+    assert_eq!(inserted.stats().processing_time(), Duration::from_millis(2));
+
+    let mut cursor = transaction.query("SELECT * FROM Cars").get_cursor()?;
+
+    let first_page = cursor.load_more().await?.unwrap();
+
+    assert_eq!(first_page.stats().read_ios(), 3);
+    assert_eq!(first_page.stats().processing_time(), Duration::from_millis(1));
+
+    cursor.load_more().await?;
+
+    assert_eq!(cursor.stats().read_ios(), 3);
+    assert_eq!(cursor.stats().processing_time(), Duration::from_millis(2));
+
+    transaction.commit().await?;
+
+    let total = transaction.stats().await;
+
+    assert_eq!(total.read_ios(), 3);
+    assert_eq!(total.write_ios(), 6);
+    assert_eq!(total.processing_time(), Duration::from_millis(5));
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct TypedCar {
+    plate: String,
+    doors: u8,
+    mileage: u64,
+    owner: Option<String>,
+    #[serde(with = "qldb::serialization::timestamp")]
+    registered_on: DateTime<Utc>,
+    #[serde(with = "qldb::serialization::timestamp::option")]
+    sold_on: Option<DateTime<Utc>>,
+    #[serde(with = "qldb::serialization::decimal")]
+    price: BigDecimal,
+    photo: Vec<u8>,
+    tags: Vec<String>,
+    engine: Engine,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Engine {
+    Electric { kw: u32 },
+    Combustion(String),
+}
+
+#[async_std::test]
+async fn fake_serde_round_trip() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    let car = TypedCar {
+        plate: "1111".to_string(),
+        doors: 5,
+        mileage: u64::MAX,
+        owner: None,
+        registered_on: Utc.with_ymd_and_hms(2020, 1, 31, 8, 0, 0).unwrap(),
+        sold_on: Some(Utc.with_ymd_and_hms(2023, 7, 1, 12, 30, 0).unwrap()),
+        price: BigDecimal::from_str("12999.95").unwrap(),
+        photo: vec![0, 1, 255],
+        tags: vec!["family".to_string(), "blue".to_string()],
+        engine: Engine::Electric { kw: 150 },
+    };
+
+    let stored_car = client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Cars VALUE ?")
+                .param_serde(&car)?
+                .execute()
+                .await?;
+
+            let cars = tx.query("SELECT * FROM Cars").execute().await?;
+
+            Ok((car, cars.deserialize_all::<TypedCar>()?))
+        })
+        .await?;
+
+    let (car, cars) = stored_car;
+
+    assert_eq!(cars, [car]);
+
+    let document = &fake.documents("Cars")[0];
+
+    assert!(matches!(document["owner"], IonValue::Null(_)));
+    assert!(matches!(document["registered_on"], IonValue::DateTime(_)));
+    assert!(matches!(document["price"], IonValue::Decimal(_)));
+    assert_eq!(document["photo"], IonValue::Blob(vec![0, 1, 255]));
+    assert!(matches!(document["mileage"], IonValue::BigInteger(_)));
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SoldCar {
+    plate: String,
+    #[serde(with = "qldb::serialization::null::string")]
+    owner: Option<String>,
+    #[serde(with = "qldb::serialization::null::integer")]
+    doors: Option<u8>,
+    #[serde(with = "qldb::serialization::null::timestamp")]
+    sold_on: Option<DateTime<Utc>>,
+    #[serde(with = "qldb::serialization::null::decimal")]
+    price: Option<BigDecimal>,
+}
+
+#[async_std::test]
+async fn fake_serde_typed_nulls() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    let unsold = SoldCar {
+        plate: "1111".to_string(),
+        owner: None,
+        doors: None,
+        sold_on: None,
+        price: None,
+    };
+
+    let sold = SoldCar {
+        plate: "2222".to_string(),
+        owner: Some("Ana".to_string()),
+        doors: Some(3),
+        sold_on: Some(Utc.with_ymd_and_hms(2023, 7, 1, 12, 30, 0).unwrap()),
+        price: Some(BigDecimal::from_str("12999.95").unwrap()),
+    };
+
+    let (cars, stored_cars) = client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Cars << ?, ? >>")
+                .param_serde(&unsold)?
+                .param_serde(&sold)?
+                .execute()
+                .await?;
+
+            let cars = tx.query("SELECT * FROM Cars").execute().await?;
+
+            Ok(([unsold, sold], cars.deserialize_all::<SoldCar>()?))
+        })
+        .await?;
+
+    assert_eq!(stored_cars, cars);
+
+    let documents = fake.documents("Cars");
+
+    assert_eq!(documents[0]["owner"], IonValue::Null(NullIonValue::String));
+    assert_eq!(documents[0]["doors"], IonValue::Null(NullIonValue::Integer));
+    assert_eq!(documents[0]["sold_on"], IonValue::Null(NullIonValue::DateTime));
+    assert_eq!(documents[0]["price"], IonValue::Null(NullIonValue::Decimal));
+
+    assert_eq!(documents[1]["owner"], IonValue::String("Ana".to_string()));
+    assert_eq!(documents[1]["doors"], IonValue::Integer(3));
+    assert!(matches!(documents[1]["sold_on"], IonValue::DateTime(_)));
+    assert!(matches!(documents[1]["price"], IonValue::Decimal(_)));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_serde_optional_bytes_are_not_blobs() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    let car = HashMap::from([("seats".to_string(), vec![Some(1u8), Some(2)])]);
+
+    client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Cars VALUE ?")
+                .param_serde(&car)?
+                .execute()
+                .await?;
+            Ok(())
+        })
+        .await?;
+
+    assert_eq!(
+        fake.documents("Cars")[0]["seats"],
+        IonValue::List(vec![IonValue::Integer(1), IonValue::Integer(2)])
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_serde_rejects_unsupported_params() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    let owners = HashMap::from([("owners".to_string(), HashMap::from([(1, "Ana".to_string())]))]);
+
+    let result = client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Cars VALUE ?")
+                .param_serde(&owners)?
+                .execute()
+                .await?;
+            Ok(())
+        })
+        .await;
+
+    match result {
+        Err(QldbError::ParamSerializationError { path, message }) => {
+            assert_eq!(path, "owners");
+            assert!(message.contains("keys must be strings"), "{}", message);
+        }
+        other => panic!("Expected a serialization error, got {:?}", other),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+mod utils;
+
+use eyre::Result;
+use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+use qldb::{AcquireOptions, QldbClient, QldbError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use utils::fake::{create_table, wait_until};
+
+#[async_std::test]
+async fn fake_builder_uses_the_given_transport() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let ledgers = Arc::new(Mutex::new(vec![]));
+    let recorded = ledgers.clone();
+
+    let transport = InterceptingTransport::new(fake.clone(), move |request| {
+        if let Some(start_session) = &request.start_session {
+            recorded.lock().unwrap().push(start_session.ledger_name.clone());
+        }
+
+        Intercept::Forward
+    });
+
+    let client = QldbClient::builder("my-ledger")
+        .transport(Arc::new(transport))
+        .build()?;
+
+    create_table(&client, "Cars").await?;
+
+    assert_eq!(*ledgers.lock().unwrap(), vec!["my-ledger".to_string()]);
+    assert_eq!(fake.open_sessions(), 1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_builder_applies_pool_settings() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .max_sessions(2)
+        .min_idle(1)
+        .acquire_options(AcquireOptions::new().timeout(Duration::from_millis(50)))
+        .build()?;
+
+    // The concurrency limit starts at max_sessions
+    assert_eq!(client.concurrency_limit(), 2);
+
+    // min_idle opens a session before any transaction needs it
+    wait_until(|| client.pool_stats().idle_sessions() == 1).await;
+
+    let _first = client.transaction().await?;
+    let _second = client.transaction().await?;
+
+    let error = client.transaction().await.unwrap_err();
+    assert!(matches!(error, QldbError::SessionAcquireTimeout(timeout) if timeout == Duration::from_millis(50)));
+    assert_eq!(fake.open_sessions(), 2);
+
+    Ok(())
+}
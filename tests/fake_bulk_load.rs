@@ -0,0 +1,262 @@
+mod utils;
+
+use eyre::Result;
+use ion_binary_rs::IonValue;
+use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+use qldb::transport::{ServiceError, TransportError};
+use qldb::{BulkLimits, BulkLoadOptions, QldbClient, QldbError, QldbErrorKind};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed};
+use std::sync::{Arc, Mutex};
+use utils::fake::{car, create_client, create_table, record_statements};
+
+fn numbered_cars(range: std::ops::Range<usize>) -> Vec<HashMap<String, IonValue>> {
+    range.map(|number| car(&format!("{:04}", number), "Ana")).collect()
+}
+
+#[async_std::test]
+async fn fake_insert_many() -> Result<()> {
+    let fake = FakeQldb::new();
+    create_table(&create_client(&fake)?, "Cars").await?;
+
+    let (transport, statements) = record_statements(&fake);
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(transport.clone())
+        .bulk_limits(
+            BulkLimits::new()
+                .documents_per_statement(40)
+                .documents_per_transaction(100),
+        )
+        .build()?;
+
+    let document_ids = client
+        .transaction_within(|tx| async move { tx.insert_many("Cars", numbered_cars(0..100)).await })
+        .await?;
+
+    assert_eq!(document_ids.len(), 100);
+    assert_eq!(document_ids.iter().collect::<HashSet<_>>().len(), 100);
+
+    let params: Vec<usize> = statements.lock().unwrap().iter().map(|(_, params)| *params).collect();
+    assert_eq!(params, vec![40, 40, 20]);
+
+    // Each car takes a few dozens of bytes, so only two fit
+    let client = QldbClient::builder("fake-ledger")
+        .transport(transport.clone())
+        .bulk_limits(BulkLimits::new().bytes_per_transaction(100))
+        .build()?;
+
+    statements.lock().unwrap().clear();
+
+    let error = client
+        .transaction_within(|tx| async move { tx.insert_many("Cars", numbered_cars(100..105)).await })
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, QldbError::BulkLimitsExceeded { documents: 5, .. }));
+    assert_eq!(error.kind(), QldbErrorKind::Usage);
+    assert!(statements.lock().unwrap().is_empty());
+
+    client
+        .transaction_within(|tx| async move { tx.insert_many("Cars", numbered_cars(100..102)).await })
+        .await?;
+
+    let statements = statements.lock().unwrap().clone();
+    assert_eq!(statements, [("INSERT INTO Cars << ?, ? >>".to_string(), 2)]);
+
+    // The default limits allow 40 documents per transaction
+    let client = create_client(&fake)?;
+
+    let error = client
+        .transaction_within(|tx| async move { tx.insert_many("Cars", numbered_cars(200..241)).await })
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, QldbError::BulkLimitsExceeded { documents: 41, .. }));
+
+    assert_eq!(
+        client.read_query("SELECT COUNT(*) FROM Cars").await?.count().await?,
+        102
+    );
+
+    Ok(())
+}
+
+/// Fails every statement after the first `allowed` ones.
+fn fail_after(fake: &FakeQldb, allowed: usize) -> InterceptingTransport {
+    let allowed = AtomicUsize::new(allowed);
+
+    InterceptingTransport::new(fake.clone(), move |request| {
+        if request.execute_statement.is_none() {
+            return Intercept::Forward;
+        }
+
+        match allowed.fetch_update(Relaxed, Relaxed, |allowed| allowed.checked_sub(1)) {
+            Ok(_) => Intercept::Forward,
+            Err(_) => Intercept::Fail(ServiceError::BadRequest("Something went wrong".to_string()).into()),
+        }
+    })
+}
+
+#[async_std::test]
+async fn fake_bulk_load_reports_progress_and_resumes() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    let limits = BulkLimits::new()
+        .documents_per_transaction(30)
+        .documents_per_statement(30);
+
+    let failing = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fail_after(&fake, 2)))
+        .bulk_limits(limits)
+        .build()?;
+
+    let reported = Arc::new(Mutex::new(vec![]));
+    let reported_by_callback = reported.clone();
+
+    let options = BulkLoadOptions::new().on_progress(Arc::new(move |progress| {
+        reported_by_callback.lock().unwrap().push((
+            progress.loaded_documents(),
+            progress.transactions(),
+            progress.document_ids().len(),
+        ));
+    }));
+
+    let error = failing
+        .bulk_load("Cars", numbered_cars(0..100), options.clone())
+        .await
+        .unwrap_err();
+
+    let loaded = match error {
+        QldbError::BulkLoadInterrupted {
+            loaded_documents,
+            source,
+        } => {
+            assert_eq!(source.kind(), QldbErrorKind::BadRequest);
+            loaded_documents
+        }
+        error => panic!("Unexpected error {:?}", error),
+    };
+
+    assert_eq!(loaded, 60);
+    assert_eq!(*reported.lock().unwrap(), vec![(30, 1, 30), (60, 2, 30)]);
+
+    reported.lock().unwrap().clear();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .bulk_limits(limits)
+        .build()?;
+
+    let loaded = client
+        .bulk_load("Cars", numbered_cars(0..100), options.resume_from(loaded))
+        .await?;
+
+    assert_eq!(loaded, 100);
+    assert_eq!(*reported.lock().unwrap(), vec![(90, 1, 30), (100, 2, 10)]);
+
+    let plates: HashSet<String> = client
+        .read_query("SELECT * FROM Cars")
+        .await?
+        .execute()
+        .await?
+        .into_iter()
+        .map(|car| car.get_value::<String>("plate"))
+        .collect::<Result<_, _>>()?;
+
+    assert_eq!(plates.len(), 100);
+    assert_eq!(
+        client.read_query("SELECT COUNT(*) FROM Cars").await?.count().await?,
+        100
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_bulk_inserts_reject_invalid_table_names() -> Result<()> {
+    let fake = FakeQldb::new();
+    create_table(&create_client(&fake)?, "Cars").await?;
+
+    let (transport, statements) = record_statements(&fake);
+
+    let client = QldbClient::builder("fake-ledger").transport(transport).build()?;
+
+    let malicious = "Cars << {'plate': 'stolen'} >> --";
+
+    let error = client
+        .transaction_within(|tx| async move { tx.insert_many(malicious, numbered_cars(0..2)).await })
+        .await
+        .unwrap_err();
+
+    assert!(matches!(&error, QldbError::InvalidTableName(table) if table == malicious));
+    assert_eq!(error.kind(), QldbErrorKind::Usage);
+
+    for table in ["", "1Cars", "Cars\"", "Cars Owners", &"C".repeat(129)] {
+        let error = client
+            .bulk_load(table, numbered_cars(0..2), BulkLoadOptions::new())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, QldbError::InvalidTableName(_)), "{:?}", table);
+    }
+
+    assert!(statements.lock().unwrap().is_empty());
+    assert_eq!(fake.documents("Cars").len(), 0);
+
+    let loaded = client
+        .bulk_load("_Cars_2", numbered_cars(0..2), BulkLoadOptions::new())
+        .await
+        .unwrap_err();
+
+    // Valid, but the table doesn't exist
+    assert_eq!(loaded.kind(), QldbErrorKind::BadRequest);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_bulk_load_doesnt_retry_unanswered_commits() -> Result<()> {
+    let fake = FakeQldb::new();
+    create_table(&create_client(&fake)?, "Cars").await?;
+
+    let lost = AtomicBool::new(false);
+
+    // The first commit reaches the ledger, but not its answer
+    let transport = InterceptingTransport::new(fake.clone(), move |request| {
+        if request.commit_transaction.is_some() && !lost.swap(true, Relaxed) {
+            return Intercept::LoseResponse(TransportError::Dispatch("Connection reset".to_string()));
+        }
+
+        Intercept::Forward
+    });
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(transport))
+        .bulk_limits(BulkLimits::new().documents_per_transaction(10))
+        .build()?;
+
+    let error = client
+        .bulk_load("Cars", numbered_cars(0..20), BulkLoadOptions::new())
+        .await
+        .unwrap_err();
+
+    match error {
+        QldbError::BulkLoadInterrupted {
+            loaded_documents,
+            source,
+        } => {
+            assert_eq!(loaded_documents, 0);
+            assert_eq!(source.kind(), QldbErrorKind::Transport);
+        }
+        error => panic!("Unexpected error {:?}", error),
+    }
+
+    // Committed once, not retried
+    assert_eq!(fake.documents("Cars").len(), 10);
+
+    Ok(())
+}
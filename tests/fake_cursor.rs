@@ -0,0 +1,287 @@
+mod utils;
+
+use eyre::Result;
+use futures::{StreamExt, TryStreamExt};
+use ion_binary_rs::IonValue;
+use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+use qldb::transport::SendCommandRequest;
+use qldb::QldbClient;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use utils::fake::{car, create_client, create_table, wait_until};
+
+async fn insert_cars(client: &QldbClient, count: usize) -> Result<()> {
+    client
+        .transaction_within(|tx| async move {
+            for index in 0..count {
+                tx.query("INSERT INTO Cars VALUE ?")
+                    .param(car(&index.to_string(), "Ana"))
+                    .execute()
+                    .await?;
+            }
+
+            Ok(())
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn command_name(request: &SendCommandRequest) -> Option<&'static str> {
+    if request.start_transaction.is_some() {
+        Some("StartTransaction")
+    } else if request.execute_statement.is_some() {
+        Some("ExecuteStatement")
+    } else if request.fetch_page.is_some() {
+        Some("FetchPage")
+    } else if request.commit_transaction.is_some() {
+        Some("CommitTransaction")
+    } else if request.abort_transaction.is_some() {
+        Some("AbortTransaction")
+    } else {
+        None
+    }
+}
+
+#[async_std::test]
+async fn fake_cursor_loads_every_page() -> Result<()> {
+    let fake = FakeQldb::new().with_page_size(3);
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    client
+        .transaction_within(|tx| async move {
+            for index in 0..10 {
+                tx.query("INSERT INTO Cars VALUE ?")
+                    .param(car(&index.to_string(), "Ana"))
+                    .execute()
+                    .await?;
+            }
+
+            Ok(())
+        })
+        .await?;
+
+    let pages = client
+        .transaction_within(|tx| async move {
+            let mut cursor = tx.query("SELECT * FROM Cars").get_cursor()?;
+            let mut pages = vec![];
+
+            while let Some(page) = cursor.load_more().await? {
+                pages.push(page.len());
+            }
+
+            Ok(pages)
+        })
+        .await?;
+
+    assert_eq!(pages, [3, 3, 3, 1]);
+
+    let all = client
+        .transaction_within(|tx| async move { tx.query("SELECT * FROM Cars").execute().await })
+        .await?;
+
+    assert_eq!(all.len(), 10);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_cursor_streams_stop_early() -> Result<()> {
+    let fake = FakeQldb::new().with_page_size(2);
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    client
+        .transaction_within(|tx| async move {
+            for index in 0..9 {
+                tx.query("INSERT INTO Cars VALUE ?")
+                    .param(car(&index.to_string(), "Ana"))
+                    .execute()
+                    .await?;
+            }
+
+            Ok(())
+        })
+        .await?;
+
+    let transaction = client.transaction().await?;
+
+    let pages: Vec<usize> = transaction
+        .query("SELECT * FROM Cars")
+        .get_cursor()?
+        .into_page_stream()
+        .map_ok(|page| page.len())
+        .try_collect()
+        .await?;
+
+    assert_eq!(pages, [2, 2, 2, 2, 1]);
+
+    let plates: Vec<String> = transaction
+        .query("SELECT * FROM Cars")
+        .get_cursor()?
+        .into_stream()
+        .take(3)
+        .map(|car| Ok(car?.get_value::<String>("plate")?))
+        .collect::<Vec<Result<String>>>()
+        .await
+        .into_iter()
+        .collect::<Result<_>>()?;
+
+    assert_eq!(plates, ["0", "1", "2"]);
+
+    // Stopping the stream early leaves the transaction usable and its
+    // commit digest still matches the one calculated by the ledger.
+    transaction
+        .query("UPDATE Cars SET owner = 'Bob' WHERE plate = '0'")
+        .execute()
+        .await?;
+
+    transaction.commit().await?;
+    let stats = transaction.stats().await;
+
+    // 5 pages, 2 more until the third document, the update and the commit.
+    assert_eq!(stats.processing_time(), Duration::from_millis(9));
+    assert_eq!(fake.documents("Cars")[0]["owner"], IonValue::String("Bob".to_string()));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_cursor_prefetches_pages() -> Result<()> {
+    let fake = FakeQldb::new().with_page_size(2);
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    client
+        .transaction_within(|tx| async move {
+            for index in 0..10 {
+                tx.query("INSERT INTO Cars VALUE ?")
+                    .param(car(&index.to_string(), "Ana"))
+                    .execute()
+                    .await?;
+            }
+
+            Ok(())
+        })
+        .await?;
+
+    let transaction = client.transaction().await?;
+
+    let mut cursor = transaction.query("SELECT * FROM Cars").get_cursor_with_prefetch(2)?;
+
+    let first_page = cursor.load_more().await?.expect("first page");
+    assert_eq!(first_page.len(), 2);
+
+    // Gives the background task time to load more pages than it should.
+    async_std::task::sleep(Duration::from_millis(100)).await;
+
+    // The statement, the 2 pages waiting in the cursor and the one the
+    // background task holds until there is room for it.
+    assert_eq!(transaction.stats().await.processing_time(), Duration::from_millis(4));
+
+    let rest = cursor.load_all().await?;
+
+    let plates = first_page
+        .into_iter()
+        .chain(rest)
+        .map(|car| Ok(car.get_value::<String>("plate")?))
+        .collect::<Result<Vec<String>>>()?;
+
+    assert_eq!(plates, (0..10).map(|index| index.to_string()).collect::<Vec<_>>());
+
+    // 5 pages and the commit.
+    transaction.commit().await?;
+    assert_eq!(transaction.stats().await.processing_time(), Duration::from_millis(6));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_cursor_dropped_while_prefetching() -> Result<()> {
+    let fake = FakeQldb::new().with_page_size(1);
+    let commands = Arc::new(Mutex::new(vec![]));
+    let received = commands.clone();
+
+    // Commands are recorded when they reach the ledger, after the delay.
+    let recorder = InterceptingTransport::new(fake.clone(), move |request| {
+        if let Some(name) = command_name(request) {
+            received.lock().unwrap().push(name);
+        }
+
+        Intercept::Forward
+    });
+
+    let fetching = Arc::new(AtomicUsize::new(0));
+    let sent = fetching.clone();
+
+    let slow_pages = InterceptingTransport::new(recorder, move |request| match request.fetch_page {
+        Some(_) => {
+            sent.fetch_add(1, Relaxed);
+            Intercept::Delay(Duration::from_millis(20))
+        }
+        None => Intercept::Forward,
+    });
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(slow_pages))
+        .build()?;
+
+    create_table(&client, "Cars").await?;
+    insert_cars(&client, 5).await?;
+
+    commands.lock().unwrap().clear();
+
+    let transaction = client.transaction().await?;
+
+    let mut cursor = transaction.query("SELECT * FROM Cars").get_cursor_with_prefetch(2)?;
+    assert_eq!(cursor.load_more().await?.map(|page| page.len()), Some(1));
+
+    // The second page is on its way, the commit waits for it.
+    wait_until(|| fetching.load(Relaxed) == 1).await;
+    drop(cursor);
+    transaction.commit().await?;
+
+    assert_eq!(
+        *commands.lock().unwrap(),
+        ["StartTransaction", "ExecuteStatement", "FetchPage", "CommitTransaction"]
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_cursor_loads_the_pages_left_by_a_stopped_prefetch() -> Result<()> {
+    let fake = FakeQldb::new().with_page_size(2);
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+    insert_cars(&client, 10).await?;
+
+    let transaction = client.transaction().await?;
+
+    let mut cursor = transaction.query("SELECT * FROM Cars").get_cursor_with_prefetch(1)?;
+    let first_page = cursor.load_more().await?.expect("first page");
+
+    // Another statement stops the prefetch.
+    let count = transaction.query("SELECT COUNT(*) FROM Cars").count().await?;
+    assert_eq!(count, 10);
+
+    let rest = cursor.load_all().await?;
+
+    let plates = first_page
+        .into_iter()
+        .chain(rest)
+        .map(|car| Ok(car.get_value::<String>("plate")?))
+        .collect::<Result<Vec<String>>>()?;
+
+    assert_eq!(plates, (0..10).map(|index| index.to_string()).collect::<Vec<_>>());
+
+    transaction.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+mod utils;
+
+use eyre::Result;
+use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+use qldb::transport::{ServiceError, TransportError};
+use qldb::{QldbClient, QldbError, QldbErrorKind};
+use std::sync::Arc;
+use utils::fake::{create_client, fail_first_session};
+
+#[async_std::test]
+async fn fake_unknown_table() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    let result = client
+        .transaction_within(|tx| async move { tx.query("SELECT * FROM Missing").execute().await })
+        .await;
+
+    let error = result.unwrap_err();
+    assert_eq!(error.kind(), QldbErrorKind::BadRequest);
+    assert!(!error.is_retryable());
+    assert_eq!(error.service_message(), Some("No such variable named 'Missing'"));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_error_classification() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(InterceptingTransport::new(fake.clone(), |request| {
+            // Rejects every statement as QLDB does when throttling
+            if request.execute_statement.is_none() {
+                return Intercept::Forward;
+            }
+
+            Intercept::Fail(TransportError::Service {
+                error: ServiceError::RateExceeded("Slow down".to_string()),
+                request_id: Some("REQUEST-1".to_string()),
+            })
+        })))
+        .build()?;
+
+    let error = client
+        .transaction_within(|tx| async move { tx.query("SELECT * FROM Cars").execute().await })
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.kind(), QldbErrorKind::RateExceeded);
+    assert_eq!(error.request_id(), Some("REQUEST-1"));
+    assert_eq!(error.service_message(), Some("Slow down"));
+    assert_eq!(
+        error.to_string(),
+        "The QLDB command returned an error: QLDB returned an error: Rate exceeded: Slow down"
+    );
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fail_first_session(&fake)))
+        .build()?;
+
+    let error = client.transaction().await.unwrap_err();
+    assert_eq!(error.kind(), QldbErrorKind::Credentials);
+    assert_eq!(error.request_id(), None);
+    assert!(!error.is_retryable());
+
+    let transaction = client.transaction().await?;
+    transaction.rollback().await?;
+
+    let error = transaction.commit().await.unwrap_err();
+    assert_eq!(error.kind(), QldbErrorKind::Usage);
+    assert!(!error.is_retryable());
+
+    Ok(())
+}
+
+#[test]
+fn fake_session_start_errors_are_retryable() {
+    // What the internal pools return once they give up starting a
+    // session, which takes a while with the fake
+    let error = QldbError::SessionStartError(eyre::eyre!(TransportError::Dispatch("Connection reset".to_string())));
+
+    assert_eq!(error.kind(), QldbErrorKind::Transport);
+    assert!(error.is_retryable());
+
+    let error = QldbError::SessionStartError(eyre::eyre!("Empty session on QLDB response"));
+
+    assert_eq!(error.kind(), QldbErrorKind::Integrity);
+    assert!(!error.is_retryable());
+}
@@ -0,0 +1,175 @@
+mod utils;
+
+use eyre::Result;
+use qldb::testing::FakeQldb;
+use qldb::{Document, QldbError, QldbErrorKind, QldbExtractError};
+use utils::fake::{car, create_client, create_table};
+
+#[derive(Debug, PartialEq)]
+struct Plate(String);
+
+impl TryFrom<Document> for Plate {
+    type Error = QldbExtractError;
+
+    fn try_from(document: Document) -> Result<Self, Self::Error> {
+        Ok(Plate(document.get_value("plate")?))
+    }
+}
+
+#[async_std::test]
+async fn fake_single_row_helpers() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    client
+        .transaction_within(|tx| async move {
+            tx.insert_many("Cars", vec![car("1111", "Ana"), car("2222", "Bob")])
+                .await
+        })
+        .await?;
+
+    let by_plate = "SELECT * FROM Cars AS c WHERE c.plate = ?";
+
+    let car = client.read_query(by_plate).await?.param("1111").fetch_one().await?;
+    assert_eq!(car.get_value::<String>("owner")?, "Ana");
+
+    let error = client
+        .read_query(by_plate)
+        .await?
+        .param("9999")
+        .fetch_one()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::UnexpectedRowCount { expected: 1, got: 0 }));
+    assert_eq!(error.kind(), QldbErrorKind::Usage);
+
+    let error = client
+        .read_query("SELECT * FROM Cars")
+        .await?
+        .fetch_one()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::UnexpectedRowCount { expected: 1, got: 2 }));
+
+    let missing = client
+        .read_query(by_plate)
+        .await?
+        .param("9999")
+        .fetch_optional()
+        .await?;
+    assert_eq!(missing, None);
+
+    let found = client
+        .read_query(by_plate)
+        .await?
+        .param("2222")
+        .fetch_optional()
+        .await?;
+    assert_eq!(
+        found.map(|car| car.get_value::<String>("owner")).transpose()?,
+        Some("Bob".to_string())
+    );
+
+    let error = client
+        .read_query("SELECT * FROM Cars")
+        .await?
+        .fetch_optional()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::UnexpectedRowCount { expected: 1, got: 2 }));
+
+    let owner: String = client
+        .read_query("SELECT VALUE c.owner FROM Cars AS c WHERE c.plate = ?")
+        .await?
+        .param("2222")
+        .execute_scalar()
+        .await?;
+    assert_eq!(owner, "Bob");
+
+    let error = client
+        .read_query("SELECT VALUE c.owner FROM Cars AS c WHERE c.plate = ?")
+        .await?
+        .param("2222")
+        .execute_scalar::<i64>()
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        QldbError::QldbExtractError(QldbExtractError::BadDataType(_))
+    ));
+
+    let error = client
+        .read_query("SELECT VALUE c.owner FROM Cars AS c")
+        .await?
+        .execute_scalar::<String>()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::UnexpectedRowCount { expected: 1, got: 2 }));
+
+    let mut plates: Vec<Plate> = client.read_query("SELECT * FROM Cars").await?.execute_as().await?;
+    plates.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(plates, vec![Plate("1111".to_string()), Plate("2222".to_string())]);
+
+    // The transaction is still usable after a wrong row count
+    client
+        .transaction_within(|tx| async move {
+            tx.query(by_plate).param("9999").fetch_one().await.unwrap_err();
+            tx.query(by_plate).param("1111").fetch_one().await?;
+            Ok(())
+        })
+        .await?;
+
+    assert_eq!(fake.open_transactions(), 0);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_count() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    client
+        .transaction_within(|tx| async move {
+            tx.insert_many("Cars", vec![car("1111", "Ana"), car("2222", "Bob")])
+                .await
+        })
+        .await?;
+
+    let count = client.read_query("SELECT COUNT(*) FROM Cars").await?.count().await?;
+    assert_eq!(count, 2);
+
+    let error = client
+        .read_query("SELECT VALUE c.owner FROM Cars AS c WHERE c.plate = ?")
+        .await?
+        .param("1111")
+        .count()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::NonValidCountStatementResult));
+
+    let error = client
+        .read_query("SELECT * FROM Cars AS c WHERE c.plate = ?")
+        .await?
+        .param("1111")
+        .count()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::NonValidCountStatementResult));
+
+    let error = client
+        .read_query("SELECT * FROM Cars")
+        .await?
+        .count()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::UnexpectedRowCount { expected: 1, got: 2 }));
+
+    assert_eq!(fake.open_transactions(), 0);
+
+    Ok(())
+}
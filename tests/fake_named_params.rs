@@ -0,0 +1,144 @@
+mod utils;
+
+use eyre::Result;
+use qldb::testing::FakeQldb;
+use qldb::{QldbClient, QldbError, QldbErrorKind};
+use utils::fake::{car, create_client, create_table, record_statements};
+
+#[async_std::test]
+async fn fake_named_params() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Cars << :first, :second >>")
+                .named_param("second", car("2222", "Bob"))
+                .named_param("first", car("1111", "Ana"))
+                .execute()
+                .await?;
+
+            tx.query("UPDATE Cars AS c SET c.note = 'at 10:30 :late', c.seller = :owner WHERE c.owner = :owner")
+                .named_param("owner", "Bob")
+                .execute()
+                .await?;
+
+            Ok(())
+        })
+        .await?;
+
+    let cars = client
+        .read_query("SELECT * FROM Cars AS c WHERE c.owner = :owner AND c.plate = :plate")
+        .await?
+        .named_param("plate", "2222")
+        .named_param("owner", "Bob")
+        .execute()
+        .await?;
+
+    assert_eq!(cars.len(), 1);
+    assert_eq!(cars[0].get_value::<String>("note")?, "at 10:30 :late");
+    assert_eq!(cars[0].get_value::<String>("seller")?, "Bob");
+
+    let (transport, statements) = record_statements(&fake);
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(transport.clone())
+        .build()?;
+
+    // The fake doesn't understand comments nor Ion literals, only the
+    // statement sent matters here
+    let statement = "SELECT * FROM \"Cars:old\" AS c /* :a */ WHERE c.plate = :plate -- :b\n\
+                     AND c.info = `{a:1}` AND c.owner = '' || :owner || '''s:c'";
+
+    client
+        .read_query(statement)
+        .await?
+        .named_param("owner", "Bob")
+        .named_param("plate", "2222")
+        .execute()
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        statements.lock().unwrap()[0],
+        (
+            "SELECT * FROM \"Cars:old\" AS c /* :a */ WHERE c.plate = ? -- :b\n\
+             AND c.info = `{a:1}` AND c.owner = '' || ? || '''s:c'"
+                .to_string(),
+            2
+        )
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_named_params_errors() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    let tx = client.transaction().await?;
+    let query = "SELECT * FROM Cars AS c WHERE c.plate = :plate AND c.owner = :owner";
+
+    let error = tx
+        .query(query)
+        .named_param("plate", "1111")
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(matches!(&error, QldbError::MissingNamedParam(name) if name == "owner"));
+    assert_eq!(error.kind(), QldbErrorKind::Usage);
+
+    let error = tx
+        .query(query)
+        .named_param("plate", "1111")
+        .named_param("owner", "Ana")
+        .named_param("model", "T")
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(matches!(&error, QldbError::UnusedNamedParam(name) if name == "model"));
+
+    let error = tx
+        .query(query)
+        .named_param("plate", "1111")
+        .named_param("owner", "Ana")
+        .named_param("plate", "2222")
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(matches!(&error, QldbError::DuplicatedNamedParam(name) if name == "plate"));
+
+    let error = tx
+        .query("SELECT * FROM Cars AS c WHERE c.plate = :plate AND c.owner = ?")
+        .named_param("plate", "1111")
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::MixedParams));
+
+    let error = tx
+        .query("SELECT * FROM Cars AS c WHERE c.plate = :plate")
+        .named_param("plate", "1111")
+        .param("Ana")
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::MixedParams));
+
+    // Nothing was sent, so the transaction can still be used
+    let cars = tx
+        .query("SELECT * FROM Cars AS c WHERE c.plate = :plate")
+        .named_param("plate", "1111")
+        .execute()
+        .await?;
+    assert!(cars.is_empty());
+
+    tx.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,644 @@
+mod utils;
+
+use eyre::Result;
+use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+use qldb::transport::{EndSessionRequest, QldbTransport, SendCommandRequest, StartSessionRequest};
+use qldb::{
+    AcquireOptions, BackgroundTask, PoolCloseReport, QldbClient, QldbError, QldbErrorKind, QldbResult, Session,
+    SessionPool, ThrottlingPolicy,
+};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utils::fake::{car, create_client, create_table, fail_first_session, wait_until};
+
+#[async_std::test]
+async fn fake_pool_stats() -> Result<()> {
+    let fake = FakeQldb::new();
+    let reported = Arc::new(Mutex::new(vec![]));
+    let reported_callback = reported.clone();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fail_first_session(&fake)))
+        .pool_stats_callback(Duration::from_millis(10), move |stats| {
+            reported_callback.lock().unwrap().push(stats);
+        })
+        .build()?;
+
+    assert_eq!(client.pool_stats().created_sessions(), 0);
+
+    // The transaction waiting for the session gets the error instead of
+    // the pool trying again forever.
+    let error = client.transaction().await.unwrap_err();
+    assert!(matches!(error, QldbError::SessionStartError(_)));
+
+    create_table(&client, "Cars").await?;
+
+    // Sessions are given back to the pool in the background.
+    wait_until(|| client.pool_stats().idle_sessions() == 1).await;
+
+    let stats = client.pool_stats();
+    assert_eq!(stats.created_sessions(), 1);
+    assert_eq!(stats.creation_failures(), 1);
+    assert_eq!(stats.in_use_sessions(), 0);
+    assert_eq!(stats.idle_sessions(), 1);
+    assert_eq!(stats.pending_requests(), 0);
+
+    let first = client.transaction().await?;
+    let second = client.transaction().await?;
+
+    let stats = client.pool_stats();
+    assert_eq!(stats.created_sessions(), 2);
+    assert_eq!(stats.in_use_sessions(), 2);
+    assert_eq!(stats.idle_sessions(), 0);
+
+    first.commit().await?;
+    second.rollback().await?;
+
+    let stats = client.pool_stats();
+    assert_eq!(stats.in_use_sessions(), 0);
+    assert!(stats.acquire_latency().max() >= stats.acquire_latency().p50());
+
+    wait_until(|| {
+        let reported = reported.lock().unwrap();
+        reported.last().is_some_and(|stats| stats.idle_sessions() == 2)
+    })
+    .await;
+
+    let last_reported = *reported.lock().unwrap().last().expect("reported stats");
+    assert_eq!(last_reported.created_sessions(), 2);
+    assert_eq!(last_reported.idle_sessions(), 2);
+
+    Ok(())
+}
+
+fn create_slow_client(fake: &FakeQldb, acquire_options: AcquireOptions) -> Result<QldbClient> {
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(InterceptingTransport::new(fake.clone(), |request| {
+            // Takes a while to start every session, as a throttled ledger would
+            match request.start_session {
+                Some(_) => Intercept::Delay(Duration::from_millis(200)),
+                None => Intercept::Forward,
+            }
+        })))
+        .acquire_options(acquire_options)
+        .build()?;
+
+    Ok(client)
+}
+
+#[async_std::test]
+async fn fake_session_acquire_timeout() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_slow_client(&fake, AcquireOptions::new().timeout(Duration::from_millis(50)))?;
+
+    let error = client.transaction().await.unwrap_err();
+    assert!(matches!(error, QldbError::SessionAcquireTimeout(timeout) if timeout == Duration::from_millis(50)));
+
+    // The session created for the request that timed out is kept in
+    // the pool, and the per transaction options replace the client's.
+    let transaction = client
+        .transaction_with_options(AcquireOptions::new().timeout(Duration::from_secs(5)))
+        .await?;
+    transaction.rollback().await?;
+
+    assert_eq!(client.pool_stats().created_sessions(), 1);
+    assert_eq!(client.pool_stats().pending_requests(), 0);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_pool_exhausted() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_slow_client(&fake, AcquireOptions::new().max_waiters(1))?;
+
+    let waiting_client = client.clone();
+    let waiting = async_std::task::spawn(async move { waiting_client.transaction().await });
+
+    wait_until(|| client.pool_stats().pending_requests() == 1).await;
+
+    let error = client.transaction().await.unwrap_err();
+    assert!(matches!(error, QldbError::PoolExhausted(1)));
+
+    let error = client
+        .transaction_with_options(AcquireOptions::new().max_waiters(1))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::PoolExhausted(1)));
+
+    waiting.await?.rollback().await?;
+
+    let transaction = client
+        .transaction_with_options(AcquireOptions::new().max_waiters(2))
+        .await?;
+    transaction.rollback().await?;
+
+    Ok(())
+}
+
+/// Runs many concurrent transactions against a pool of 3 sessions and
+/// checks that there are never more than 3 sessions open in the ledger.
+async fn stress_pool(client: QldbClient, fake: FakeQldb) -> Result<()> {
+    let max_open_sessions = Arc::new(AtomicUsize::new(0));
+
+    let tasks: Vec<_> = (0..50)
+        .map(|index| {
+            let client = client.clone();
+            let fake = fake.clone();
+            let max_open_sessions = max_open_sessions.clone();
+
+            async_std::task::spawn(async move {
+                client
+                    .transaction_within(|tx| async move {
+                        max_open_sessions.fetch_max(fake.open_sessions(), Relaxed);
+
+                        tx.query("INSERT INTO Cars VALUE ?")
+                            .param(car(&index.to_string(), "Ana"))
+                            .execute()
+                            .await?;
+
+                        async_std::task::sleep(Duration::from_millis(2)).await;
+
+                        Ok(())
+                    })
+                    .await
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        task.await?;
+    }
+
+    assert!(max_open_sessions.load(Relaxed) <= 3);
+    assert_eq!(fake.documents("Cars").len(), 50);
+
+    let stats = client.pool_stats();
+    assert!(stats.created_sessions() <= 3);
+    assert_eq!(stats.pending_requests(), 0);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_pool_respects_max_sessions() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .max_sessions(3)
+        .build()?;
+
+    create_table(&client, "Cars").await?;
+
+    stress_pool(client, fake).await
+}
+
+#[async_std::test]
+async fn fake_spawner_pool_respects_max_sessions() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .max_sessions(3)
+        .build_with_spawner(Arc::new(move |fut| {
+            async_std::task::spawn(Box::pin(fut));
+        }))?;
+
+    create_table(&client, "Cars").await?;
+
+    stress_pool(client, fake).await
+}
+
+#[async_std::test]
+async fn fake_waiters_get_returned_sessions() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .max_sessions(1)
+        // So the transactions wait in the pool instead of in the
+        // concurrency limit
+        .throttling_policy(ThrottlingPolicy::new().max_limit(2))
+        .acquire_options(AcquireOptions::new().timeout(Duration::from_millis(50)))
+        .build()?;
+
+    let first = client.transaction().await?;
+
+    // There is only one session and it is in use.
+    let error = client.transaction().await.unwrap_err();
+    assert!(matches!(error, QldbError::SessionAcquireTimeout(_)));
+
+    let waiting_client = client.clone();
+    let waiting = async_std::task::spawn(async move {
+        waiting_client
+            .transaction_with_options(AcquireOptions::new().timeout(Duration::from_secs(5)))
+            .await
+    });
+
+    wait_until(|| client.pool_stats().pending_requests() == 1).await;
+    first.rollback().await?;
+
+    waiting.await?.rollback().await?;
+
+    assert_eq!(client.pool_stats().created_sessions(), 1);
+    assert_eq!(fake.open_sessions(), 1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_pool_keeps_min_idle_sessions() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .min_idle(2)
+        .build()?;
+
+    // The sessions are created before any transaction needs them.
+    wait_until(|| client.pool_stats().idle_sessions() == 2).await;
+
+    assert_eq!(fake.open_sessions(), 2);
+    assert_eq!(client.pool_stats().idle_sessions(), 2);
+
+    create_table(&client, "Cars").await?;
+
+    wait_until(|| client.pool_stats().idle_sessions() == 3).await;
+
+    // The transaction used one of them, and another one was created
+    // meanwhile so there were always 2 idle sessions.
+    assert_eq!(client.pool_stats().created_sessions(), 3);
+    assert_eq!(client.pool_stats().idle_sessions(), 3);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_pool_closes_idle_sessions() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .idle_timeout(Duration::from_millis(50))
+        .min_idle(1)
+        .build()?;
+
+    let transactions = futures::future::try_join_all((0..3).map(|_| client.transaction())).await?;
+
+    assert!(fake.open_sessions() >= 3);
+
+    for transaction in transactions {
+        transaction.rollback().await?;
+    }
+
+    // The idle ones are ended, but min_idle are kept.
+    wait_until(|| fake.open_sessions() == 1).await;
+
+    assert_eq!(fake.open_sessions(), 1);
+
+    let stats = client.pool_stats();
+    assert_eq!(stats.idle_sessions(), 1);
+    assert_eq!(stats.closed_sessions(), stats.created_sessions() - 1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_pool_replaces_old_sessions() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .max_session_age(Duration::from_millis(50))
+        .min_idle(1)
+        .build()?;
+
+    async_std::task::sleep(Duration::from_millis(200)).await;
+
+    // Sessions are retired when they get too old, even when idle, and
+    // new ones are created for min_idle.
+    let stats = client.pool_stats();
+    assert!(stats.closed_sessions() >= 2);
+    assert_eq!(stats.created_sessions(), stats.closed_sessions() + 1);
+    assert_eq!(fake.open_sessions(), 1);
+
+    create_table(&client, "Cars").await?;
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_invalid_sessions_are_discarded() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    fake.expire_sessions();
+
+    // The pool gives the expired session, QLDB rejects it and it is
+    // discarded instead of going back to the pool.
+    let error = client.transaction().await.unwrap_err();
+    assert_eq!(error.kind(), QldbErrorKind::InvalidSession);
+
+    create_table(&client, "Owners").await?;
+
+    let transaction = client.transaction().await?;
+
+    fake.expire_sessions();
+
+    assert!(transaction.query("SELECT * FROM Cars").execute().await.is_err());
+
+    // Nothing to abort, the transaction is already gone with the session.
+    transaction.rollback().await?;
+
+    wait_until(|| client.pool_stats().closed_sessions() == 2).await;
+
+    let stats = client.pool_stats();
+    assert_eq!(stats.created_sessions(), 2);
+    assert_eq!(stats.closed_sessions(), 2);
+    assert_eq!(stats.idle_sessions(), 0);
+
+    create_table(&client, "Trucks").await?;
+    wait_until(|| client.pool_stats().idle_sessions() == 1).await;
+
+    fake.expire_sessions();
+
+    // The retry gets a new session instead of the expired one.
+    client
+        .transaction_within_retry(|tx| async move {
+            tx.query("INSERT INTO Cars VALUE ?")
+                .param(car("1", "Ana"))
+                .execute()
+                .await?;
+            Ok(())
+        })
+        .await?;
+
+    assert_eq!(fake.documents("Cars").len(), 1);
+    assert_eq!(fake.open_sessions(), 1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_close_ends_every_session() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?;
+
+    create_table(&client, "Cars").await?;
+
+    let mut transactions = futures::future::try_join_all((0..3).map(|_| client.transaction())).await?;
+    let in_flight = transactions.pop().unwrap();
+
+    for transaction in transactions {
+        transaction.rollback().await?;
+    }
+
+    let mut closing_client = client.clone();
+    let mut closing = Box::pin(async move { closing_client.close_with_timeout(Duration::from_secs(5)).await });
+
+    // The pool is marked as closed before closing waits for anything
+    assert!(futures::poll!(&mut closing).is_pending());
+
+    // No new transactions, but the current one can finish.
+    let error = client.transaction().await.unwrap_err();
+    assert!(matches!(error, QldbError::SessionPoolClosed(_)));
+
+    in_flight
+        .query("INSERT INTO Cars VALUE ?")
+        .param(car("1", "Ana"))
+        .execute()
+        .await?;
+    in_flight.commit().await?;
+
+    let report = closing.await;
+
+    assert_eq!(report.closed_sessions(), 3);
+    assert_eq!(report.failed_sessions(), 0);
+    assert_eq!(report.abandoned_sessions(), 0);
+    assert_eq!(fake.open_sessions(), 0);
+    assert_eq!(fake.documents("Cars").len(), 1);
+
+    let stats = client.pool_stats();
+    assert_eq!(stats.closed_sessions(), 3);
+    assert_eq!(stats.idle_sessions(), 0);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_close_abandons_sessions_in_use() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let mut client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .build_with_spawner(Arc::new(move |fut| {
+            async_std::task::spawn(Box::pin(fut));
+        }))?;
+
+    create_table(&client, "Cars").await?;
+
+    let transaction = client.transaction().await?;
+
+    let report = client.close_with_timeout(Duration::from_millis(50)).await;
+
+    assert_eq!(report.closed_sessions(), 0);
+    assert_eq!(report.abandoned_sessions(), 1);
+
+    // Too late, the session is not ended when given back. There is
+    // nothing to wait for, so the pool gets some time to end it wrongly.
+    transaction.rollback().await?;
+    async_std::task::sleep(Duration::from_millis(20)).await;
+
+    assert_eq!(fake.open_sessions(), 1);
+
+    // Closing again doesn't do anything.
+    assert_eq!(client.close().await.abandoned_sessions(), 0);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_close_doesnt_wait_for_unanswered_end_sessions() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    // QLDB never answers EndSession
+    let transport = InterceptingTransport::new(fake.clone(), |request| match request.end_session {
+        Some(_) => Intercept::Delay(Duration::from_secs(3600)),
+        None => Intercept::Forward,
+    });
+
+    let mut client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(transport))
+        .build()?;
+
+    let transactions = futures::future::try_join_all((0..3).map(|_| client.transaction())).await?;
+
+    for transaction in transactions {
+        transaction.rollback().await?;
+    }
+
+    wait_until(|| client.pool_stats().idle_sessions() == 3).await;
+
+    let started = Instant::now();
+    let report = client.close_with_timeout(Duration::from_millis(100)).await;
+
+    // The sessions are ended at the same time and the timeout covers
+    // all of them
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(report.closed_sessions(), 0);
+    assert_eq!(report.failed_sessions(), 3);
+    assert_eq!(report.abandoned_sessions(), 0);
+
+    Ok(())
+}
+
+/// Minimal pool that opens sessions on demand and keeps them forever.
+struct CustomPool {
+    transport: FakeQldb,
+    idle: Mutex<Vec<Session>>,
+    given_back: AtomicUsize,
+    is_closed: AtomicBool,
+}
+
+#[async_trait::async_trait]
+impl SessionPool for CustomPool {
+    async fn close(&self, _timeout: Duration) -> PoolCloseReport {
+        self.is_closed.store(true, Relaxed);
+
+        let idle: Vec<Session> = self.idle.lock().unwrap().drain(..).collect();
+
+        for session in &idle {
+            let request = SendCommandRequest {
+                session_token: Some(session.get_session_id().to_string()),
+                end_session: Some(EndSessionRequest {}),
+                ..Default::default()
+            };
+
+            self.transport.send_command(request).await.unwrap();
+        }
+
+        PoolCloseReport::new(idle.len(), 0, 0)
+    }
+
+    async fn get(&self, _options: &AcquireOptions) -> QldbResult<Session> {
+        if self.is_closed() {
+            return Err(QldbError::SessionPoolClosed(eyre::eyre!("Closed")));
+        }
+
+        if let Some(session) = self.idle.lock().unwrap().pop() {
+            return Ok(session);
+        }
+
+        let request = SendCommandRequest {
+            start_session: Some(StartSessionRequest {
+                ledger_name: "fake-ledger".to_string(),
+            }),
+            ..Default::default()
+        };
+
+        let result = self.transport.send_command(request).await?;
+        let token = result.start_session.and_then(|session| session.session_token).unwrap();
+
+        Ok(Session::new(token))
+    }
+
+    fn give_back(&self, session: Session) {
+        self.given_back.fetch_add(1, Relaxed);
+
+        if !session.is_poisoned() {
+            self.idle.lock().unwrap().push(session);
+        }
+    }
+
+    fn spawn(&self, task: BackgroundTask) {
+        async_std::task::spawn(task);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.is_closed.load(Relaxed)
+    }
+}
+
+#[async_std::test]
+async fn fake_custom_session_pool() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let pool = Arc::new(CustomPool {
+        transport: fake.clone(),
+        idle: Mutex::new(vec![]),
+        given_back: AtomicUsize::new(0),
+        is_closed: AtomicBool::new(false),
+    });
+
+    let mut client = QldbClient::with_pool(Arc::new(fake.clone()), "fake-ledger", pool.clone(), 3);
+
+    // The limit follows the size of the pool, not the internal pool default.
+    assert_eq!(client.concurrency_limit(), 3);
+
+    create_table(&client, "Cars").await?;
+    create_table(&client, "Owners").await?;
+
+    assert_eq!(pool.given_back.load(Relaxed), 2);
+    assert_eq!(fake.open_sessions(), 1);
+
+    // The default stats of a custom pool.
+    assert_eq!(client.pool_stats().created_sessions(), 0);
+
+    assert_eq!(client.close().await.closed_sessions(), 1);
+    assert_eq!(fake.open_sessions(), 0);
+
+    let error = client.transaction().await.unwrap_err();
+    assert!(matches!(error, QldbError::SessionPoolClosed(_)));
+
+    Ok(())
+}
+
+#[test]
+fn fake_tokio_pool_from_outside_the_runtime() -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let handle = runtime.handle().clone();
+    let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+
+    // The runtime is driven by another thread, this one isn't part of it.
+    let runtime_thread = std::thread::spawn(move || runtime.block_on(stopped));
+
+    futures::executor::block_on(async {
+        let fake = FakeQldb::new();
+
+        let mut client = QldbClient::builder("fake-ledger")
+            .transport(Arc::new(fake.clone()))
+            .build_with_tokio(handle)?;
+
+        create_table(&client, "Cars").await?;
+
+        assert_eq!(client.close().await.closed_sessions(), 1);
+        assert_eq!(fake.open_sessions(), 0);
+
+        Ok::<_, eyre::Report>(())
+    })?;
+
+    let _ = stop.send(());
+    let _ = runtime_thread.join();
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_async_std_pool() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .build_with_async_std()?;
+
+    create_table(&client, "Cars").await?;
+
+    assert_eq!(fake.documents("Cars").len(), 0);
+    assert_eq!(fake.open_sessions(), 1);
+
+    Ok(())
+}
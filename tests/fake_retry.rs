@@ -0,0 +1,208 @@
+mod utils;
+
+use eyre::Result;
+use ion_binary_rs::IonValue;
+use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+use qldb::transport::TransportError;
+use qldb::{QldbClient, QldbError, QldbErrorKind, QldbResult, RetryPolicy, Transaction};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed};
+use std::sync::Arc;
+use std::time::Duration;
+use utils::fake::{car, create_client, create_table};
+
+/// Updates the owner of the car in a transaction that commits before
+/// `tx`, so `tx` fails with an OCC conflict when it commits.
+async fn update_concurrently(client: &QldbClient, tx: &Transaction, owner: &str) -> QldbResult<()> {
+    tx.query("UPDATE Cars SET owner = 'Retried' WHERE plate = '1111'")
+        .execute()
+        .await?;
+
+    let statement = format!("UPDATE Cars SET owner = '{}' WHERE plate = '1111'", owner);
+
+    client
+        .transaction_within(|other| async move {
+            other.query(&statement).execute().await?;
+            Ok(())
+        })
+        .await
+}
+
+#[async_std::test]
+async fn fake_retry_after_occ_conflict() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?.with_retry_policy(RetryPolicy::new(2).base_delay(Duration::ZERO));
+
+    create_table(&client, "Cars").await?;
+    client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Cars VALUE ?")
+                .param(car("1111", "Ana"))
+                .execute()
+                .await?;
+            Ok(())
+        })
+        .await?;
+
+    let attempts = AtomicUsize::new(0);
+
+    client
+        .transaction_within_retry(|tx| {
+            let (client, attempts) = (&client, &attempts);
+
+            async move {
+                if attempts.fetch_add(1, Relaxed) == 0 {
+                    update_concurrently(client, &tx, "Bob").await
+                } else {
+                    tx.query("UPDATE Cars SET owner = 'Retried' WHERE plate = '1111'")
+                        .execute()
+                        .await?;
+                    Ok(())
+                }
+            }
+        })
+        .await?;
+
+    assert_eq!(attempts.load(Relaxed), 2);
+    assert_eq!(
+        fake.documents("Cars")[0]["owner"],
+        IonValue::String("Retried".to_string())
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_retries_exhausted() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?.with_retry_policy(RetryPolicy::new(2).base_delay(Duration::ZERO));
+
+    create_table(&client, "Cars").await?;
+    client
+        .transaction_within(|tx| async move {
+            tx.query("INSERT INTO Cars VALUE ?")
+                .param(car("1111", "Ana"))
+                .execute()
+                .await?;
+            Ok(())
+        })
+        .await?;
+
+    let attempts = AtomicUsize::new(0);
+
+    let error = client
+        .transaction_within_retry(|tx| {
+            let (client, attempts) = (&client, &attempts);
+
+            async move {
+                let owner = format!("Owner {}", attempts.fetch_add(1, Relaxed));
+                update_concurrently(client, &tx, &owner).await
+            }
+        })
+        .await
+        .unwrap_err();
+
+    match error {
+        QldbError::TransactionRetriesExhausted { attempts, source } => {
+            assert_eq!(attempts, 3);
+            assert_eq!(source.kind(), QldbErrorKind::OccConflict);
+        }
+        error => panic!("Unexpected error {:?}", error),
+    }
+
+    assert_eq!(attempts.load(Relaxed), 3);
+    assert_eq!(
+        fake.documents("Cars")[0]["owner"],
+        IonValue::String("Owner 2".to_string())
+    );
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_non_retryable_errors_are_not_retried() -> Result<()> {
+    let fake = FakeQldb::new();
+    let client = create_client(&fake)?.with_retry_policy(RetryPolicy::new(2).base_delay(Duration::ZERO));
+
+    let attempts = AtomicUsize::new(0);
+
+    let error = client
+        .transaction_within_retry(|tx| {
+            let attempts = &attempts;
+
+            async move {
+                attempts.fetch_add(1, Relaxed);
+                tx.query("SELECT * FROM Missing").execute().await
+            }
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.kind(), QldbErrorKind::BadRequest);
+    assert!(matches!(error, QldbError::SendCommandError(_)));
+    assert_eq!(attempts.load(Relaxed), 1);
+
+    Ok(())
+}
+
+/// Client whose first commit reaches the ledger, but not its answer.
+fn lose_first_commit(fake: &FakeQldb, policy: RetryPolicy) -> Result<QldbClient> {
+    let lost = AtomicBool::new(false);
+
+    let transport = InterceptingTransport::new(fake.clone(), move |request| {
+        if request.commit_transaction.is_some() && !lost.swap(true, Relaxed) {
+            return Intercept::LoseResponse(TransportError::Dispatch("Connection reset".to_string()));
+        }
+
+        Intercept::Forward
+    });
+
+    Ok(QldbClient::builder("fake-ledger")
+        .transport(Arc::new(transport))
+        .build()?
+        .with_retry_policy(policy))
+}
+
+async fn insert_car(client: &QldbClient) -> QldbResult<()> {
+    client
+        .transaction_within_retry(|tx| async move {
+            tx.query("INSERT INTO Cars VALUE ?")
+                .param(car("1111", "Ana"))
+                .execute()
+                .await?;
+            Ok(())
+        })
+        .await
+}
+
+#[async_std::test]
+async fn fake_unknown_commits_are_not_retried() -> Result<()> {
+    let fake = FakeQldb::new();
+    create_table(&create_client(&fake)?, "Cars").await?;
+
+    let client = lose_first_commit(&fake, RetryPolicy::new(2).base_delay(Duration::ZERO))?;
+
+    let error = insert_car(&client).await.unwrap_err();
+
+    assert_eq!(error.kind(), QldbErrorKind::Transport);
+    assert_eq!(fake.documents("Cars").len(), 1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_unknown_commits_retried_on_demand() -> Result<()> {
+    let fake = FakeQldb::new();
+    create_table(&create_client(&fake)?, "Cars").await?;
+
+    let policy = RetryPolicy::new(2)
+        .base_delay(Duration::ZERO)
+        .retry_unknown_commits(true);
+    let client = lose_first_commit(&fake, policy)?;
+
+    insert_car(&client).await?;
+
+    // The lost commit was applied, so the car is inserted twice
+    assert_eq!(fake.documents("Cars").len(), 2);
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use eyre::Result;
+use qldb::transport::{
+    QldbTransport, RusotoTransport, SendCommandRequest, ServiceError, StartSessionRequest, TransportError,
+};
+use rusoto_core::credential::StaticProvider;
+use rusoto_core::request::{DispatchSignedRequestFuture, HttpResponse};
+use rusoto_core::signature::SignedRequest;
+use rusoto_core::{Client, DispatchSignedRequest, Region};
+use std::time::Duration;
+
+/// Answers every request with the same HTTP response, as QLDB would.
+struct FixedResponse {
+    status: u16,
+    body: &'static str,
+}
+
+impl DispatchSignedRequest for FixedResponse {
+    fn dispatch(&self, _request: SignedRequest, _timeout: Option<Duration>) -> DispatchSignedRequestFuture {
+        let mut headers = http::HeaderMap::<String>::default();
+        headers.insert("x-amzn-RequestId", "REQUEST-1".to_string());
+
+        let response = HttpResponse {
+            status: http::StatusCode::from_u16(self.status).unwrap(),
+            body: self.body.as_bytes().to_vec().into(),
+            headers,
+        };
+
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+fn fixed_transport(status: u16, body: &'static str) -> RusotoTransport {
+    let credentials = StaticProvider::new_minimal("key".to_string(), "secret".to_string());
+    let client = Client::new_with(credentials, FixedResponse { status, body });
+
+    RusotoTransport::with_client(client, Region::EuCentral1)
+}
+
+fn start_session() -> SendCommandRequest {
+    SendCommandRequest {
+        start_session: Some(StartSessionRequest {
+            ledger_name: "rust-crate-test".to_string(),
+        }),
+        ..Default::default()
+    }
+}
+
+#[async_std::test]
+async fn fake_rusoto_service_errors_keep_the_request_id() -> Result<()> {
+    let transport = fixed_transport(
+        400,
+        r#"{"__type": "InvalidSessionException", "message": "Session expired"}"#,
+    );
+
+    let error = transport.send_command(start_session()).await.unwrap_err();
+
+    assert_eq!(error.request_id(), Some("REQUEST-1"));
+    assert!(matches!(
+        error,
+        TransportError::Service {
+            error: ServiceError::InvalidSession(message),
+            ..
+        } if message == "Session expired"
+    ));
+
+    let transport = fixed_transport(500, "Internal error");
+
+    let error = transport.send_command(start_session()).await.unwrap_err();
+
+    assert_eq!(error.request_id(), Some("REQUEST-1"));
+    assert!(matches!(error, TransportError::Unknown { status: 500, .. }));
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_rusoto_parses_the_result() -> Result<()> {
+    let transport = fixed_transport(200, r#"{"StartSession": {"SessionToken": "SESSION-1"}}"#);
+
+    let result = transport.send_command(start_session()).await?;
+
+    assert_eq!(
+        result.start_session.and_then(|session| session.session_token),
+        Some("SESSION-1".to_string())
+    );
+
+    Ok(())
+}
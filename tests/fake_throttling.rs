@@ -0,0 +1,205 @@
+mod utils;
+
+use eyre::Result;
+use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+use qldb::transport::ServiceError;
+use qldb::{AcquireOptions, QldbClient, QldbError, QldbErrorKind, ThrottlingPolicy};
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::sync::Arc;
+use std::time::Duration;
+use utils::fake::create_table;
+
+#[async_std::test]
+async fn fake_throttling_shrinks_and_recovers_concurrency() -> Result<()> {
+    let fake = FakeQldb::new();
+    let throttling = Arc::new(AtomicBool::new(false));
+    let throttled = throttling.clone();
+
+    // Rejects the statements with `LimitExceededException` while
+    // `throttling` is set
+    let transport = InterceptingTransport::new(fake.clone(), move |request| {
+        if request.execute_statement.is_some() && throttled.load(Relaxed) {
+            return Intercept::Fail(ServiceError::LimitExceeded("Too many requests".to_string()).into());
+        }
+
+        Intercept::Forward
+    });
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(transport))
+        .max_sessions(8)
+        .throttling_policy(ThrottlingPolicy::new().min_limit(2))
+        .build()?;
+
+    create_table(&client, "Cars").await?;
+    assert_eq!(client.concurrency_limit(), 8);
+
+    let first = client.transaction().await?;
+    let second = client.transaction().await?;
+
+    throttling.store(true, Relaxed);
+
+    let error = first.query("SELECT * FROM Cars").execute().await.unwrap_err();
+    assert_eq!(error.kind(), QldbErrorKind::LimitExceeded);
+    assert_eq!(client.concurrency_limit(), 4);
+
+    // It started before the limit was reduced, so it doesn't count
+    second.query("SELECT * FROM Cars").execute().await.unwrap_err();
+    assert_eq!(client.concurrency_limit(), 4);
+
+    drop((first, second));
+
+    client
+        .transaction_within(|tx| async move { tx.query("SELECT * FROM Cars").execute().await })
+        .await
+        .unwrap_err();
+    assert_eq!(client.concurrency_limit(), 2);
+
+    // It never goes below the minimum
+    client
+        .transaction_within(|tx| async move { tx.query("SELECT * FROM Cars").execute().await })
+        .await
+        .unwrap_err();
+    assert_eq!(client.concurrency_limit(), 2);
+
+    throttling.store(false, Relaxed);
+
+    let running = vec![client.transaction().await?, client.transaction().await?];
+
+    let error = client
+        .transaction_with_options(AcquireOptions::new().timeout(Duration::from_millis(50)))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::SessionAcquireTimeout(_)));
+
+    for transaction in running {
+        transaction.commit().await?;
+    }
+
+    let mut previous = client.concurrency_limit();
+
+    for _ in 0..100 {
+        client
+            .transaction_within(|tx| async move { tx.query("SELECT * FROM Cars").execute().await })
+            .await?;
+
+        let limit = client.concurrency_limit();
+        assert!(limit >= previous && limit <= 8);
+        previous = limit;
+    }
+
+    assert_eq!(client.concurrency_limit(), 8);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_rollbacks_raise_the_limit() -> Result<()> {
+    let fake = FakeQldb::new();
+    let throttling = Arc::new(AtomicBool::new(false));
+    let throttled = throttling.clone();
+
+    let transport = InterceptingTransport::new(fake.clone(), move |request| {
+        if request.execute_statement.is_some() && throttled.load(Relaxed) {
+            return Intercept::Fail(ServiceError::RateExceeded("Slow down".to_string()).into());
+        }
+
+        Intercept::Forward
+    });
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(transport))
+        .max_sessions(4)
+        .build()?;
+
+    create_table(&client, "Cars").await?;
+    throttling.store(true, Relaxed);
+
+    // The throttled transaction doesn't raise the limit when it finishes
+    let transaction = client.transaction().await?;
+    transaction.query("SELECT * FROM Cars").execute().await.unwrap_err();
+    transaction.rollback().await?;
+    assert_eq!(client.concurrency_limit(), 2);
+
+    throttling.store(false, Relaxed);
+
+    // Read-only traffic: 2 + 1/2 + 1/2.5...
+    for _ in 0..3 {
+        let transaction = client.transaction().await?;
+        transaction.query("SELECT * FROM Cars").execute().await?;
+        transaction.rollback().await?;
+    }
+
+    assert_eq!(client.concurrency_limit(), 3);
+
+    for _ in 0..20 {
+        client
+            .transaction_within(|tx| async move {
+                tx.query("SELECT * FROM Cars").execute().await?;
+                tx.rollback().await
+            })
+            .await?;
+    }
+
+    assert_eq!(client.concurrency_limit(), 4);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_cancelled_waiters_pass_the_slot_on() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .throttling_policy(ThrottlingPolicy::new().max_limit(1))
+        .build()?;
+
+    let running = client.transaction().await?;
+
+    let mut first_waiter = Box::pin(client.transaction());
+    assert!(futures::poll!(&mut first_waiter).is_pending());
+
+    let mut second_waiter = Box::pin(client.transaction());
+    assert!(futures::poll!(&mut second_waiter).is_pending());
+
+    // Only the first waiter is woken up, and it is cancelled before
+    // taking the slot.
+    running.commit().await?;
+    drop(first_waiter);
+
+    let transaction = async_std::future::timeout(Duration::from_secs(1), second_waiter).await??;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_freed_slots_go_to_the_waiters_first() -> Result<()> {
+    let fake = FakeQldb::new();
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .throttling_policy(ThrottlingPolicy::new().max_limit(1))
+        .build()?;
+
+    let running = client.transaction().await?;
+
+    let mut waiter = Box::pin(client.transaction());
+    assert!(futures::poll!(&mut waiter).is_pending());
+
+    // The slot is handed over to the waiter before it runs again, so a
+    // transaction arriving now cannot take it
+    running.commit().await?;
+
+    let error = client
+        .transaction_with_options(AcquireOptions::new().timeout(Duration::from_millis(50)))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, QldbError::SessionAcquireTimeout(_)));
+
+    let transaction = async_std::future::timeout(Duration::from_secs(1), waiter).await??;
+    transaction.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,218 @@
+mod utils;
+
+use eyre::Result;
+use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+use qldb::{QldbClient, QldbError, QldbResult, TimeoutPhase};
+use std::sync::Arc;
+use std::time::Duration;
+use utils::fake::{car, create_table, wait_until};
+
+/// Waits until the transactions cancelled or timed out are rolled back
+/// and their sessions are back in the pool.
+async fn wait_for_rollbacks(fake: &FakeQldb, client: &QldbClient) {
+    wait_until(|| {
+        let stats = client.pool_stats();
+
+        // The clients have a single session, which is out of use a bit
+        // before the pool has it back as idle
+        fake.open_transactions() == 0 && stats.in_use_sessions() == 0 && stats.idle_sessions() == 1
+    })
+    .await;
+}
+
+/// Takes 40ms to answer every command.
+fn slow_commands(fake: &FakeQldb) -> InterceptingTransport {
+    InterceptingTransport::new(fake.clone(), |_| Intercept::Delay(Duration::from_millis(40)))
+}
+
+#[async_std::test]
+async fn fake_cancelled_transactions_are_rolled_back() -> Result<()> {
+    let fake = FakeQldb::new().with_page_size(1);
+
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(slow_commands(&fake)))
+        .max_sessions(1)
+        .build()?;
+
+    create_table(&client, "Cars").await?;
+
+    for plate in ["1", "2"] {
+        client
+            .transaction_within(|tx| async move {
+                tx.query("INSERT INTO Cars VALUE ?")
+                    .param(car(plate, "Ana"))
+                    .execute()
+                    .await?;
+                Ok(())
+            })
+            .await?;
+    }
+
+    // Every command takes 40ms: StartTransaction, ExecuteStatement,
+    // FetchPage and CommitTransaction. It is cancelled in each of them.
+    for cancel_after in [20, 60, 100, 140] {
+        let cancelled = async_std::future::timeout(
+            Duration::from_millis(cancel_after),
+            client.transaction_within(|tx| async move {
+                let cars = tx.query("SELECT * FROM Cars").execute().await?;
+                Ok(cars.len())
+            }),
+        )
+        .await;
+
+        assert!(cancelled.is_err(), "cancelled after {}ms", cancel_after);
+
+        // The rollback is sent in the background.
+        wait_for_rollbacks(&fake, &client).await;
+
+        assert_eq!(fake.open_transactions(), 0, "cancelled after {}ms", cancel_after);
+        assert_eq!(client.pool_stats().in_use_sessions(), 0);
+        assert_eq!(client.pool_stats().idle_sessions(), 1);
+    }
+
+    // Cancelled while waiting for the only session.
+    let first = client.transaction().await?;
+
+    let waiting = async_std::future::timeout(Duration::from_millis(20), client.transaction()).await;
+    assert!(waiting.is_err());
+
+    drop(first);
+    wait_for_rollbacks(&fake, &client).await;
+
+    assert_eq!(fake.open_transactions(), 0);
+
+    // The same session keeps working.
+    let cars = client
+        .transaction_within(|tx| async move { Ok(tx.query("SELECT * FROM Cars").execute().await?.len()) })
+        .await?;
+
+    assert_eq!(cars, 2);
+    assert_eq!(fake.open_sessions(), 1);
+    assert_eq!(client.pool_stats().created_sessions(), 1);
+
+    Ok(())
+}
+
+async fn create_slow_commands_client(fake: &FakeQldb) -> Result<QldbClient> {
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(slow_commands(fake)))
+        .max_sessions(1)
+        .build()?;
+
+    create_table(&client, "Cars").await?;
+
+    for plate in ["1", "2"] {
+        client
+            .transaction_within(|tx| async move {
+                tx.query("INSERT INTO Cars VALUE ?")
+                    .param(car(plate, "Ana"))
+                    .execute()
+                    .await?;
+                Ok(())
+            })
+            .await?;
+    }
+
+    Ok(client)
+}
+
+fn timeout_phase<T>(result: QldbResult<T>) -> Option<TimeoutPhase> {
+    match result {
+        Err(QldbError::Timeout { phase, .. }) => Some(phase),
+        _ => None,
+    }
+}
+
+#[async_std::test]
+async fn fake_transaction_timeouts() -> Result<()> {
+    let fake = FakeQldb::new().with_page_size(1);
+    let client = create_slow_commands_client(&fake).await?;
+
+    // Every command takes 40ms: StartTransaction, ExecuteStatement,
+    // FetchPage and CommitTransaction.
+    let count = |tx: qldb::Transaction| async move { tx.query("SELECT COUNT(*) FROM Cars").count().await };
+    let select = |tx: qldb::Transaction| async move { Ok(tx.query("SELECT * FROM Cars").execute().await?.len()) };
+
+    let cars = client
+        .transaction_within_timeout(Duration::from_secs(1), select)
+        .await?;
+    assert_eq!(cars, 2);
+
+    let result = client
+        .transaction_within_timeout(Duration::from_millis(60), select)
+        .await;
+    assert_eq!(timeout_phase(result), Some(TimeoutPhase::Statement));
+    // Until the rollback is done the only session isn't available.
+    wait_for_rollbacks(&fake, &client).await;
+
+    let result = client
+        .transaction_within_timeout(Duration::from_millis(100), select)
+        .await;
+    assert_eq!(timeout_phase(result), Some(TimeoutPhase::FetchPage));
+    wait_for_rollbacks(&fake, &client).await;
+
+    let result = client
+        .transaction_within_timeout(Duration::from_millis(100), count)
+        .await;
+    assert_eq!(timeout_phase(result), Some(TimeoutPhase::Commit));
+    wait_for_rollbacks(&fake, &client).await;
+
+    let first = client.transaction().await?;
+    let result = client
+        .transaction_within_timeout(Duration::from_millis(20), count)
+        .await;
+    assert_eq!(timeout_phase(result), Some(TimeoutPhase::Acquire));
+    first.rollback().await?;
+
+    // The rollbacks are sent in the background.
+    wait_for_rollbacks(&fake, &client).await;
+
+    assert_eq!(client.pool_stats().created_sessions(), 1);
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn fake_statement_timeout() -> Result<()> {
+    let fake = FakeQldb::new().with_page_size(1);
+    let client = create_slow_commands_client(&fake).await?;
+
+    let transaction = client.transaction().await?;
+
+    let result = transaction
+        .query("SELECT * FROM Cars")
+        .timeout(Duration::from_millis(60))
+        .execute()
+        .await;
+    assert_eq!(timeout_phase(result), Some(TimeoutPhase::FetchPage));
+
+    let error = transaction.commit().await.unwrap_err();
+    assert!(matches!(error, QldbError::TransactionAlreadyRollback));
+
+    let transaction = client.transaction().await?;
+
+    let mut cursor = transaction
+        .query("SELECT * FROM Cars")
+        .timeout(Duration::from_millis(60))
+        .get_cursor()?;
+
+    // Every page gets its own deadline.
+    assert_eq!(cursor.load_more().await?.map(|page| page.len()), Some(1));
+    assert_eq!(cursor.load_more().await?.map(|page| page.len()), Some(1));
+
+    transaction.commit().await?;
+
+    let transaction = client.transaction().await?;
+
+    let result = transaction
+        .query("SELECT * FROM Cars")
+        .timeout(Duration::from_millis(20))
+        .get_cursor()?
+        .load_more()
+        .await;
+    assert_eq!(timeout_phase(result), Some(TimeoutPhase::Statement));
+
+    wait_for_rollbacks(&fake, &client).await;
+
+    Ok(())
+}
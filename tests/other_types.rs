@@ -1,3 +1,5 @@
+#![allow(clippy::useless_vec)]
+
 mod utils;
 use bigdecimal::BigDecimal;
 use eyre::Result;
@@ -1,3 +1,5 @@
+#![allow(clippy::nonminimal_bool)]
+
 mod utils;
 use eyre::Result;
 use ion_binary_rs::IonValue;
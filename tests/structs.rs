@@ -1,3 +1,5 @@
+#![allow(clippy::legacy_numeric_constants)]
+
 mod utils;
 use bigdecimal::BigDecimal;
 use eyre::Result;
@@ -1,10 +1,7 @@
 mod utils;
 use eyre::Result;
 use ion_binary_rs::IonValue;
-use qldb::QldbClient;
-use qldb::QldbError::SendCommandError;
-use rusoto_core::RusotoError::Service;
-use rusoto_qldb_session::SendCommandError::OccConflict;
+use qldb::{QldbClient, QldbErrorKind};
 use std::collections::HashMap;
 use utils::ensure_test_table;
 
@@ -167,7 +164,7 @@ async fn qldb_transaction_occ_conflict() -> Result<()> {
     }
 
     match result.0 {
-        Err(SendCommandError(Service(OccConflict(_)))) => {}
+        Err(error) if error.kind() == QldbErrorKind::OccConflict => {}
         _ => panic!("Non OCC error on the OCC test!"),
     }
 
@@ -1,4 +1,4 @@
-#![allow(dead_code)]
+#![allow(dead_code, clippy::useless_conversion)]
 
 use crate::utils::ensure_test_table;
 use ion_binary_rs::IonValue;
@@ -0,0 +1,88 @@
+use eyre::Result;
+use ion_binary_rs::IonValue;
+use qldb::testing::{FakeQldb, Intercept, InterceptingTransport};
+use qldb::transport::TransportError;
+use qldb::QldbClient;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[allow(dead_code)]
+pub fn create_client(fake: &FakeQldb) -> Result<QldbClient> {
+    let client = QldbClient::builder("fake-ledger")
+        .transport(Arc::new(fake.clone()))
+        .build()?;
+
+    Ok(client)
+}
+
+#[allow(dead_code)]
+pub async fn create_table(client: &QldbClient, table: &str) -> Result<()> {
+    let statement = format!("CREATE TABLE {}", table);
+
+    client
+        .transaction_within(|tx| async move {
+            tx.query(&statement).execute().await?;
+            Ok(())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub fn car(plate: &str, owner: &str) -> HashMap<String, IonValue> {
+    let mut car = HashMap::new();
+    car.insert("plate".to_string(), IonValue::String(plate.to_string()));
+    car.insert("owner".to_string(), IonValue::String(owner.to_string()));
+    car
+}
+
+/// Waits until `condition` holds, for example until the pool took back
+/// a session in the background. Panics after 5 seconds.
+#[allow(dead_code)]
+pub async fn wait_until<F: Fn() -> bool>(condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while !condition() {
+        assert!(Instant::now() < deadline, "The condition didn't hold after 5 seconds");
+        async_std::task::sleep(Duration::from_millis(1)).await;
+    }
+}
+
+/// Statements sent to QLDB and how many params they had.
+pub type Statements = Arc<Mutex<Vec<(String, usize)>>>;
+
+/// Transport that keeps the statements sent through it.
+#[allow(dead_code)]
+pub fn record_statements(fake: &FakeQldb) -> (Arc<InterceptingTransport>, Statements) {
+    let statements = Arc::new(Mutex::new(vec![]));
+    let recorded = statements.clone();
+
+    let transport = InterceptingTransport::new(fake.clone(), move |request| {
+        if let Some(execute) = &request.execute_statement {
+            let params = execute.parameters.as_ref().map_or(0, Vec::len);
+            recorded.lock().unwrap().push((execute.statement.clone(), params));
+        }
+
+        Intercept::Forward
+    });
+
+    (Arc::new(transport), statements)
+}
+
+/// Fails the first attempt to start a session, as when the credentials
+/// cannot be loaded.
+#[allow(dead_code)]
+pub fn fail_first_session(fake: &FakeQldb) -> InterceptingTransport {
+    let failed = AtomicBool::new(false);
+
+    InterceptingTransport::new(fake.clone(), move |request| {
+        if request.start_session.is_some() && !failed.swap(true, Relaxed) {
+            return Intercept::Fail(TransportError::Credentials("No credentials".to_string()));
+        }
+
+        Intercept::Forward
+    })
+}
@@ -1,4 +1,5 @@
 pub mod cursor_utils;
+pub mod fake;
 
 use eyre::Result;
 use ion_binary_rs::IonValue;